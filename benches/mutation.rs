@@ -0,0 +1,75 @@
+/*
+   DeepSAFL - mutation benchmarks
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+#[macro_use]
+extern crate criterion;
+extern crate rand;
+extern crate seed_management;
+
+use criterion::{BenchmarkId, Criterion, Throughput};
+use seed_management::seed_generator::mutator;
+use seed_management::seed_generator::MutationBuffer;
+
+const SEED_SIZES: [usize; 3] = [1 << 10, 1 << 16, 1 << 20];
+
+fn make_seed(seed_len: usize) -> Vec<u8> {
+    (0..seed_len).map(|i| i as u8).collect()
+}
+
+//One input of the flip1 stage: a fresh copy per input vs. undo in a reused buffer
+fn bench_flip1(c: &mut Criterion) {
+    let mut group = c.benchmark_group("flip1");
+    for &seed_len in SEED_SIZES.iter() {
+        let input_seed = make_seed(seed_len);
+        let bit_len = (seed_len as u64) << 3;
+        group.throughput(Throughput::Elements(1));
+        group.bench_with_input(BenchmarkId::new("clone", seed_len), &input_seed, |b, input_seed| {
+            let mut pos = 0;
+            b.iter(|| {
+                let seed = mutator::flip_one_bit(input_seed, pos).unwrap();
+                pos = (pos + 1) % bit_len;
+                seed
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("in_place", seed_len), &input_seed, |b, input_seed| {
+            let mut seed_buf = MutationBuffer::with_capacity(seed_len);
+            let mut pos = 0;
+            b.iter(|| {
+                seed_buf.mutate_at(input_seed, pos >> 3, 2, |buf| mutator::flip_bits_in_place(buf, pos, 1)).unwrap();
+                pos = (pos + 1) % bit_len;
+                seed_buf.as_slice().len()
+            });
+        });
+    }
+    group.finish();
+}
+
+//One input of the havoc stage, which always needs the seed copied in again
+fn bench_havoc(c: &mut Criterion) {
+    let mut group = c.benchmark_group("havoc");
+    for &seed_len in SEED_SIZES.iter() {
+        let input_seed = make_seed(seed_len);
+        group.throughput(Throughput::Elements(1));
+        group.bench_with_input(BenchmarkId::new("clone", seed_len), &input_seed, |b, input_seed| {
+            let mut rang = rand::thread_rng();
+            b.iter(|| mutator::havoc_mutate(input_seed, &mut rang).unwrap());
+        });
+        group.bench_with_input(BenchmarkId::new("in_place", seed_len), &input_seed, |b, input_seed| {
+            let mut rang = rand::thread_rng();
+            let mut seed_buf = MutationBuffer::with_capacity(seed_len);
+            b.iter(|| {
+                seed_buf.mutate_all(input_seed, |buf| mutator::havoc_mutate_in_place(buf, &mut rang)).unwrap();
+                seed_buf.as_slice().len()
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_flip1, bench_havoc);
+criterion_main!(benches);
@@ -0,0 +1,75 @@
+extern crate env_logger;
+extern crate getopts;
+extern crate seed_management;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use getopts::Options;
+
+use seed_management::cli;
+use seed_management::corpus;
+use seed_management::executor::{Executor, Forkserver};
+use seed_management::feedback::ExecStatus;
+
+fn usage(program: &str, opts: &Options)->String {
+    cli::usage(program, "-i dir -o dir [ options ]",
+               "Copies the smallest subset of the inputs in -i that covers the same edges to -o.\n\
+                With -C only the inputs that crash are kept, as the corpus of afl-fuzz -C.",
+               opts)
+}
+
+fn run(args: &[String])->Result<(), String> {
+    let program = args[0].clone();
+    let mut opts = Options::new();
+    opts.optopt("i", "", "input directory with the corpus", "dir");
+    opts.optopt("o", "", "output directory for the minimized corpus", "dir");
+    cli::target_opts(&mut opts);
+    opts.optflag("C", "", "crash mode, keep the inputs that crash instead of the ones that run fine");
+    opts.optflag("h", "help", "print this help");
+
+    let matches = opts.parse(&args[1..]).map_err(|e| format!("{}\n\n{}", e, usage(&program, &opts)))?;
+    if matches.opt_present("h") {
+        println!("{}", usage(&program, &opts));
+        return Ok(());
+    }
+    let (in_dir, out_dir) = match (matches.opt_str("i"), matches.opt_str("o")) {
+        (Some(in_dir), Some(out_dir)) => (in_dir, out_dir),
+        _ => return Err(format!("-i and -o are required\n\n{}", usage(&program, &opts))),
+    };
+    if matches.free.is_empty() {
+        return Err(format!("no target to run\n\n{}", usage(&program, &opts)));
+    }
+    let cur_input = Path::new(&out_dir).join(".cur_input");
+    let options = cli::forkserver_options(&matches, cur_input.clone())?;
+    let kept_status = if matches.opt_present("C") { ExecStatus::Crash } else { ExecStatus::Normal };
+
+    let inputs = corpus::load_inputs(&in_dir).map_err(|e| format!("cannot read {}: {}", in_dir, e))?;
+    if inputs.is_empty() {
+        return Err(format!("no usable inputs in {}", in_dir));
+    }
+    fs::create_dir_all(&out_dir).map_err(|e| format!("cannot create {}: {}", out_dir, e))?;
+    let mut executor = Forkserver::start(&matches.free, options).map_err(|e| e.to_string())?;
+
+    let all: Vec<&[u8]> = inputs.iter().map(|(_, input)| &input[..]).collect();
+    let picked = corpus::minimize(&all, kept_status, |input, cov_info| executor.run_target(input, cov_info))
+        .map_err(|e| e.to_string())?;
+    let _ = fs::remove_file(&cur_input);
+    corpus::write_inputs(&out_dir, &inputs, &picked).map_err(|e| format!("cannot write to {}: {}", out_dir, e))?;
+
+    let kept: Vec<&[u8]> = picked.iter().map(|&index| &inputs[index].1[..]).collect();
+    eprintln!("[+] kept {} of {} inputs ({} of {} bytes) in {}",
+              kept.len(), all.len(), corpus::total_len(&kept), corpus::total_len(&all), out_dir);
+    Ok(())
+}
+
+fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    let args: Vec<String> = env::args().collect();
+    if let Err(e) = run(&args) {
+        eprintln!("[-] {}", e);
+        process::exit(1);
+    }
+}
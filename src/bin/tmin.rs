@@ -0,0 +1,73 @@
+extern crate env_logger;
+extern crate getopts;
+extern crate seed_management;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use getopts::Options;
+
+use seed_management::cli;
+use seed_management::tmin;
+use seed_management::executor::{Executor, Forkserver};
+
+fn usage(program: &str, opts: &Options)->String {
+    cli::usage(program, "-i file -o file [ options ]",
+               "Writes the smallest input that has the same coverage as -i to -o,\n\
+                or one that still crashes or hangs if -i does.",
+               opts)
+}
+
+fn run(args: &[String])->Result<(), String> {
+    let program = args[0].clone();
+    let mut opts = Options::new();
+    opts.optopt("i", "", "input file to minimize", "file");
+    opts.optopt("o", "", "file for the minimized input", "file");
+    cli::target_opts(&mut opts);
+    opts.optflag("h", "help", "print this help");
+
+    let matches = opts.parse(&args[1..]).map_err(|e| format!("{}\n\n{}", e, usage(&program, &opts)))?;
+    if matches.opt_present("h") {
+        println!("{}", usage(&program, &opts));
+        return Ok(());
+    }
+    let (in_file, out_file) = match (matches.opt_str("i"), matches.opt_str("o")) {
+        (Some(in_file), Some(out_file)) => (in_file, out_file),
+        _ => return Err(format!("-i and -o are required\n\n{}", usage(&program, &opts))),
+    };
+    if matches.free.is_empty() {
+        return Err(format!("no target to run\n\n{}", usage(&program, &opts)));
+    }
+    //the target reads a file next to the output, so several tmins can run in one directory
+    let out_path = Path::new(&out_file);
+    let cur_input = out_path.with_file_name(format!(".{}.cur_input",
+        out_path.file_name().map_or("tmin".into(), |name| name.to_string_lossy())));
+    let options = cli::forkserver_options(&matches, cur_input.clone())?;
+
+    let input = fs::read(&in_file).map_err(|e| format!("cannot read {}: {}", in_file, e))?;
+    if input.is_empty() {
+        return Err(format!("{} is empty", in_file));
+    }
+    let mut executor = Forkserver::start(&matches.free, options).map_err(|e| e.to_string())?;
+
+    let mut execs = 0;
+    let minimized = tmin::minimize(&input, |input, cov_info| {
+        execs += 1;
+        executor.run_target(input, cov_info)
+    }).map_err(|e| e.to_string())?;
+    let _ = fs::remove_file(&cur_input);
+    fs::write(&out_file, &minimized).map_err(|e| format!("cannot write {}: {}", out_file, e))?;
+    eprintln!("[+] {} bytes down to {} in {} execs, written to {}", input.len(), minimized.len(), execs, out_file);
+    Ok(())
+}
+
+fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    let args: Vec<String> = env::args().collect();
+    if let Err(e) = run(&args) {
+        eprintln!("[-] {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,104 @@
+/*
+   DeepSAFL - calibration of new seeds
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+// A new seed is run CAL_CYCLES times. An edge whose hit count bucket is not the
+// same in every run is variable: the target does something nondeterministic there
+// (threads, time, randomness), so the edge says nothing about the input. Once a
+// variable edge shows up the seed gets CAL_CYCLES_LONG runs to find the others.
+
+use config;
+use feedback::ExecInfo;
+use tmin::count_class;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Calibration {
+    //average time of a run
+    pub exec_us: u64,
+    //edges covered by the first run
+    pub bitmap_size: u32,
+    //edges that changed between runs, in increasing order
+    pub var_edges: Vec<usize>,
+}
+
+//Run input a few times, run_target fills in the (cleared) coverage of a run
+pub fn calibrate<F, E>(input: &[u8], mut run_target: F)->Result<Calibration, E>
+    where F: FnMut(&[u8], &mut [u32; config::MAP_SIZE])->Result<ExecInfo, E> {
+    let mut cov_info = Box::new([0u32; config::MAP_SIZE]);
+    let mut first_classes = vec![0u8; config::MAP_SIZE];
+    let mut is_var = vec![false; config::MAP_SIZE];
+    let mut cycles = config::CAL_CYCLES;
+    let mut total_us = 0;
+    let mut bitmap_size = 0;
+
+    let mut cycle = 0;
+    while cycle < cycles {
+        for cov in cov_info.iter_mut() {
+            *cov = 0;
+        }
+        total_us += run_target(input, &mut cov_info)?.exec_us;
+        if cycle == 0 {
+            for (class, cov) in first_classes.iter_mut().zip(cov_info.iter()) {
+                *class = count_class(*cov);
+            }
+            bitmap_size = cov_info.iter().filter(|cov| **cov != 0).count() as u32;
+        }
+        else {
+            for edge in 0..config::MAP_SIZE {
+                if !is_var[edge] && count_class(cov_info[edge]) != first_classes[edge] {
+                    is_var[edge] = true;
+                    cycles = config::CAL_CYCLES_LONG;
+                }
+            }
+        }
+        cycle += 1;
+    }
+
+    Ok(Calibration {
+        exec_us: total_us / cycles as u64,
+        bitmap_size,
+        var_edges: (0..config::MAP_SIZE).filter(|&edge| is_var[edge]).collect(),
+    })
+}
+
+//An edge that varies between runs, and the pool indexes of the seeds it varies for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariableEdge {
+    pub edge: usize,
+    pub seeds: Vec<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_variable_edges() {
+        //edge 1 is always hit, edge 2 every other run, edge 3 only in the 20th run
+        let mut runs = 0;
+        let calibration = calibrate(b"x", |_input, cov_info| {
+            runs += 1;
+            cov_info[1] = 1;
+            cov_info[2] = runs % 2;
+            cov_info[3] = if runs == 20 { 1 } else { 0 };
+            Ok::<ExecInfo, ()>(ExecInfo::new(Default::default(), 10 * runs as u64))
+        }).unwrap();
+        assert_eq!(runs, config::CAL_CYCLES_LONG);
+        assert_eq!(calibration.var_edges, vec![2, 3]);
+        assert_eq!(calibration.bitmap_size, 2);
+        assert_eq!(calibration.exec_us, 205);
+
+        let mut runs = 0;
+        let calibration = calibrate(b"x", |_input, cov_info| {
+            runs += 1;
+            cov_info[1] = 3;
+            Ok::<ExecInfo, ()>(ExecInfo::default())
+        }).unwrap();
+        assert_eq!(runs, config::CAL_CYCLES);
+        assert!(calibration.var_edges.is_empty());
+    }
+}
@@ -0,0 +1,77 @@
+/*
+   DeepSAFL - command line of the binaries
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+// The fuzzer, cmin and tmin all run a target through the fork server. They take it
+// the same way, after --, and share the options that limit a run of it (-t, -m).
+
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use getopts::{Matches, Options};
+
+use config;
+use executor::ForkserverOptions;
+
+//The help text of program, args are its arguments before the target
+pub fn usage(program: &str, args: &str, about: &str, opts: &Options)->String {
+    let brief = format!("Usage: {} {} -- /path/to/target_app [ ... ]\n\n{}", program, args, about);
+    opts.usage(&brief)
+}
+
+pub fn parse_number<T: FromStr>(value: &str, what: &str)->Result<T, String> {
+    value.parse().map_err(|_| format!("bad {} '{}'", what, value))
+}
+
+//Add -t and -m, the limits of a run of the target
+pub fn target_opts(opts: &mut Options) {
+    opts.optopt("t", "", &format!("timeout for each run (default {})", config::EXEC_TIMEOUT), "msec");
+    opts.optopt("m", "", &format!("memory limit for the target, or none (default {})", config::MEM_LIMIT), "megs");
+}
+
+//The fork server options that -t and -m ask for, the target reads its inputs from input_file
+pub fn forkserver_options(matches: &Matches, input_file: PathBuf)->Result<ForkserverOptions, String> {
+    let timeout = match matches.opt_str("t") {
+        Some(timeout) => parse_number(&timeout, "timeout")?,
+        None => config::EXEC_TIMEOUT,
+    };
+    let mem_limit_mb = match matches.opt_str("m") {
+        Some(ref mem_limit) if mem_limit == "none" => 0,
+        Some(mem_limit) => parse_number(&mem_limit, "memory limit")?,
+        None => config::MEM_LIMIT,
+    };
+    Ok(ForkserverOptions {
+        timeout: Duration::from_millis(timeout),
+        mem_limit_mb,
+        input_file: Some(input_file),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str])->Result<ForkserverOptions, String> {
+        let mut opts = Options::new();
+        target_opts(&mut opts);
+        let matches = opts.parse(args).map_err(|e| e.to_string())?;
+        forkserver_options(&matches, PathBuf::from(".cur_input"))
+    }
+
+    #[test]
+    fn limits_of_the_target() {
+        let options = parse(&[]).unwrap();
+        assert_eq!(options.timeout, Duration::from_millis(config::EXEC_TIMEOUT));
+        assert_eq!(options.mem_limit_mb, config::MEM_LIMIT);
+        assert_eq!(options.input_file, Some(PathBuf::from(".cur_input")));
+
+        let options = parse(&["-t", "200", "-m", "none"]).unwrap();
+        assert_eq!((options.timeout, options.mem_limit_mb), (Duration::from_millis(200), 0));
+        assert_eq!(parse(&["-m", "lots"]).err(), Some("bad memory limit 'lots'".to_string()));
+    }
+}
@@ -88,6 +88,35 @@ pub const SPLICE_HAVOC:u8 = 32;
 pub const HAVOC_STACK_POW2:u8 = 7;
 pub const HAVOC_WAY:u8 = 19;
 
+// Text mode: a seed with at least TEXT_MIN_PRINTABLE printable bytes is text, and its
+// havoc also picks among the HAVOC_TEXT_WAY text operations. These write the
+// TEXT_NUMBERS over the numbers of the text and insert or delete TEXT_SEPARATORS:
+
+pub const HAVOC_TEXT_WAY:u8 = 6;
+pub const TEXT_MIN_PRINTABLE:f64 = 0.9;
+pub const TEXT_NUMBERS: &[&str] = &[
+    "0", "1", "-1", "127", "128", "-129", "255", "256", "32767", "32768", "-32769", "65535", "65536",
+    "2147483647", "2147483648", "-2147483649", "4294967295", "4294967296",
+    "9223372036854775807", "9223372036854775808", "18446744073709551616", "1e308", "0.0", "-0",
+];
+pub const TEXT_SEPARATORS: &[u8] = b" \t\n,;:.=&|/\\'\"()[]{}<>";
+
+// Share of the bandit havoc scheduling that still picks operations uniformly,
+// so an operation that had bad luck early gets tried again:
+
+pub const HAVOC_BANDIT_EXPLORE:f64 = 0.1;
+
+// Most replacements the input-to-state stage makes from the comparisons of a seed:
+
+pub const CMPLOG_MAX_REPLACEMENTS:usize = 4096;
+
+// Field inference: a text field is at least FIELD_MIN_TEXT printable bytes, and a
+// 16 or 32 bit havoc operation goes on an inferred integer with FIELD_HAVOC_BIAS
+// probability if the seed has one of its width:
+
+pub const FIELD_MIN_TEXT:usize = 4;
+pub const FIELD_HAVOC_BIAS:f64 = 0.5;
+
 // Caps on block sizes for cloning and deletion operations. Each of these
 // ranges has a 33% probability of getting picked, except for the first
 // two cycles where smaller blocks are favored:
@@ -97,5 +126,35 @@ pub const HAVOC_BLK_MEDIUM: u64 = 128;
 pub const HAVOC_BLK_LARGE: u64 = 1500;
 pub const HAVOC_BLK_XL:u64 = 32768; // Extra-large blocks, selected very rarely (<5% of the time)
 
-pub const MAX_FILE:u64 = 1*1024*1024; //Maximum size of input file, in bytes (keep under 100MB)
+pub const MAX_FILE:u64 = 1024*1024; //Maximum size of input file, in bytes (keep under 100MB)
 pub const MAP_SIZE:usize = 1 << 16;
+
+pub const EXEC_TIMEOUT:u64 = 1000; //Default timeout for fuzzed code (milliseconds)
+pub const MEM_LIMIT:u64 = 50; //Default memory limit for child process (MB)
+pub const FORK_WAIT_MULT:u32 = 10; //The forkserver may take this many timeouts to start
+pub const FORKSRV_FD:i32 = 198; //Control pipe of the forkserver, the status pipe is FORKSRV_FD + 1
+pub const SHM_ENV_VAR: &str = "__AFL_SHM_ID"; //Environment variable used to pass the coverage map id
+pub const MAX_DICT_FILE:u64 = 128; //Maximum length of a dictionary token
+pub const STATS_UPDATE_SEC:u64 = 60; //How often fuzzer_stats is rewritten
+pub const PLOT_UPDATE_SEC:u64 = 5; //How often a line is added to plot_data
+pub const UI_TARGET_HZ:u64 = 1; //Refreshes of the status screen per second
+
+// Trimming: blocks of 1/TRIM_START_STEPS of the input are removed first, halving
+// down to 1/TRIM_END_STEPS but never below TRIM_MIN_BYTES
+
+pub const TRIM_MIN_BYTES:usize = 4;
+pub const TRIM_START_STEPS:usize = 16;
+pub const TRIM_END_STEPS:usize = 1024;
+
+pub const CAL_CYCLES:u32 = 8; //Runs of a new seed in calibration
+pub const CAL_CYCLES_LONG:u32 = 40; //Runs when the seed turns out to be variable
+
+// Grammar mode: derivations are at most GRAMMAR_MAX_DEPTH rules deep and take the
+// shortest way to end after GRAMMAR_MAX_NODES rules, recursion repeats a rule up to
+// GRAMMAR_MAX_RECURSION more times, and every seed gets GRAMMAR_ROUNDS inputs in
+// a row:
+
+pub const GRAMMAR_MAX_DEPTH:usize = 16;
+pub const GRAMMAR_MAX_NODES:usize = 1000;
+pub const GRAMMAR_MAX_RECURSION:u32 = 4;
+pub const GRAMMAR_ROUNDS:u32 = 256;
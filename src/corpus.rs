@@ -0,0 +1,184 @@
+/*
+   DeepSAFL - corpus loading and minimisation
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+// minimize works like afl-cmin: every edge is kept by the smallest input that
+// covers it, going from the rarest edge to the most common one, and an input that
+// is picked for one edge also keeps all its other edges. Then the picked inputs
+// whose edges are all kept by other picked ones are dropped again, biggest first.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use config;
+use feedback::{ExecInfo, ExecStatus};
+
+//The inputs in dir with their file names, sorted by name.
+//Hidden files, empty files and files bigger than MAX_FILE are skipped.
+pub fn load_inputs<P: AsRef<Path>>(dir: P)->io::Result<Vec<(String, Vec<u8>)>> {
+    let mut inputs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') || !entry.file_type()?.is_file() {
+            continue;
+        }
+        let input = fs::read(entry.path())?;
+        if input.is_empty() {
+            warn!("skipping empty input {}", name);
+            continue;
+        }
+        if input.len() as u64 > config::MAX_FILE {
+            warn!("skipping {}, it is bigger than {} bytes", name, config::MAX_FILE);
+            continue;
+        }
+        inputs.push((name, input));
+    }
+    inputs.sort();
+    Ok(inputs)
+}
+
+//Indexes of a subset of inputs that covers the same edges, in increasing order.
+//run_target runs an input and fills in its (cleared) coverage; inputs that do not
+//end with kept_status (ExecStatus::Normal, or Crash for a corpus of crashes) are left out.
+pub fn minimize<T, F, E>(inputs: &[T], kept_status: ExecStatus, mut run_target: F)->Result<Vec<usize>, E>
+    where T: AsRef<[u8]>, F: FnMut(&[u8], &mut [u32; config::MAP_SIZE])->Result<ExecInfo, E> {
+    let mut cov_info = Box::new([0u32; config::MAP_SIZE]);
+    let mut edges_of = Vec::with_capacity(inputs.len());
+    for input in inputs.iter() {
+        for cov in cov_info.iter_mut() {
+            *cov = 0;
+        }
+        let exec_info = run_target(input.as_ref(), &mut cov_info)?;
+        let edges: Vec<usize> = if exec_info.status == kept_status {
+            cov_info.iter().enumerate().filter(|&(_, cov)| *cov != 0).map(|(edge, _)| edge).collect()
+        }
+        else {
+            Vec::new()
+        };
+        edges_of.push(edges);
+    }
+
+    //the smallest input of each edge, and how many inputs have it
+    let mut by_size: Vec<usize> = (0..inputs.len()).collect();
+    by_size.sort_by_key(|&index| (inputs[index].as_ref().len(), index));
+    let mut best_input: Vec<Option<usize>> = vec![None; config::MAP_SIZE];
+    let mut edge_cnt = vec![0u32; config::MAP_SIZE];
+    for &index in by_size.iter() {
+        for &edge in edges_of[index].iter() {
+            if best_input[edge].is_none() {
+                best_input[edge] = Some(index);
+            }
+            edge_cnt[edge] += 1;
+        }
+    }
+
+    let mut edges: Vec<usize> = (0..config::MAP_SIZE).filter(|&edge| edge_cnt[edge] != 0).collect();
+    edges.sort_by_key(|&edge| (edge_cnt[edge], edge));
+    let mut covered = vec![false; config::MAP_SIZE];
+    let mut is_picked = vec![false; inputs.len()];
+    for edge in edges {
+        if covered[edge] {
+            continue;
+        }
+        if let Some(index) = best_input[edge] {
+            is_picked[index] = true;
+            for &other_edge in edges_of[index].iter() {
+                covered[other_edge] = true;
+            }
+        }
+    }
+
+    let mut picked_cnt = vec![0u32; config::MAP_SIZE];
+    for index in (0..inputs.len()).filter(|&index| is_picked[index]) {
+        for &edge in edges_of[index].iter() {
+            picked_cnt[edge] += 1;
+        }
+    }
+    for &index in by_size.iter().rev() {
+        if is_picked[index] && edges_of[index].iter().all(|&edge| picked_cnt[edge] > 1) {
+            is_picked[index] = false;
+            for &edge in edges_of[index].iter() {
+                picked_cnt[edge] -= 1;
+            }
+        }
+    }
+    Ok((0..inputs.len()).filter(|&index| is_picked[index]).collect())
+}
+
+//Write the picked inputs to out_dir under their names
+pub fn write_inputs<P: AsRef<Path>>(out_dir: P, inputs: &[(String, Vec<u8>)], picked: &[usize])->io::Result<()> {
+    let out_dir = out_dir.as_ref();
+    fs::create_dir_all(out_dir)?;
+    for &index in picked.iter() {
+        let (name, input) = &inputs[index];
+        fs::write(out_dir.join(name), input)?;
+    }
+    Ok(())
+}
+
+//Total size of the inputs, for the summary of a minimisation
+pub fn total_len<T: AsRef<[u8]>>(inputs: &[T])->usize {
+    inputs.iter().fold(0, |total, input| total + input.as_ref().len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile;
+
+    //each byte of an input is an edge, a 0xff byte crashes
+    fn run_target(input: &[u8], cov_info: &mut [u32; config::MAP_SIZE])->Result<ExecInfo, ()> {
+        for &byte in input.iter() {
+            cov_info[byte as usize] = 1;
+        }
+        let status = if input.contains(&0xff) { ExecStatus::Crash } else { ExecStatus::Normal };
+        Ok(ExecInfo::new(status, 0))
+    }
+
+    #[test]
+    fn keeps_all_edges_with_small_inputs() {
+        let inputs: Vec<Vec<u8>> = vec![
+            vec![1, 2, 3, 4],
+            vec![1, 2],
+            vec![3],
+            vec![2, 1],
+            vec![4, 4, 4, 4, 4, 4],
+            vec![5, 0xff],
+        ];
+        //[1,2,3,4] is the smallest input with edge 4 and has all the other ones
+        assert_eq!(minimize(&inputs, ExecStatus::Normal, run_target).unwrap(), vec![0]);
+
+        let inputs: Vec<Vec<u8>> = vec![vec![1, 2, 3], vec![1, 1], vec![2], vec![3, 3, 3, 3]];
+        assert_eq!(minimize(&inputs, ExecStatus::Normal, run_target).unwrap(), vec![0]);
+
+        //the smaller input wins among the ones with the same edges
+        let inputs: Vec<Vec<u8>> = vec![vec![1, 2, 1], vec![2, 1], vec![3]];
+        assert_eq!(minimize(&inputs, ExecStatus::Normal, run_target).unwrap(), vec![1, 2]);
+
+        //a corpus of crashes keeps the crashes only
+        let inputs: Vec<Vec<u8>> = vec![vec![1, 0xff], vec![1, 2], vec![0xff], vec![2, 0xff, 2]];
+        assert_eq!(minimize(&inputs, ExecStatus::Crash, run_target).unwrap(), vec![0, 3]);
+    }
+
+    #[test]
+    fn loads_and_writes_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let in_dir = dir.path().join("in");
+        fs::create_dir(&in_dir).unwrap();
+        fs::write(in_dir.join("b"), b"bb").unwrap();
+        fs::write(in_dir.join("a"), b"a").unwrap();
+        fs::write(in_dir.join(".hidden"), b"h").unwrap();
+        fs::write(in_dir.join("empty"), b"").unwrap();
+        let inputs = load_inputs(&in_dir).unwrap();
+        assert_eq!(inputs, vec![("a".to_string(), b"a".to_vec()), ("b".to_string(), b"bb".to_vec())]);
+
+        write_inputs(dir.path().join("out"), &inputs, &[1]).unwrap();
+        assert_eq!(load_inputs(dir.path().join("out")).unwrap(), vec![("b".to_string(), b"bb".to_vec())]);
+    }
+}
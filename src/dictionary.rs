@@ -0,0 +1,158 @@
+/*
+   DeepSAFL - dictionary loading
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+// Reads afl-fuzz -x dictionaries: either a directory with one token per file, or a
+// file with one token per line, like
+//
+//   # comment
+//   keyword_if="if"
+//   "\x7fELF"
+//
+// Besides \\, \" and \xNN the values may use \n, \r and \t, as grammar terminals do.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use config;
+
+//An error about line line_number of a file of this kind, e.g. "dictionary"
+pub(crate) fn invalid_line(kind: &str, line_number: usize, message: &str)->io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{} line {}: {}", kind, line_number, message))
+}
+
+fn invalid_data(line_number: usize, message: &str)->io::Error {
+    invalid_line("dictionary", line_number, message)
+}
+
+fn hex_value(digit: u8)->Option<u8> {
+    (digit as char).to_digit(16).map(|value| value as u8)
+}
+
+//The byte of the escape text starts with, and the length of the escape. text starts
+//at the backslash; None if \\, \", \n, \r, \t or \xNN does not follow.
+pub(crate) fn parse_escape(text: &[u8])->Option<(u8, usize)> {
+    match text.get(1) {
+        Some(&b'\\') => Some((b'\\', 2)),
+        Some(&b'"') => Some((b'"', 2)),
+        Some(&b'n') => Some((b'\n', 2)),
+        Some(&b'r') => Some((b'\r', 2)),
+        Some(&b't') => Some((b'\t', 2)),
+        Some(&b'x') => {
+            let high = text.get(2).and_then(|digit| hex_value(*digit))?;
+            let low = text.get(3).and_then(|digit| hex_value(*digit))?;
+            Some((high << 4 | low, 4))
+        },
+        _ => None,
+    }
+}
+
+//The token of one line, None for blank lines and comments
+fn parse_line(line: &str, line_number: usize)->io::Result<Option<Vec<u8>>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    //skip the name (and the @level of the name) in name="value"
+    let value = match line.find('"') {
+        Some(start) => &line[start..],
+        None => return Err(invalid_data(line_number, "no opening quote")),
+    };
+    if value.len() < 2 || !value.ends_with('"') {
+        return Err(invalid_data(line_number, "no closing quote"));
+    }
+    let value = &value.as_bytes()[1..value.len() - 1];
+
+    let mut token = Vec::with_capacity(value.len());
+    let mut i = 0;
+    while i < value.len() {
+        match value[i] {
+            b'\\' => {
+                match parse_escape(&value[i..]) {
+                    Some((byte, escape_len)) => {
+                        token.push(byte);
+                        i += escape_len;
+                    },
+                    None => return Err(invalid_data(line_number, "bad escape")),
+                }
+            },
+            //unescaped quotes and control characters are not allowed inside the value
+            byte if byte == b'"' || !(32..=126).contains(&byte) => {
+                return Err(invalid_data(line_number, "non-printable or unescaped character"));
+            },
+            byte => {
+                token.push(byte);
+                i += 1;
+            },
+        }
+    }
+    if token.is_empty() {
+        return Err(invalid_data(line_number, "empty token"));
+    }
+    if token.len() as u64 > config::MAX_DICT_FILE {
+        return Err(invalid_data(line_number, "token is too long"));
+    }
+    Ok(Some(token))
+}
+
+//Tokens of a dictionary file's content
+pub fn parse_dictionary(content: &str)->io::Result<Vec<Vec<u8>>> {
+    let mut tokens = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        if let Some(token) = parse_line(line, i + 1)? {
+            tokens.push(token);
+        }
+    }
+    Ok(tokens)
+}
+
+//Tokens of a dictionary file or directory, sorted by length like afl-fuzz does
+pub fn load_dictionary<P: AsRef<Path>>(path: P)->io::Result<Vec<Vec<u8>>> {
+    let path = path.as_ref();
+    let mut tokens = if path.is_dir() {
+        let mut tokens = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+            let token = fs::read(entry.path())?;
+            if token.is_empty() || token.len() as u64 > config::MAX_DICT_FILE {
+                continue;
+            }
+            tokens.push(token);
+        }
+        tokens
+    }
+    else {
+        parse_dictionary(&fs::read_to_string(path)?)?
+    };
+    tokens.sort_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)));
+    tokens.dedup();
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_afl_dictionaries() {
+        let content = "# keywords\n\nkw_if=\"if\"\nkw_else@2=\"else\"\n\"\\x7fELF\"\n\"a\\\"b\\\\\"\n\"\\r\\n\\t\"\n";
+        let tokens = parse_dictionary(content).unwrap();
+        assert_eq!(tokens, vec![b"if".to_vec(), b"else".to_vec(), b"\x7fELF".to_vec(), b"a\"b\\".to_vec(), b"\r\n\t".to_vec()]);
+    }
+
+    #[test]
+    fn rejects_broken_lines() {
+        assert!(parse_dictionary("kw=if").is_err());
+        assert!(parse_dictionary("\"unterminated").is_err());
+        assert!(parse_dictionary("\"\\x4\"").is_err());
+        assert!(parse_dictionary("\"\"").is_err());
+    }
+}
@@ -0,0 +1,59 @@
+/*
+   DeepSAFL - fuzzing events
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+// Tools that want to follow a run subscribe to SeedManagement with
+// on_event instead of parsing the log.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FuzzEvent<'a> {
+    //An input found new coverage and is now seed number index of the pool
+    NewSeed { index: usize, seed: &'a [u8], imported: bool },
+    //The generator moved on to another stage, e.g. from "bitflip 1/1" to "bitflip 2/1"
+    StageChange { from: &'static str, to: &'static str },
+    Crash { seed: &'a [u8] },
+    Hang { seed: &'a [u8] },
+    //The pool has been gone through once more
+    CycleDone { cycles_done: u64 },
+}
+
+pub type EventListener = Box<dyn FnMut(&FuzzEvent) + Send>;
+
+#[derive(Default)]
+pub struct EventBus {
+    listeners: Vec<EventListener>,
+}
+
+impl fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EventBus")
+            .field("listeners", &self.listeners.len())
+            .finish()
+    }
+}
+
+impl EventBus {
+    pub fn subscribe(&mut self, listener: EventListener) {
+        self.listeners.push(listener);
+    }
+
+    //Hand event to every listener and the log
+    pub fn emit(&mut self, event: &FuzzEvent) {
+        match *event {
+            FuzzEvent::StageChange { .. } => trace!("{:?}", event),
+            FuzzEvent::NewSeed { index, seed, imported } => debug!("new seed {} ({} bytes, imported: {})", index, seed.len(), imported),
+            FuzzEvent::Crash { seed } => debug!("crash ({} bytes)", seed.len()),
+            FuzzEvent::Hang { seed } => debug!("hang ({} bytes)", seed.len()),
+            FuzzEvent::CycleDone { cycles_done } => info!("cycle {} done", cycles_done),
+        }
+        for listener in self.listeners.iter_mut() {
+            listener(event);
+        }
+    }
+}
@@ -0,0 +1,405 @@
+/*
+   DeepSAFL - forkserver executor
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+// Runs an AFL-instrumented binary. The target gets the id of a SysV shared memory
+// map in __AFL_SHM_ID and stops before main; the forkserver then forks one child
+// per input when asked on the control pipe (fd 198) and sends back the child pid
+// and its wait status on the status pipe (fd 199).
+
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{self, Child, Command, Stdio};
+use std::ptr;
+use std::slice;
+use std::time::{Duration, Instant};
+
+use libc;
+
+use config;
+use feedback::{ExecInfo, ExecStatus};
+use super::{copy_trace_bits, Executor, ExecutorError};
+
+#[derive(Debug, Clone)]
+pub struct ForkserverOptions {
+    //a run taking longer is killed and reported as ExecStatus::Timeout
+    pub timeout: Duration,
+    //address space limit of the target in MB, 0 for none
+    pub mem_limit_mb: u64,
+    //where inputs are written, a file in the temp dir if None
+    pub input_file: Option<PathBuf>,
+}
+
+impl Default for ForkserverOptions {
+    fn default() -> ForkserverOptions {
+        ForkserverOptions {
+            timeout: Duration::from_millis(config::EXEC_TIMEOUT),
+            mem_limit_mb: config::MEM_LIMIT,
+            input_file: None,
+        }
+    }
+}
+
+//How the target reads its input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputMode {
+    //the path of the input file replaces @@ in the arguments
+    File,
+    Stdin,
+}
+
+//The SysV shared memory the target writes its coverage to
+#[derive(Debug)]
+struct ShmMap {
+    shm_id: libc::c_int,
+    trace_bits: *mut u8,
+}
+
+// The map is only touched by its owner, the target writes to its own mapping
+unsafe impl Send for ShmMap {}
+
+impl ShmMap {
+    fn new() -> io::Result<ShmMap> {
+        unsafe {
+            let shm_id = libc::shmget(libc::IPC_PRIVATE, config::MAP_SIZE, libc::IPC_CREAT | libc::IPC_EXCL | 0o600);
+            if shm_id < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let trace_bits = libc::shmat(shm_id, ptr::null(), 0);
+            if trace_bits as isize == -1 {
+                let e = io::Error::last_os_error();
+                libc::shmctl(shm_id, libc::IPC_RMID, ptr::null_mut());
+                return Err(e);
+            }
+            Ok(ShmMap {
+                shm_id,
+                trace_bits: trace_bits as *mut u8,
+            })
+        }
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.trace_bits, config::MAP_SIZE) }
+    }
+
+    fn clear(&mut self) {
+        unsafe { ptr::write_bytes(self.trace_bits, 0, config::MAP_SIZE) }
+    }
+}
+
+impl Drop for ShmMap {
+    fn drop(&mut self) {
+        unsafe {
+            libc::shmdt(self.trace_bits as *const libc::c_void);
+            libc::shmctl(self.shm_id, libc::IPC_RMID, ptr::null_mut());
+        }
+    }
+}
+
+//A pipe whose ends are closed on exec, the target only sees the dup2'ed copies
+fn cloexec_pipe() -> io::Result<(File, File)> {
+    let mut fds: [libc::c_int; 2] = [0; 2];
+    unsafe {
+        if libc::pipe(fds.as_mut_ptr()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let pipe = (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1]));
+        for fd in fds.iter() {
+            if libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(pipe)
+    }
+}
+
+//Read a 4 byte message from the forkserver, None if nothing came within timeout
+fn read_u32(pipe: &mut File, timeout: Option<Duration>) -> io::Result<Option<u32>> {
+    if let Some(timeout) = timeout {
+        let timeout_ms = ::std::cmp::min(timeout.as_millis(), libc::c_int::MAX as u128) as libc::c_int;
+        let mut poll_fd = libc::pollfd {
+            fd: pipe.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        loop {
+            let ready = unsafe { libc::poll(&mut poll_fd, 1, timeout_ms) };
+            if ready > 0 {
+                break;
+            }
+            if ready == 0 {
+                return Ok(None);
+            }
+            let e = io::Error::last_os_error();
+            if e.kind() != io::ErrorKind::Interrupted {
+                return Err(e);
+            }
+        }
+    }
+    let mut buf = [0u8; 4];
+    pipe.read_exact(&mut buf)?;
+    Ok(Some(u32::from_ne_bytes(buf)))
+}
+
+#[derive(Debug)]
+pub struct Forkserver {
+    forkserver: Child,
+    ctl_pipe: File,
+    st_pipe: File,
+    shm: ShmMap,
+    input_mode: InputMode,
+    input_path: PathBuf,
+    input_file: File,
+    //remove input_path when done, it was not chosen by the user
+    remove_input: bool,
+    timeout: Duration,
+    last_run_timed_out: bool,
+}
+
+impl Forkserver {
+    //Start target_args[0] with the rest as its arguments and wait for the forkserver handshake
+    pub fn start(target_args: &[String], options: ForkserverOptions) -> Result<Forkserver, ExecutorError> {
+        if target_args.is_empty() {
+            return Err(ExecutorError::Io(io::Error::new(io::ErrorKind::InvalidInput, "no target to run")));
+        }
+        let shm = ShmMap::new().map_err(ExecutorError::Shm)?;
+
+        let remove_input = options.input_file.is_none();
+        let input_path = match options.input_file {
+            Some(input_path) => input_path,
+            None => env::temp_dir().join(format!(".cur_input.{}.{}", process::id(), shm.shm_id)),
+        };
+        let input_file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&input_path)?;
+
+        let input_mode = if target_args.iter().any(|arg| arg.contains("@@")) { InputMode::File } else { InputMode::Stdin };
+        let input_path_str = input_path.to_string_lossy().into_owned();
+
+        let (ctl_read, ctl_pipe) = cloexec_pipe()?;
+        let (st_pipe, st_write) = cloexec_pipe()?;
+
+        let mut command = Command::new(&target_args[0]);
+        command.args(target_args[1..].iter().map(|arg| arg.replace("@@", &input_path_str)))
+            .env(config::SHM_ENV_VAR, shm.shm_id.to_string())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        match input_mode {
+            //the target shares the file offset with input_file, it is rewound before each run
+            InputMode::Stdin => command.stdin(Stdio::from(input_file.try_clone()?)),
+            InputMode::File => command.stdin(Stdio::null()),
+        };
+
+        let ctl_fd: RawFd = ctl_read.as_raw_fd();
+        let st_fd: RawFd = st_write.as_raw_fd();
+        let mem_limit = options.mem_limit_mb << 20;
+        unsafe {
+            command.pre_exec(move || {
+                if libc::dup2(ctl_fd, config::FORKSRV_FD) < 0 || libc::dup2(st_fd, config::FORKSRV_FD + 1) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                if mem_limit > 0 {
+                    let limit = libc::rlimit {
+                        rlim_cur: mem_limit as libc::rlim_t,
+                        rlim_max: mem_limit as libc::rlim_t,
+                    };
+                    libc::setrlimit(libc::RLIMIT_AS, &limit);
+                }
+                //keep the target away from our terminal signals
+                libc::setsid();
+                Ok(())
+            });
+        }
+        let forkserver = command.spawn()?;
+        drop(ctl_read);
+        drop(st_write);
+
+        let mut executor = Forkserver {
+            forkserver,
+            ctl_pipe,
+            st_pipe,
+            shm,
+            input_mode,
+            input_path,
+            input_file,
+            remove_input,
+            timeout: options.timeout,
+            last_run_timed_out: false,
+        };
+        //the handshake: four bytes once the target is initialised
+        match read_u32(&mut executor.st_pipe, Some(options.timeout * config::FORK_WAIT_MULT)) {
+            Ok(Some(_)) => Ok(executor),
+            _ => {
+                executor.kill();
+                Err(ExecutorError::Handshake)
+            },
+        }
+    }
+
+    pub fn input_mode(&self) -> InputMode {
+        self.input_mode
+    }
+
+    //The coverage map of the last run, as written by the target
+    pub fn trace_bits(&self) -> &[u8] {
+        self.shm.as_slice()
+    }
+
+    fn write_input(&mut self, seed: &[u8]) -> io::Result<()> {
+        self.input_file.seek(SeekFrom::Start(0))?;
+        self.input_file.write_all(seed)?;
+        self.input_file.set_len(seed.len() as u64)?;
+        self.input_file.seek(SeekFrom::Start(0))?;
+        Ok(())
+    }
+
+    fn kill(&mut self) {
+        let _ = self.forkserver.kill();
+        let _ = self.forkserver.wait();
+    }
+}
+
+impl Executor for Forkserver {
+    fn run_target(&mut self, seed: &[u8], cov_info: &mut [u32; config::MAP_SIZE]) -> Result<ExecInfo, ExecutorError> {
+        self.write_input(seed)?;
+        self.shm.clear();
+
+        let was_killed = self.last_run_timed_out as u32;
+        self.ctl_pipe.write_all(&was_killed.to_ne_bytes()).map_err(|_| ExecutorError::ForkserverDied)?;
+        let child_pid = match read_u32(&mut self.st_pipe, None) {
+            Ok(Some(child_pid)) if child_pid as i32 > 0 => child_pid as libc::pid_t,
+            _ => return Err(ExecutorError::ForkserverDied),
+        };
+
+        let start = Instant::now();
+        let mut timed_out = false;
+        let wait_status = match read_u32(&mut self.st_pipe, Some(self.timeout)) {
+            Ok(Some(wait_status)) => wait_status,
+            Ok(None) => {
+                timed_out = true;
+                unsafe { libc::kill(child_pid, libc::SIGKILL) };
+                match read_u32(&mut self.st_pipe, None) {
+                    Ok(Some(wait_status)) => wait_status,
+                    _ => return Err(ExecutorError::ForkserverDied),
+                }
+            },
+            Err(_) => return Err(ExecutorError::ForkserverDied),
+        };
+        let exec_us = start.elapsed().as_micros() as u64;
+        self.last_run_timed_out = timed_out;
+
+        let status = if timed_out {
+            ExecStatus::Timeout
+        }
+        else if libc::WIFSIGNALED(wait_status as libc::c_int) {
+            ExecStatus::Crash
+        }
+        else {
+            ExecStatus::Normal
+        };
+        copy_trace_bits(self.shm.as_slice(), cov_info);
+        Ok(ExecInfo::new(status, exec_us))
+    }
+}
+
+impl Drop for Forkserver {
+    fn drop(&mut self) {
+        self.kill();
+        if self.remove_input {
+            let _ = fs::remove_file(&self.input_path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use tempfile;
+
+    //Build the C target, None if there is no C compiler around
+    fn build_target(dir: &Path) -> Option<String> {
+        let source = dir.join("target.c");
+        let binary = dir.join("target");
+        fs::write(&source, include_str!("forkserver_target.c")).unwrap();
+        let status = Command::new("cc").arg("-o").arg(&binary).arg(&source).status().ok()?;
+        if !status.success() {
+            return None;
+        }
+        Some(binary.to_string_lossy().into_owned())
+    }
+
+    fn options(dir: &Path) -> ForkserverOptions {
+        ForkserverOptions {
+            timeout: Duration::from_millis(200),
+            mem_limit_mb: 0,
+            input_file: Some(dir.join(".cur_input")),
+        }
+    }
+
+    #[test]
+    fn runs_inputs_from_file_and_stdin() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = match build_target(dir.path()) {
+            Some(target) => target,
+            None => return,
+        };
+        let mut cov_info = [0u32; config::MAP_SIZE];
+        for target_args in [vec![target.clone(), "@@".to_string()], vec![target.clone()]].iter() {
+            let mut executor = Forkserver::start(target_args, options(dir.path())).unwrap();
+            for seed in [&b"abc"[..], &b"x"[..], &b"abcdef"[..]].iter() {
+                let exec_info = executor.run_target(seed, &mut cov_info).unwrap();
+                assert_eq!(exec_info.status, ExecStatus::Normal);
+                assert_eq!(cov_info.iter().filter(|cov| **cov != 0).count(), 1);
+                assert_eq!(cov_info[seed[0] as usize], 1);
+            }
+        }
+    }
+
+    #[test]
+    fn reports_crashes_and_timeouts() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = match build_target(dir.path()) {
+            Some(target) => target,
+            None => return,
+        };
+        let mut cov_info = [0u32; config::MAP_SIZE];
+        let mut executor = Forkserver::start(&[target, "@@".to_string()], options(dir.path())).unwrap();
+        assert_eq!(executor.run_target(b"crash", &mut cov_info).unwrap().status, ExecStatus::Crash);
+        let exec_info = executor.run_target(b"hang", &mut cov_info).unwrap();
+        assert_eq!(exec_info.status, ExecStatus::Timeout);
+        assert!(exec_info.exec_us >= 200_000);
+        //the forkserver keeps going after both
+        assert_eq!(executor.run_target(b"ok", &mut cov_info).unwrap().status, ExecStatus::Normal);
+    }
+
+    #[test]
+    fn enforces_the_memory_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = match build_target(dir.path()) {
+            Some(target) => target,
+            None => return,
+        };
+        let mut cov_info = [0u32; config::MAP_SIZE];
+        let mut options = options(dir.path());
+        options.mem_limit_mb = 64;
+        let mut executor = Forkserver::start(&[target, "@@".to_string()], options).unwrap();
+        assert_eq!(executor.run_target(b"big", &mut cov_info).unwrap().status, ExecStatus::Crash);
+        assert_eq!(executor.run_target(b"small", &mut cov_info).unwrap().status, ExecStatus::Normal);
+    }
+
+    #[test]
+    fn uninstrumented_target_fails_the_handshake() {
+        let dir = tempfile::tempdir().unwrap();
+        match Forkserver::start(&["true".to_string()], options(dir.path())) {
+            Err(ExecutorError::Handshake) => {},
+            other => panic!("unexpected {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,81 @@
+/*
+   DeepSAFL - target for the forkserver tests
+   ------------------------------------------------------
+
+   It speaks the forkserver protocol by hand instead of being built with
+   afl-gcc, and marks one edge per distinct first byte of its input.
+   Inputs starting with "crash" abort, "hang" never returns and "big"
+   allocates 256 MB.
+*/
+
+#include <signal.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/shm.h>
+#include <sys/types.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define MAP_SIZE (1 << 16)
+#define FORKSRV_FD 198
+
+static unsigned char dummy_map[MAP_SIZE];
+static unsigned char *trace_bits = dummy_map;
+
+static void afl_forkserver(void) {
+  char *shm_id = getenv("__AFL_SHM_ID");
+  unsigned char tmp[4] = {0};
+
+  if (shm_id) {
+    void *map = shmat(atoi(shm_id), NULL, 0);
+    if (map == (void *)-1) _exit(1);
+    trace_bits = map;
+  }
+
+  /* not run by a forkserver client, just execute once */
+  if (write(FORKSRV_FD + 1, tmp, 4) != 4) return;
+
+  for (;;) {
+    unsigned int was_killed;
+    int status;
+    pid_t child;
+
+    if (read(FORKSRV_FD, &was_killed, 4) != 4) _exit(1);
+    child = fork();
+    if (child < 0) _exit(1);
+    if (!child) {
+      close(FORKSRV_FD);
+      close(FORKSRV_FD + 1);
+      return;
+    }
+    if (write(FORKSRV_FD + 1, &child, 4) != 4) _exit(1);
+    if (waitpid(child, &status, 0) < 0) _exit(1);
+    if (write(FORKSRV_FD + 1, &status, 4) != 4) _exit(1);
+  }
+}
+
+int main(int argc, char **argv) {
+  unsigned char buf[256];
+  size_t len;
+  FILE *in = stdin;
+
+  afl_forkserver();
+
+  if (argc > 1 && !(in = fopen(argv[1], "rb"))) return 2;
+  len = fread(buf, 1, sizeof(buf), in);
+  if (!len) return 0;
+
+  trace_bits[buf[0]]++;
+
+  if (len >= 5 && !memcmp(buf, "crash", 5)) abort();
+  if (len >= 4 && !memcmp(buf, "hang", 4)) for (;;) pause();
+  if (len >= 3 && !memcmp(buf, "big", 3)) {
+    size_t big = (size_t)256 << 20;
+    char *p = malloc(big);
+    if (!p) abort();
+    memset(p, 1, big);
+    free(p);
+  }
+  return 0;
+}
@@ -0,0 +1,105 @@
+/*
+   DeepSAFL - in-process executor
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+// Runs a libFuzzer-style harness, fn(&[u8]), in the fuzzer process. The harness
+// (e.g. SanitizerCoverage trace-pc-guard or inline 8-bit counters) updates a byte
+// map of its own, which is cleared before every run and folded into MAP_SIZE edges
+// afterwards. A panic of the harness is a crash; a real signal still kills us.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+use std::time::Instant;
+
+use config;
+use feedback::{ExecInfo, ExecStatus};
+use super::{Executor, ExecutorError};
+
+pub struct InProcessExecutor<F: Fn(&[u8])> {
+    harness: F,
+    map_ptr: *mut u8,
+    map_len: usize,
+}
+
+impl<F: Fn(&[u8])> InProcessExecutor<F> {
+    /// # Safety
+    /// map_ptr must point to map_len bytes that stay valid as long as the executor,
+    /// and nothing but the harness may write them during a run.
+    pub unsafe fn new(harness: F, map_ptr: *mut u8, map_len: usize) -> InProcessExecutor<F> {
+        InProcessExecutor {
+            harness,
+            map_ptr,
+            map_len,
+        }
+    }
+
+    //The map as the harness left it after the last run
+    pub fn coverage_map(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.map_ptr, self.map_len) }
+    }
+}
+
+impl<F: Fn(&[u8])> Executor for InProcessExecutor<F> {
+    fn run_target(&mut self, seed: &[u8], cov_info: &mut [u32; config::MAP_SIZE]) -> Result<ExecInfo, ExecutorError> {
+        unsafe { ptr::write_bytes(self.map_ptr, 0, self.map_len) };
+
+        let start = Instant::now();
+        let harness = &self.harness;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| harness(seed)));
+        let exec_us = start.elapsed().as_micros() as u64;
+
+        for cov in cov_info.iter_mut() {
+            *cov = 0;
+        }
+        //a map bigger than MAP_SIZE wraps around, like a hash of the edge index
+        for (i, counter) in self.coverage_map().iter().enumerate() {
+            if *counter != 0 {
+                let cov = &mut cov_info[i % config::MAP_SIZE];
+                *cov = cov.saturating_add(*counter as u32);
+            }
+        }
+        let status = if result.is_err() { ExecStatus::Crash } else { ExecStatus::Normal };
+        Ok(ExecInfo::new(status, exec_us))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_edges_and_catches_panics() {
+        let mut map = vec![0u8; 16];
+        let map_ptr = map.as_mut_ptr();
+        let harness = move |data: &[u8]| {
+            if data.is_empty() {
+                return;
+            }
+            unsafe { *map_ptr.add(data[0] as usize % 16) += 1 };
+            if data == b"boom" {
+                panic!("boom");
+            }
+        };
+        let mut executor = unsafe { InProcessExecutor::new(harness, map.as_mut_ptr(), map.len()) };
+        let mut cov_info = [0u32; config::MAP_SIZE];
+
+        assert_eq!(executor.run_target(&[3], &mut cov_info).unwrap().status, ExecStatus::Normal);
+        assert_eq!(cov_info[3], 1);
+        //the map is cleared between runs
+        assert_eq!(executor.run_target(&[5], &mut cov_info).unwrap().status, ExecStatus::Normal);
+        assert_eq!(cov_info[3], 0);
+        assert_eq!(cov_info[5], 1);
+
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(|_| {}));
+        let exec_info = executor.run_target(b"boom", &mut cov_info);
+        panic::set_hook(hook);
+        assert_eq!(exec_info.unwrap().status, ExecStatus::Crash);
+        assert_eq!(cov_info[b'b' as usize % 16], 1);
+    }
+}
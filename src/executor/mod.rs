@@ -0,0 +1,65 @@
+/*
+   DeepSAFL - executors
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+use std::error;
+use std::fmt;
+use std::io;
+
+use config;
+use feedback::ExecInfo;
+
+pub mod in_process;
+pub use self::in_process::InProcessExecutor;
+
+#[cfg(unix)]
+pub mod forkserver;
+#[cfg(unix)]
+pub use self::forkserver::{Forkserver, ForkserverOptions};
+
+//Something that runs the target on an input and collects its coverage
+pub trait Executor {
+    //cov_info is cleared first, then gets the hit count of every edge touched by seed
+    fn run_target(&mut self, seed: &[u8], cov_info: &mut [u32; config::MAP_SIZE]) -> Result<ExecInfo, ExecutorError>;
+}
+
+#[derive(Debug)]
+pub enum ExecutorError {
+    Io(io::Error),
+    //The shared memory for the coverage map could not be set up
+    Shm(io::Error),
+    //The target did not answer the forkserver handshake, it is probably not instrumented
+    Handshake,
+    //The forkserver stopped answering
+    ForkserverDied,
+}
+
+impl fmt::Display for ExecutorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ExecutorError::Io(ref e) => write!(f, "executor i/o error: {}", e),
+            ExecutorError::Shm(ref e) => write!(f, "cannot set up the coverage map: {}", e),
+            ExecutorError::Handshake => write!(f, "the target did not start the forkserver, is it instrumented?"),
+            ExecutorError::ForkserverDied => write!(f, "the forkserver stopped answering"),
+        }
+    }
+}
+
+impl error::Error for ExecutorError {}
+
+impl From<io::Error> for ExecutorError {
+    fn from(e: io::Error) -> ExecutorError {
+        ExecutorError::Io(e)
+    }
+}
+
+//Copy an AFL-style byte map into cov_info
+pub fn copy_trace_bits(trace_bits: &[u8], cov_info: &mut [u32; config::MAP_SIZE]) {
+    for (cov, trace) in cov_info.iter_mut().zip(trace_bits.iter()) {
+        *cov = *trace as u32;
+    }
+}
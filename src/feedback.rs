@@ -0,0 +1,72 @@
+/*
+   DeepSAFL - feedback of executed inputs
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+use std::error;
+use std::fmt;
+
+//Every input handed out by SeedManagement::get_seeds gets its own id,
+//the executor gives it back with the feedback of that input
+pub type SeedId = u64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaggedSeed {
+    pub id: SeedId,
+    pub seed_vec: Vec<u8>,
+}
+
+//How the target exited on an input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExecStatus {
+    #[default]
+    Normal,
+    Crash,
+    Timeout,
+}
+
+//What the executor knows about a run besides the coverage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExecInfo {
+    pub status: ExecStatus,
+    pub exec_us: u64,
+}
+
+impl ExecInfo {
+    pub fn new(status: ExecStatus, exec_us: u64) -> ExecInfo {
+        ExecInfo {
+            status,
+            exec_us,
+        }
+    }
+}
+
+//What happened to one input of SeedManagement::fuzz_one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FuzzResult {
+    pub exec_info: ExecInfo,
+    //it found new coverage and went into the seed pool
+    pub is_kept: bool,
+    //a crash that opened this coverage bucket of SeedManagement::crash_triage,
+    //the crashes worth saving
+    pub new_crash_bucket: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedbackError {
+    //No input with this id is waiting for feedback (never handed out, or already answered)
+    UnknownSeed(SeedId),
+}
+
+impl fmt::Display for FeedbackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FeedbackError::UnknownSeed(id) => write!(f, "no input with id {} is waiting for feedback", id),
+        }
+    }
+}
+
+impl error::Error for FeedbackError {}
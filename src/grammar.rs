@@ -0,0 +1,582 @@
+/*
+   DeepSAFL - grammar-based generation
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+// For targets that parse structured text (JSON, small languages) flipping bytes
+// mostly breaks the syntax. In grammar mode inputs are derived from a context-free
+// grammar and the mutations work on derivation trees, as Nautilus does:
+//
+//   replace:   a subtree is derived again from its rule
+//   splice:    a subtree is swapped for a subtree of the same rule from another seed
+//   recursion: a rule that derives itself is repeated a few more times
+//
+// The grammar file has a rule per line, the first one derives the inputs. Rules
+// are <names>, terminals are quoted with the escapes of the dictionaries, "" is
+// the empty string and a line starting with | goes on with the alternatives of
+// the rule above:
+//
+//   # sums of binary numbers
+//   <expr> ::= <num> | "(" <expr> ")"
+//            | <expr> "+" <expr>
+//   <num>  ::= "0" | "1" | <num> "0"
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use rand;
+use rand::{Rng, SeedableRng, StdRng};
+
+use config;
+use dictionary::{invalid_line, parse_escape};
+
+fn invalid_data(line_number: usize, message: &str)->io::Error {
+    invalid_line("grammar", line_number, message)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Symbol {
+    Terminal(Vec<u8>),
+    Rule(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Grammar {
+    names: Vec<String>,
+    //the alternatives of every rule
+    rules: Vec<Vec<Vec<Symbol>>>,
+    //depth of the shallowest derivation of every rule
+    min_depth: Vec<usize>,
+}
+
+//A rule as it is written in the grammar file
+struct Definition<'a> {
+    name: String,
+    line_number: usize,
+    //the right-hand side and the continuation lines, with their line numbers
+    pieces: Vec<(usize, &'a str)>,
+}
+
+//The symbols of the right-hand side text, split into alternatives
+fn parse_alternatives(text: &str, line_number: usize, names: &[String])->io::Result<Vec<Vec<Symbol>>> {
+    let text = text.as_bytes();
+    let mut alternatives = vec![Vec::new()];
+    let mut is_empty = true;
+    let mut i = 0;
+    while i < text.len() {
+        match text[i] {
+            byte if byte.is_ascii_whitespace() => i += 1,
+            b'|' => {
+                if is_empty {
+                    return Err(invalid_data(line_number, "empty alternative"));
+                }
+                alternatives.push(Vec::new());
+                is_empty = true;
+                i += 1;
+            },
+            b'<' => {
+                let end = match text[i..].iter().position(|&byte| byte == b'>') {
+                    Some(end) => i + end,
+                    None => return Err(invalid_data(line_number, "no closing >")),
+                };
+                let name = String::from_utf8_lossy(&text[i + 1..end]);
+                match names.iter().position(|known| *known == name) {
+                    Some(rule) => alternatives.last_mut().unwrap().push(Symbol::Rule(rule)),
+                    None => return Err(invalid_data(line_number, &format!("unknown rule <{}>", name))),
+                }
+                is_empty = false;
+                i = end + 1;
+            },
+            b'"' => {
+                let mut terminal = Vec::new();
+                i += 1;
+                loop {
+                    match text.get(i) {
+                        None => return Err(invalid_data(line_number, "no closing quote")),
+                        Some(&b'"') => break,
+                        Some(&b'\\') => {
+                            match parse_escape(&text[i..]) {
+                                Some((byte, escape_len)) => {
+                                    terminal.push(byte);
+                                    i += escape_len;
+                                },
+                                None => return Err(invalid_data(line_number, "bad escape")),
+                            }
+                        },
+                        Some(&byte) => {
+                            terminal.push(byte);
+                            i += 1;
+                        },
+                    }
+                }
+                //"" derives the empty string
+                if !terminal.is_empty() {
+                    alternatives.last_mut().unwrap().push(Symbol::Terminal(terminal));
+                }
+                is_empty = false;
+                i += 1;
+            },
+            byte => return Err(invalid_data(line_number, &format!("unexpected character '{}'", byte as char))),
+        }
+    }
+    if is_empty {
+        return Err(invalid_data(line_number, "empty alternative"));
+    }
+    Ok(alternatives)
+}
+
+//The grammar of a grammar file's content
+pub fn parse_grammar(content: &str)->io::Result<Grammar> {
+    let mut definitions: Vec<Definition> = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('|') {
+            match definitions.last_mut() {
+                Some(definition) => definition.pieces.push((i + 1, line)),
+                None => return Err(invalid_data(i + 1, "alternatives without a rule")),
+            }
+            continue;
+        }
+        let (name, text) = match line.find("::=") {
+            Some(pos) => (line[..pos].trim(), &line[pos + 3..]),
+            None => return Err(invalid_data(i + 1, "no ::=")),
+        };
+        if name.len() < 3 || !name.starts_with('<') || !name.ends_with('>') {
+            return Err(invalid_data(i + 1, "the rule name is not a <name>"));
+        }
+        let name = name[1..name.len() - 1].to_string();
+        if definitions.iter().any(|definition| definition.name == name) {
+            return Err(invalid_data(i + 1, &format!("rule <{}> is defined twice", name)));
+        }
+        definitions.push(Definition { name, line_number: i + 1, pieces: vec![(i + 1, text)] });
+    }
+    if definitions.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "grammar has no rules"));
+    }
+
+    let names: Vec<String> = definitions.iter().map(|definition| definition.name.clone()).collect();
+    let mut rules = Vec::with_capacity(definitions.len());
+    for definition in definitions.iter() {
+        let mut alternatives = Vec::new();
+        for (n, &(line_number, text)) in definition.pieces.iter().enumerate() {
+            //a continuation line starts with the | that separates it from the line above
+            let text = if n > 0 { &text[1..] } else { text };
+            alternatives.extend(parse_alternatives(text, line_number, &names)?);
+        }
+        rules.push(alternatives);
+    }
+
+    let mut grammar = Grammar { names, rules, min_depth: Vec::new() };
+    grammar.min_depth = vec![usize::MAX; grammar.rules.len()];
+    let mut is_changed = true;
+    while is_changed {
+        is_changed = false;
+        for rule in 0..grammar.rules.len() {
+            for alt in 0..grammar.rules[rule].len() {
+                let depth = grammar.alt_depth(rule, alt);
+                if depth < grammar.min_depth[rule] {
+                    grammar.min_depth[rule] = depth;
+                    is_changed = true;
+                }
+            }
+        }
+    }
+    if let Some(rule) = (0..grammar.rules.len()).find(|&rule| grammar.min_depth[rule] == usize::MAX) {
+        return Err(invalid_data(definitions[rule].line_number, &format!("rule <{}> never derives a string", grammar.names[rule])));
+    }
+    Ok(grammar)
+}
+
+//The grammar of a grammar file
+pub fn load_grammar<P: AsRef<Path>>(path: P)->io::Result<Grammar> {
+    parse_grammar(&fs::read_to_string(path)?)
+}
+
+impl Grammar {
+    pub fn rule_cnt(&self)->usize {
+        self.rules.len()
+    }
+
+    pub fn rule_name(&self, rule: usize)->&str {
+        &self.names[rule]
+    }
+
+    //Depth of the shallowest derivation that starts with alternative alt of rule,
+    //usize::MAX while it is not known to end
+    fn alt_depth(&self, rule: usize, alt: usize)->usize {
+        let mut depth = 1;
+        for symbol in self.rules[rule][alt].iter() {
+            if let Symbol::Rule(child) = *symbol {
+                if self.min_depth[child] == usize::MAX {
+                    return usize::MAX;
+                }
+                depth = depth.max(self.min_depth[child] + 1);
+            }
+        }
+        depth
+    }
+
+    //A random derivation of rule at most depth deep. Once depth or nodes_left runs
+    //out, the rules take the shallowest way to end.
+    fn generate<R: Rng>(&self, rule: usize, depth: usize, nodes_left: &mut usize, rang: &mut R)->Node {
+        let alt_cnt = self.rules[rule].len();
+        let fitting: Vec<usize> = if *nodes_left == 0 {
+            Vec::new()
+        }
+        else {
+            (0..alt_cnt).filter(|&alt| self.alt_depth(rule, alt) <= depth).collect()
+        };
+        let alt = if fitting.is_empty() {
+            (0..alt_cnt).min_by_key(|&alt| self.alt_depth(rule, alt)).unwrap_or(0)
+        }
+        else {
+            fitting[rang.gen_range(0, fitting.len())]
+        };
+        *nodes_left = nodes_left.saturating_sub(1);
+        let children = self.rules[rule][alt].iter().map(|symbol| match *symbol {
+            Symbol::Terminal(ref bytes) => Node::Terminal(bytes.clone()),
+            Symbol::Rule(child) => self.generate(child, depth.saturating_sub(1), nodes_left, rang),
+        }).collect();
+        Node::Rule { rule, children }
+    }
+}
+
+//A derivation tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    Terminal(Vec<u8>),
+    Rule { rule: usize, children: Vec<Node> },
+}
+
+impl Node {
+    //The derived input
+    pub fn to_bytes(&self)->Vec<u8> {
+        let mut input = Vec::new();
+        self.unparse(&mut input);
+        input
+    }
+
+    fn unparse(&self, input: &mut Vec<u8>) {
+        match *self {
+            Node::Terminal(ref bytes) => input.extend_from_slice(bytes),
+            Node::Rule { ref children, .. } => {
+                for child in children.iter() {
+                    child.unparse(input);
+                }
+            },
+        }
+    }
+
+    fn rule(&self)->Option<usize> {
+        match *self {
+            Node::Terminal(_) => None,
+            Node::Rule { rule, .. } => Some(rule),
+        }
+    }
+
+    //The node at path, a path is the child indexes from the root
+    fn get(&self, path: &[usize])->&Node {
+        path.iter().fold(self, |node, &child| match *node {
+            Node::Rule { ref children, .. } => &children[child],
+            Node::Terminal(_) => node,
+        })
+    }
+
+    fn get_mut(&mut self, path: &[usize])->&mut Node {
+        match path.split_first() {
+            Some((&child, rest)) => match *self {
+                Node::Rule { ref mut children, .. } => children[child].get_mut(rest),
+                Node::Terminal(_) => self,
+            },
+            None => self,
+        }
+    }
+
+    //Paths of the rule nodes with their rules, parents before their children
+    fn rule_paths(&self)->Vec<(Vec<usize>, usize)> {
+        fn walk(node: &Node, path: &mut Vec<usize>, paths: &mut Vec<(Vec<usize>, usize)>) {
+            if let Node::Rule { rule, ref children } = *node {
+                paths.push((path.clone(), rule));
+                for (i, child) in children.iter().enumerate() {
+                    path.push(i);
+                    walk(child, path, paths);
+                    path.pop();
+                }
+            }
+        }
+        let mut paths = Vec::new();
+        walk(self, &mut Vec::new(), &mut paths);
+        paths
+    }
+
+    //Pairs of paths (ancestor, descendant) of two nodes of the same rule
+    fn recursions(&self)->Vec<(Vec<usize>, Vec<usize>)> {
+        let paths = self.rule_paths();
+        let mut recursions = Vec::new();
+        for &(ref path, rule) in paths.iter() {
+            for len in 0..path.len() {
+                if self.get(&path[..len]).rule() == Some(rule) {
+                    recursions.push((path[..len].to_vec(), path.clone()));
+                }
+            }
+        }
+        recursions
+    }
+
+    //Number of levels of the tree
+    pub fn depth(&self)->usize {
+        match *self {
+            Node::Terminal(_) => 0,
+            Node::Rule { ref children, .. } => 1 + children.iter().map(|child| child.depth()).max().unwrap_or(0),
+        }
+    }
+}
+
+//Makes the inputs of grammar mode, see SeedManagement::set_grammar_generator
+pub struct GrammarGenerator {
+    grammar: Grammar,
+    rang: StdRng,
+    //derivation trees of the pool seeds by seed id, the splice donors
+    trees: BTreeMap<usize, Node>,
+    //the tree of the last input
+    tree: Option<Node>,
+    input: Vec<u8>,
+    mutation: &'static str,
+}
+
+impl GrammarGenerator {
+    pub fn new(grammar: Grammar)->GrammarGenerator {
+        GrammarGenerator::with_rng_seed(grammar, rand::thread_rng().gen())
+    }
+
+    //A generator whose inputs only depend on rng_seed and the seeds it is given
+    pub fn with_rng_seed(grammar: Grammar, rng_seed: u64)->GrammarGenerator {
+        GrammarGenerator {
+            grammar,
+            rang: StdRng::from_seed(&[rng_seed as usize, (rng_seed >> 32) as usize][..]),
+            trees: BTreeMap::new(),
+            tree: None,
+            input: Vec::new(),
+            mutation: "GrammarGenerate",
+        }
+    }
+
+    pub fn grammar(&self)->&Grammar {
+        &self.grammar
+    }
+
+    fn derive<R: Rng>(grammar: &Grammar, rule: usize, depth: usize, rang: &mut R)->Node {
+        let mut nodes_left = config::GRAMMAR_MAX_NODES;
+        grammar.generate(rule, depth, &mut nodes_left, rang)
+    }
+
+    //A new derivation of the first rule
+    pub fn generate(&mut self)->&[u8] {
+        let tree = GrammarGenerator::derive(&self.grammar, 0, config::GRAMMAR_MAX_DEPTH, &mut self.rang);
+        self.set_input(tree, "GrammarGenerate");
+        &self.input
+    }
+
+    //A mutation of the tree of the pool seed seed_id, or a new derivation if the seed
+    //has no tree (it was not made by this generator)
+    pub fn mutate(&mut self, seed_id: usize)->&[u8] {
+        let tree = match self.trees.get(&seed_id) {
+            Some(tree) => tree.clone(),
+            None => return self.generate(),
+        };
+        let (mutation, mutated) = match self.rang.gen_range(0, 3) {
+            0 => ("GrammarSplice", self.splice(&tree)),
+            1 => ("GrammarRecursion", self.recursion(&tree)),
+            _ => ("GrammarReplace", None),
+        };
+        let (mutation, mutated) = match mutated {
+            Some(mutated) => (mutation, mutated),
+            None => ("GrammarReplace", self.replace(&tree)),
+        };
+        if mutated.to_bytes().len() as u64 > config::MAX_FILE {
+            return self.generate();
+        }
+        self.set_input(mutated, mutation);
+        &self.input
+    }
+
+    fn set_input(&mut self, tree: Node, mutation: &'static str) {
+        self.input = tree.to_bytes();
+        self.tree = Some(tree);
+        self.mutation = mutation;
+    }
+
+    //A random subtree derived again
+    fn replace(&mut self, tree: &Node)->Node {
+        let paths = tree.rule_paths();
+        let (ref path, rule) = paths[self.rang.gen_range(0, paths.len())];
+        let depth = config::GRAMMAR_MAX_DEPTH.saturating_sub(path.len());
+        let mut mutated = tree.clone();
+        *mutated.get_mut(path) = GrammarGenerator::derive(&self.grammar, rule, depth, &mut self.rang);
+        mutated
+    }
+
+    //A random subtree of another seed put in place of a subtree of the same rule,
+    //None if tree has no such subtree
+    fn splice(&mut self, tree: &Node)->Option<Node> {
+        if self.trees.is_empty() {
+            return None;
+        }
+        let donor = self.trees.values().nth(self.rang.gen_range(0, self.trees.len()))?;
+        let donor_paths = donor.rule_paths();
+        let (ref donor_path, rule) = donor_paths[self.rang.gen_range(0, donor_paths.len())];
+        let paths: Vec<Vec<usize>> = tree.rule_paths().into_iter().filter(|path| path.1 == rule).map(|path| path.0).collect();
+        if paths.is_empty() {
+            return None;
+        }
+        let mut mutated = tree.clone();
+        *mutated.get_mut(&paths[self.rang.gen_range(0, paths.len())]) = donor.get(donor_path).clone();
+        Some(mutated)
+    }
+
+    //A subtree that contains a subtree of its own rule is repeated inside itself a
+    //few times, e.g. (1) becomes (((1))). None if tree has no recursion.
+    fn recursion(&mut self, tree: &Node)->Option<Node> {
+        let recursions = tree.recursions();
+        if recursions.is_empty() {
+            return None;
+        }
+        let (ref outer, ref inner) = recursions[self.rang.gen_range(0, recursions.len())];
+        let outer_node = tree.get(outer);
+        let inner_path = &inner[outer.len()..];
+        let mut expanded = outer_node.clone();
+        for _ in 0..self.rang.gen_range(1, config::GRAMMAR_MAX_RECURSION + 1) {
+            let mut level = outer_node.clone();
+            *level.get_mut(inner_path) = expanded;
+            expanded = level;
+        }
+        let mut mutated = tree.clone();
+        *mutated.get_mut(outer) = expanded;
+        Some(mutated)
+    }
+
+    //The last input, valid until the next call
+    pub fn current_input(&self)->&[u8] {
+        &self.input
+    }
+
+    //The derivation tree of the last input
+    pub fn current_tree(&self)->Option<&Node> {
+        self.tree.as_ref()
+    }
+
+    //How the last input was made, e.g. "GrammarSplice"
+    pub fn mutation_name(&self)->&'static str {
+        self.mutation
+    }
+
+    //Whether the last input is a new derivation, made from no seed
+    pub fn is_generated(&self)->bool {
+        self.mutation == "GrammarGenerate"
+    }
+
+    //tree went into the pool as seed seed_id, it gets mutated and spliced from now on
+    pub fn add_tree(&mut self, seed_id: usize, tree: Node) {
+        self.trees.insert(seed_id, tree);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUMS: &str = "# sums of binary numbers\n<expr> ::= <num> | \"(\" <expr> \")\"\n         | <expr> \"+\" <expr>\n<num>  ::= \"0\" | \"1\" | <num> \"0\"\n";
+
+    //Whether input is an <expr> of SUMS
+    fn is_sum(input: &[u8])->bool {
+        fn term(input: &[u8], i: &mut usize)->bool {
+            match input.get(*i) {
+                Some(&b'(') => {
+                    *i += 1;
+                    expr(input, i) && input.get(*i) == Some(&b')') && { *i += 1; true }
+                },
+                Some(&b'0') | Some(&b'1') => {
+                    *i += 1;
+                    while input.get(*i) == Some(&b'0') {
+                        *i += 1;
+                    }
+                    true
+                },
+                _ => false,
+            }
+        }
+        fn expr(input: &[u8], i: &mut usize)->bool {
+            if !term(input, i) {
+                return false;
+            }
+            while input.get(*i) == Some(&b'+') {
+                *i += 1;
+                if !term(input, i) {
+                    return false;
+                }
+            }
+            true
+        }
+        let mut i = 0;
+        expr(input, &mut i) && i == input.len()
+    }
+
+    #[test]
+    fn parses_bnf_grammars() {
+        let grammar = parse_grammar(SUMS).unwrap();
+        assert_eq!(grammar.rule_cnt(), 2);
+        assert_eq!(grammar.rule_name(1), "num");
+        assert_eq!(grammar.rules[0].len(), 3);
+        assert_eq!(grammar.min_depth, vec![2, 1]);
+        let grammar = parse_grammar("<s> ::= \"\\x41\\\"\\n\" | \"\"").unwrap();
+        assert_eq!(grammar.rules[0], vec![vec![Symbol::Terminal(b"A\"\n".to_vec())], vec![]]);
+
+        assert!(parse_grammar("").is_err());
+        assert!(parse_grammar("<a> ::= <b>").is_err());
+        assert!(parse_grammar("<a> ::= \"x\" |").is_err());
+        assert!(parse_grammar("<a> ::= \"x").is_err());
+        assert!(parse_grammar("a ::= \"x\"").is_err());
+        assert!(parse_grammar("| \"x\"").is_err());
+        //<a> has no way to end
+        let error = parse_grammar("<s> ::= <a> | \"x\"\n<a> ::= \"(\" <a> \")\"").unwrap_err();
+        assert_eq!(error.to_string(), "grammar line 2: rule <a> never derives a string");
+    }
+
+    #[test]
+    fn mutations_keep_inputs_in_the_grammar() {
+        let mut generator = GrammarGenerator::with_rng_seed(parse_grammar(SUMS).unwrap(), 5);
+        let mut max_depth = 0;
+        for id in 0..200 {
+            assert!(is_sum(generator.generate()));
+            let tree = generator.current_tree().unwrap().clone();
+            assert!(tree.depth() <= config::GRAMMAR_MAX_DEPTH + 2);
+            generator.add_tree(id, tree);
+        }
+        let mut mutations = Vec::new();
+        for i in 0..2000 {
+            let input = generator.mutate(i % 200).to_vec();
+            assert!(is_sum(&input), "{:?}", String::from_utf8_lossy(&input));
+            assert_eq!(generator.current_tree().unwrap().to_bytes(), input);
+            max_depth = max_depth.max(generator.current_tree().unwrap().depth());
+            if !mutations.contains(&generator.mutation_name()) {
+                mutations.push(generator.mutation_name());
+            }
+        }
+        mutations.sort();
+        assert_eq!(mutations, vec!["GrammarRecursion", "GrammarReplace", "GrammarSplice"]);
+        //recursion goes deeper than a derivation does
+        assert!(max_depth > config::GRAMMAR_MAX_DEPTH);
+        //a seed without a tree gets a new derivation
+        generator.mutate(1000);
+        assert_eq!(generator.mutation_name(), "GrammarGenerate");
+        assert!(generator.is_generated());
+    }
+}
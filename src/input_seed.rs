@@ -1,7 +1,22 @@
 #[derive(Debug, Clone)]
 pub struct InputSeed {
+    //given by the pool, it stays the same when seeds are removed
+    pub id:usize,
+    //id of the seed this one was mutated from
+    pub parent:Option<usize>,
+    //the stage and parameters that made it from parent, see SeedGenerator::mutation_desc
+    pub mutation:Option<String>,
     pub is_favored:bool,
     pub was_fuzzed:bool,
+    //times the seed was picked to be mutated
+    pub fuzz_level:u32,
+    //already went through the Trim state, it is not trimmed again
+    pub trim_done:bool,
+    //set by calibration: average run time, edges covered, edges that vary between runs
+    pub cal_done:bool,
+    pub exec_us:u64,
+    pub bitmap_size:u32,
+    pub var_edges:Vec<usize>,
     pub seed_vec:Vec<u8>,
 }
 
@@ -23,9 +38,18 @@ impl InputSeed {
     // }
     pub fn new(seed_vec:Vec<u8>)-> InputSeed {
         InputSeed {
+            id:0,
+            parent:None,
+            mutation:None,
             is_favored:true,
             was_fuzzed:false,
-            seed_vec:seed_vec,
+            fuzz_level:0,
+            trim_done:false,
+            cal_done:false,
+            exec_us:0,
+            bitmap_size:0,
+            var_edges:Vec::new(),
+            seed_vec,
         }
 
     }
@@ -37,4 +61,8 @@ impl InputSeed {
     pub fn get_seed_vec(&self)->Vec<u8> {
         self.seed_vec.clone()
     }
+
+    pub fn get_seed_slice(&self)->&[u8] {
+        &self.seed_vec
+    }
 }
\ No newline at end of file
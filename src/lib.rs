@@ -1,85 +1,1418 @@
 extern crate rand;
+#[macro_use]
+extern crate log;
+extern crate libc;
+extern crate getopts;
+#[cfg(test)]
+extern crate proptest;
+#[cfg(test)]
+extern crate tempfile;
+
 pub mod config;
 pub mod seed_generator;
 
 pub mod input_seed;
-use input_seed::InputSeed;
+pub mod feedback;
 
 mod seed_pool;
+pub mod shared;
+pub mod sync;
+pub mod executor;
+pub mod dictionary;
+pub mod output;
+pub mod stats;
+pub mod events;
+pub mod corpus;
+pub mod tmin;
+pub mod calibrate;
+pub mod triage;
+pub mod lineage;
+pub mod grammar;
+pub mod cli;
 
-use seed_generator::SeedGenerator;
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::time::SystemTime;
+use seed_generator::{CmpLogger, CmpOperands, Mutation, OperatorWeight, SeedGenerator};
 use seed_pool::SeedPool;
+use feedback::{ExecInfo, ExecStatus, FeedbackError, FuzzResult, SeedId, TaggedSeed};
+use sync::SyncDir;
+use executor::{Executor, ExecutorError};
+use stats::{FuzzStats, StageYields};
+use events::{EventBus, FuzzEvent};
+use calibrate::VariableEdge;
+use triage::{CrashTriage, NewBuckets, StackHasher};
+use lineage::LineageNode;
+use grammar::{GrammarGenerator, Node};
+
+
+//An input of get_seeds waiting for its feedback
+#[derive(Debug)]
+struct SeedInFlight {
+    seed_vec: Vec<u8>,
+    //id of the initial seed it is a copy of
+    ini_id: Option<usize>,
+    parent: Option<usize>,
+    mutation: Option<String>,
+    //the state and havoc operations that made it, for the yields
+    state: Option<(&'static str, Vec<&'static str>)>,
+    //its derivation tree in grammar mode
+    tree: Option<Node>,
+    //the input as it is run, if post processors repaired seed_vec
+    input: Option<Vec<u8>>,
+}
+//with_seeds and with_crashes got no seed, the pool needs one at least
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoSeedsError;
 
+impl fmt::Display for NoSeedsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the seed pool needs one seed at least")
+    }
+}
+
+impl error::Error for NoSeedsError {}
 
 // #[derive(Debug)]
 pub struct SeedManagement {
     seed_generator: SeedGenerator,
     seed_pool: SeedPool,
     in_mutate: bool,
-    covered_bit_map: [u32;config::MAP_SIZE],
+    covered_bit_map: Box<[u32; config::MAP_SIZE]>,
+    //edges calibration found to vary, they are also set in covered_bit_map
+    var_bytes: Vec<bool>,
     seed_current: Vec<u8>,
+    //the last input lives in the generator's buffer, not in seed_current
+    seed_current_in_generator: bool,
+    //the last input is an initial seed, which is in the pool already
+    seed_current_is_ini: bool,
+    //the last input repaired by the post processors, when there are some
+    seed_fixed: Vec<u8>,
+    //inputs handed out by get_seeds that have no feedback yet
+    seeds_in_flight: HashMap<SeedId, SeedInFlight>,
+    //ids of the seeds kept by give_feedback and give_exec_info, for calibrate_pending
+    uncalibrated: Vec<usize>,
+    next_seed_id: SeedId,
+    //coverage of the last fuzz_one
+    cov_info: Box<[u32; config::MAP_SIZE]>,
+    total_execs: u64,
+    paths_found: usize,
+    paths_imported: usize,
+    last_path: Option<SystemTime>,
+    events: EventBus,
+    //trim the seeds fuzz_one keeps
+    trim: bool,
+    //stage name of the last mutated input, to notice stage changes
+    last_stage: &'static str,
+    triage: CrashTriage,
+    stack_hasher: Option<StackHasher>,
+    //comparisons of the seeds for the input-to-state stage
+    cmp_logger: Option<CmpLogger>,
+    //crash exploration: only inputs that crash are kept, see with_crashes
+    crash_mode: bool,
+    yields: StageYields,
+    //grammar mode: inputs come from here instead of seed_generator
+    grammar_generator: Option<GrammarGenerator>,
+    //inputs made from the seed at seed_index so far in grammar mode
+    grammar_rounds: u32,
 }
 
 impl SeedManagement {
     pub fn new(path:& str)->SeedManagement {
+        SeedManagement::with_seed_pool(SeedPool::new(path))
+    }
+
+    //Start from these seeds instead of the default ones
+    pub fn with_seeds(seeds: Vec<Vec<u8>>)->Result<SeedManagement, NoSeedsError> {
+        if seeds.is_empty() {
+            return Err(NoSeedsError);
+        }
+        Ok(SeedManagement::with_seed_pool(SeedPool::from_seeds(seeds)))
+    }
+
+    //Crash exploration mode (afl-fuzz -C): crashes is the initial pool, and an input is
+    //only kept if it still crashes and reaches new coverage. Trimming is off, as trim only
+    //keeps a crash crashing, not its coverage.
+    pub fn with_crashes(crashes: Vec<Vec<u8>>)->Result<SeedManagement, NoSeedsError> {
+        let mut seed_m = SeedManagement::with_seeds(crashes)?;
+        seed_m.crash_mode = true;
+        seed_m.set_trim(false);
+        Ok(seed_m)
+    }
+
+    fn with_seed_pool(seed_pool: SeedPool)->SeedManagement {
+        let mut seed_generator = SeedGenerator::new();
+        seed_generator.set_trim(true);
         SeedManagement {
-            seed_generator:SeedGenerator::new(),
-            seed_pool:SeedPool::new(path),
+            seed_generator,
+            seed_pool,
             in_mutate:false,
-            covered_bit_map:[0;config::MAP_SIZE],
+            covered_bit_map: Box::new([0; config::MAP_SIZE]),
+            var_bytes: vec![false; config::MAP_SIZE],
             seed_current: Vec::new(),
+            seed_current_in_generator: false,
+            seed_current_is_ini: false,
+            seed_fixed: Vec::new(),
+            seeds_in_flight: HashMap::new(),
+            uncalibrated: Vec::new(),
+            next_seed_id: 0,
+            cov_info: Box::new([0; config::MAP_SIZE]),
+            total_execs: 0,
+            paths_found: 0,
+            paths_imported: 0,
+            last_path: None,
+            events: EventBus::default(),
+            trim: true,
+            last_stage: "init",
+            triage: CrashTriage::new(),
+            stack_hasher: None,
+            cmp_logger: None,
+            crash_mode: false,
+            yields: StageYields::default(),
+            grammar_generator: None,
+            grammar_rounds: 0,
+        }
+    }
+
+    //Replace the generator, e.g. by one with a fixed RNG seed, a dictionary or other stages
+    pub fn set_seed_generator(&mut self, mut seed_generator: SeedGenerator) {
+        seed_generator.set_trim(self.trim);
+        seed_generator.set_cmplog(self.cmp_logger.is_some());
+        self.seed_generator = seed_generator;
+    }
+
+    //Grammar mode: after the initial seeds, every input is a derivation of the grammar
+    //of grammar_generator, made by mutating the derivation trees of the pool seeds.
+    //Trimming is off, a trimmed input is seldom a derivation of the grammar.
+    pub fn set_grammar_generator(&mut self, grammar_generator: GrammarGenerator) {
+        self.grammar_generator = Some(grammar_generator);
+        self.set_trim(false);
+    }
+
+    //Number of seeds in the pool
+    pub fn pool_len(&self)->usize {
+        self.seed_pool.len()
+    }
+
+    pub fn pool_seed(&self, index:usize)->Option<&[u8]> {
+        self.seed_pool.get(index).map(|seed| seed.get_seed_slice())
+    }
+
+    //Whether fuzz_one trims seeds, on by default: the ones it keeps right away,
+    //the others (initial, imported, ...) when they are first picked for fuzzing
+    pub fn set_trim(&mut self, trim:bool) {
+        self.trim = trim;
+        self.seed_generator.set_trim(trim);
+    }
+
+    //Counters for the status screen and fuzzer_stats
+    pub fn stats(&self)->FuzzStats {
+        let (stage_cur, stage_max) = if self.grammar_generator.is_some() {
+            (self.grammar_rounds as u64, config::GRAMMAR_ROUNDS as u64)
+        }
+        else {
+            self.seed_generator.stage_progress()
+        };
+        FuzzStats {
+            execs_done: self.total_execs,
+            paths_total: self.seed_pool.len(),
+            paths_favored: self.seed_pool.favored_cnt(),
+            paths_found: self.paths_found,
+            paths_imported: self.paths_imported,
+            pending_total: self.seed_pool.pending_cnt(),
+            pending_favs: self.seed_pool.pending_favored_cnt(),
+            cycles_done: self.seed_pool.cycles_done(),
+            cur_path: self.seed_pool.seed_index(),
+            //the initial seeds are run as they are first
+            stage: if !self.in_mutate {
+                "init"
+            }
+            else if self.grammar_generator.is_some() {
+                "grammar"
+            }
+            else {
+                self.seed_generator.stage_name()
+            },
+            stage_cur,
+            stage_max,
+            edges_found: self.covered_bit_map.iter().filter(|covered| **covered != 0).count(),
+            var_edges: self.var_bytes.iter().filter(|is_var| **is_var).count(),
+            last_path: self.last_path,
+            yields: self.yields.clone(),
+        }
+    }
+
+    //Percent of the covered edges that behave the same in every run of an input.
+    //Well below 100 means new coverage is often just the target being nondeterministic.
+    pub fn stability(&self)->f64 {
+        self.stats().stability()
+    }
+
+    //The edges that vary between runs, in increasing order, with the seeds of the pool
+    //they varied for. Seeds removed from the pool since then are not listed.
+    pub fn nondeterminism_report(&self)->Vec<VariableEdge> {
+        (0..config::MAP_SIZE).filter(|&edge| self.var_bytes[edge]).map(|edge| {
+            let seeds = (0..self.seed_pool.len())
+                .filter(|&index| self.seed_pool.get(index).is_some_and(|seed| seed.var_edges.binary_search(&edge).is_ok()))
+                .collect();
+            VariableEdge { edge, seeds }
+        }).collect()
+    }
+
+    //The havoc operations with their probabilities, see seed_generator::HavocScheduler
+    pub fn havoc_weights(&self)->Vec<OperatorWeight> {
+        self.seed_generator.havoc_weights()
+    }
+
+    //Where each seed of the pool came from, see lineage::to_dot and lineage::to_json
+    pub fn lineage(&self)->Vec<LineageNode> {
+        (0..self.seed_pool.len()).filter_map(|index| self.seed_pool.get(index)).map(|seed| LineageNode {
+            id: seed.id,
+            parent: seed.parent,
+            mutation: seed.mutation.clone(),
+            len: seed.seed_vec.len(),
+        }).collect()
+    }
+
+    //Call listener with every FuzzEvent from now on
+    pub fn on_event<F>(&mut self, listener:F)
+        where F: FnMut(&FuzzEvent) + Send + 'static {
+        self.events.subscribe(Box::new(listener));
+    }
+
+    //The crash buckets so far, see triage
+    pub fn crash_triage(&self)->&CrashTriage {
+        &self.triage
+    }
+
+    //Crashes also go into buckets by the string stack_hasher computes for them
+    pub fn set_stack_hasher<F>(&mut self, stack_hasher:F)
+        where F: FnMut(&[u8])->Option<String> + Send + 'static {
+        self.stack_hasher = Some(Box::new(stack_hasher));
+    }
+
+    //Turn on the input-to-state stage: cmp_logger gets the comparisons the target
+    //makes on a seed before the seed goes through the deterministic stages
+    pub fn set_cmp_logger<F>(&mut self, cmp_logger:F)
+        where F: FnMut(&[u8])->Vec<CmpOperands> + Send + 'static {
+        self.cmp_logger = Some(Box::new(cmp_logger));
+        self.seed_generator.set_cmplog(true);
+    }
+
+    //Repair every input before it is run, see SeedGenerator::add_post_processor.
+    //The pool keeps the inputs as the mutations made them. The post processors belong
+    //to the generator, add them after set_seed_generator.
+    pub fn add_post_processor<F>(&mut self, post_processor:F)
+        where F: FnMut(&mut Vec<u8>) + Send + 'static {
+        self.seed_generator.add_post_processor(post_processor);
+    }
+
+    pub fn crash_mode(&self)->bool {
+        self.crash_mode
+    }
+
+    //How an input has to end to go into the pool
+    fn kept_status(&self)->ExecStatus {
+        if self.crash_mode { ExecStatus::Crash } else { ExecStatus::Normal }
+    }
+
+    //Id of the seed the last input was mutated from, None for an initial seed and
+    //for a new derivation of the grammar
+    fn current_parent(&self)->Option<usize> {
+        if self.seed_current_is_ini || self.current_grammar().is_some_and(|grammar_generator| grammar_generator.is_generated()) {
+            None
+        }
+        else {
+            Some(self.seed_pool.get_a_seed_to_mutate().id)
+        }
+    }
+
+    //Id of the initial seed the last input is a copy of
+    fn current_ini_id(&self)->Option<usize> {
+        if !self.seed_current_is_ini {
+            return None;
         }
+        //get_a_ini_seed has moved seed_index past the initial seed
+        self.seed_pool.get(self.seed_pool.seed_index() - 1).map(|seed| seed.id)
+    }
+
+    //The grammar generator, if it made the last input
+    fn current_grammar(&self)->Option<&GrammarGenerator> {
+        self.grammar_generator.as_ref().filter(|_| !self.seed_current_is_ini)
+    }
+
+    //How the last input was made from its parent, None if it is a copy of a seed
+    fn current_mutation(&self)->Option<String> {
+        if self.seed_current_in_generator {
+            Some(self.seed_generator.mutation_desc())
+        }
+        else {
+            self.current_grammar().map(|grammar_generator| grammar_generator.mutation_name().to_string())
+        }
+    }
+
+    //The state that made the last input and its havoc operations, None if it is a copy of a seed
+    fn current_state(&self)->Option<(&'static str, Vec<&'static str>)> {
+        if self.seed_current_in_generator {
+            Some((self.seed_generator.state_family(), self.seed_generator.havoc_ops().to_vec()))
+        }
+        else {
+            self.current_grammar().map(|grammar_generator| (grammar_generator.mutation_name(), Vec::new()))
+        }
+    }
+
+    //The derivation tree of the last input in grammar mode
+    fn current_tree(&self)->Option<Node> {
+        self.current_grammar().and_then(|grammar_generator| grammar_generator.current_tree().cloned())
+    }
+
+    //Count the run of the last input in the yields of the state that made it
+    fn record_yield(&mut self, status: ExecStatus, is_kept: bool) {
+        if let Some((state, havoc_ops)) = self.current_state() {
+            self.yields.record(state, &havoc_ops, status, is_kept);
+            self.seed_generator.reward_havoc_ops(&havoc_ops, is_kept);
+        }
+    }
+
+    //A new seed found by this instance, tree is its derivation tree in grammar mode
+    fn keep_seed(&mut self, seed_vec: Vec<u8>, is_trimmed: bool, parent: Option<usize>, mutation: Option<String>,
+                 tree: Option<Node>) {
+        if is_trimmed {
+            self.seed_pool.push_a_trimmed_seed(seed_vec);
+        }
+        else {
+            self.seed_pool.push_a_seed(seed_vec);
+        }
+        self.paths_found += 1;
+        self.last_path = Some(SystemTime::now());
+        let index = self.seed_pool.len() - 1;
+        self.seed_pool.set_origin(index, parent, mutation);
+        if let (Some(grammar_generator), Some(tree)) = (self.grammar_generator.as_mut(), tree) {
+            grammar_generator.add_tree(self.seed_pool.get(index).map_or(0, |seed| seed.id), tree);
+        }
+        if let Some(seed) = self.seed_pool.get(index) {
+            self.events.emit(&FuzzEvent::NewSeed { index, seed: seed.get_seed_slice(), imported: false });
+        }
+    }
+
+    //Tell the listeners about a crash or hang of seed
+    fn report_exec(events: &mut EventBus, seed: &[u8], exec_info: &ExecInfo) {
+        match exec_info.status {
+            ExecStatus::Crash => events.emit(&FuzzEvent::Crash { seed }),
+            ExecStatus::Timeout => events.emit(&FuzzEvent::Hang { seed }),
+            ExecStatus::Normal => {},
+        }
+    }
+
+    //Put a crash of seed into its buckets, parent_chain goes back from the seed it was mutated from
+    fn triage_crash(triage: &mut CrashTriage, stack_hasher: &mut Option<StackHasher>, seed: &[u8],
+                    cov_info: &[u32; config::MAP_SIZE], parent_chain: &[usize])->NewBuckets {
+        let stack_hash = stack_hasher.as_mut().and_then(|stack_hasher| stack_hasher(seed));
+        triage.add_crash(seed, cov_info, stack_hash.as_ref().map(|hash| &hash[..]), parent_chain)
     }
 
     //You get from me
     pub fn get_a_seed(&mut self)->Vec<u8> {
+        self.get_a_seed_ref().to_vec()
+    }
+
+    //Same as get_a_seed without copying the input out, it is valid until the next call
+    pub fn get_a_seed_ref(&mut self)->&[u8] {
+        while let Some(request) = self.next_seed() {
+            if request == Mutation::CmpLog {
+                self.log_cmps_of_seed_to_mutate();
+                continue;
+            }
+            //there is no executor to trim with
+            let seed = self.seed_pool.get_a_seed_to_mutate().get_seed_slice();
+            self.seed_generator.seed_trimmed(seed);
+        }
+        self.post_process_current();
+        self.current_input()
+    }
+
+    //Make the next input, current_seed() then returns it.
+    //Returns Mutation::Trim or Mutation::CmpLog instead if the generator needs the seed
+    //at seed_index trimmed or its comparisons logged first.
+    fn next_seed(&mut self)->Option<Mutation> {
         if !self.in_mutate {
             if let Some(seed) = self.seed_pool.get_a_ini_seed(){
-                 self.seed_current = seed.get_seed_vec();
-                 return self.seed_current.clone()
+                self.seed_current.clear();
+                self.seed_current.extend_from_slice(seed.get_seed_slice());
+                self.seed_current_in_generator = false;
+                self.seed_current_is_ini = true;
+                return None;
             }
             self.in_mutate = true;
+        }
+        self.seed_current_is_ini = false;
+        if self.grammar_generator.is_some() {
+            self.next_grammar_seed();
+            return None;
+        }
+        let mut failed_seeds = 0;
+        loop {
             let seed_to_mutate = self.seed_pool.get_a_seed_to_mutate();
-            let seed_vec_option = self.seed_generator.get_a_mutated_seed(&seed_to_mutate);
-            self.seed_current = seed_vec_option.unwrap();
-            return self.seed_current.clone();
+            let mutation = self.seed_generator.mutate_in_place(seed_to_mutate);
+            if self.seed_generator.started_new_seed() {
+                self.seed_pool.mark_fuzzed();
+            }
+            match mutation {
+                Ok(request @ Mutation::Trim) | Ok(request @ Mutation::CmpLog) => return Some(request),
+                Ok(Mutation::Mutated) => {
+                    self.seed_current_in_generator = true;
+                    let stage = self.seed_generator.stage_name();
+                    if stage != self.last_stage {
+                        self.events.emit(&FuzzEvent::StageChange { from: self.last_stage, to: stage });
+                        self.last_stage = stage;
+                    }
+                    return None;
+                },
+                Ok(Mutation::NextSeed) => {},
+                Err(_) => {
+                    failed_seeds += 1;
+                    //no seed in the pool can be mutated, hand out the original one
+                    if failed_seeds >= self.seed_pool.len() {
+                        self.seed_current.clear();
+                        self.seed_current.extend_from_slice(self.seed_pool.get_a_seed_to_mutate().get_seed_slice());
+                        self.seed_current_in_generator = false;
+                        return None;
+                    }
+                },
+            }
+            self.seed_index_move();
+        }
+    }
+
+    //Go on with the next seed of the pool
+    fn seed_index_move(&mut self) {
+        let cycles_done = self.seed_pool.cycles_done();
+        self.seed_pool.seed_index_move();
+        if self.seed_pool.cycles_done() != cycles_done {
+            self.events.emit(&FuzzEvent::CycleDone { cycles_done: self.seed_pool.cycles_done() });
+        }
+    }
+
+    //Grammar mode: mutate the tree of the seed at seed_index into seed_current
+    fn next_grammar_seed(&mut self) {
+        if self.grammar_rounds == config::GRAMMAR_ROUNDS {
+            self.grammar_rounds = 0;
+            self.seed_index_move();
+        }
+        if self.grammar_rounds == 0 {
+            self.seed_pool.mark_fuzzed();
+        }
+        self.grammar_rounds += 1;
+        let id = self.seed_pool.get_a_seed_to_mutate().id;
+        if let Some(grammar_generator) = self.grammar_generator.as_mut() {
+            let input = grammar_generator.mutate(id);
+            self.seed_current.clear();
+            self.seed_current.extend_from_slice(input);
+        }
+        self.seed_current_in_generator = false;
+    }
+
+    //Repair the last input into seed_fixed
+    fn post_process_current(&mut self) {
+        if !self.seed_generator.has_post_processors() {
+            return;
+        }
+        let input = if self.seed_current_in_generator {
+            self.seed_generator.post_process_current()
         }
         else {
-            let mut seed_to_mutate = self.seed_pool.get_a_seed_to_mutate();
-            let mut seed_vec_option = self.seed_generator.get_a_mutated_seed(&seed_to_mutate);
-            while seed_vec_option.is_none() {
-                self.seed_pool.seed_index_move();
-                seed_to_mutate = self.seed_pool.get_a_seed_to_mutate();
-                seed_vec_option = self.seed_generator.get_a_mutated_seed(&seed_to_mutate);
+            self.seed_generator.post_process(&self.seed_current)
+        };
+        self.seed_fixed.clear();
+        self.seed_fixed.extend_from_slice(input);
+    }
+
+    //The last input as it is run: current_seed repaired by the post processors
+    pub fn current_input(&self)->&[u8] {
+        if self.seed_generator.has_post_processors() {
+            &self.seed_fixed
+        }
+        else {
+            self.current_seed()
+        }
+    }
+
+    //The last input before the post processors, it is what goes into the pool
+    pub fn current_seed(&self)->&[u8] {
+        if self.seed_current_in_generator {
+            self.seed_generator.get_current_seed()
+        }
+        else {
+            &self.seed_current
+        }
+    }
+
+    //The coverage of the last input run by fuzz_one
+    pub fn last_coverage(&self)->&[u32; config::MAP_SIZE] {
+        &self.cov_info
+    }
+
+    //Get an input, run it with executor and keep it if it finds new coverage.
+    //A kept input is trimmed first, pool_seed(pool_len() - 1) is what went into the pool.
+    //The seeds kept by give_feedback or give_exec_info before are calibrated first.
+    pub fn fuzz_one<E: Executor>(&mut self, executor:&mut E)->Result<FuzzResult, ExecutorError> {
+        self.calibrate_pending(executor)?;
+        while let Some(request) = self.next_seed() {
+            if request == Mutation::CmpLog {
+                self.log_cmps_of_seed_to_mutate();
+            }
+            else {
+                self.trim_seed_to_mutate(executor)?;
             }
-            self.seed_current = seed_vec_option.unwrap();
-            return self.seed_current.clone();
         }
+        self.post_process_current();
+        let seed = if self.seed_generator.has_post_processors() {
+            &self.seed_fixed[..]
+        }
+        else if self.seed_current_in_generator {
+            self.seed_generator.get_current_seed()
+        }
+        else {
+            &self.seed_current[..]
+        };
+        let exec_info = executor.run_target(seed, &mut self.cov_info)?;
+        self.total_execs += 1;
+        SeedManagement::report_exec(&mut self.events, seed, &exec_info);
+        let parent = self.current_parent();
+        let mut new_crash_bucket = None;
+        if exec_info.status == ExecStatus::Crash {
+            let parent_chain = parent.map_or(Vec::new(), |parent| self.seed_pool.parent_chain(parent));
+            new_crash_bucket = SeedManagement::triage_crash(&mut self.triage, &mut self.stack_hasher, seed, &self.cov_info,
+                                                            &parent_chain).coverage;
+        }
+        let is_kept = exec_info.status == self.kept_status() && has_new_bits(&mut self.covered_bit_map, &self.cov_info)
+            && !self.seed_current_is_ini;
+        if is_kept {
+            let seed_vec = self.current_seed().to_vec();
+            let seed_vec = if self.trim {
+                trace!("trimming a new seed of {} bytes", seed_vec.len());
+                let total_execs = &mut self.total_execs;
+                let seed_generator = &mut self.seed_generator;
+                tmin::trim(&seed_vec, |seed, cov_info| {
+                    *total_execs += 1;
+                    executor.run_target(seed_generator.post_process(seed), cov_info)
+                })?
+            }
+            else {
+                seed_vec
+            };
+            let is_trimmed = self.trim;
+            let mutation = self.current_mutation();
+            let tree = if is_trimmed { None } else { self.current_tree() };
+            self.keep_seed(seed_vec, is_trimmed, parent, mutation, tree);
+            let index = self.seed_pool.len() - 1;
+            self.calibrate_seed(index, executor)?;
+        }
+        else if self.seed_current_is_ini {
+            //get_a_ini_seed has moved seed_index past the initial seed
+            let index = self.seed_pool.seed_index() - 1;
+            if exec_info.status != self.kept_status() {
+                warn!("initial seed {} ends with {:?}, expected {:?}", index, exec_info.status, self.kept_status());
+            }
+            else if self.seed_pool.get(index).is_some_and(|seed| !seed.cal_done) {
+                self.calibrate_seed(index, executor)?;
+            }
+        }
+        self.record_yield(exec_info.status, is_kept);
+        Ok(FuzzResult { exec_info, is_kept, new_crash_bucket })
     }
 
-    pub fn has_new_bits_and_change(&mut self, cov_info:[u32; config::MAP_SIZE])->bool {
-        let mut is_changed: bool = false;
-        for i in 0..config::MAP_SIZE {
-            if self.covered_bit_map[i] == 0 && cov_info[i] != 0{
-                self.covered_bit_map[i] = 1;
-                is_changed = true;
+    //Run the seed at index a few times and store what calibrate found on it.
+    //Its variable edges are marked as covered, so they never count as new coverage.
+    fn calibrate_seed<E: Executor>(&mut self, index:usize, executor:&mut E)->Result<(), ExecutorError> {
+        let calibration = {
+            let seed = self.seed_pool.get(index).map_or(&[][..], |seed| seed.get_seed_slice());
+            let total_execs = &mut self.total_execs;
+            let seed_generator = &mut self.seed_generator;
+            calibrate::calibrate(seed, |seed, cov_info| {
+                *total_execs += 1;
+                executor.run_target(seed_generator.post_process(seed), cov_info)
+            })?
+        };
+        let new_var_edges = calibration.var_edges.iter().filter(|&&edge| !self.var_bytes[edge]).count();
+        for &edge in calibration.var_edges.iter() {
+            self.covered_bit_map[edge] = 1;
+            self.var_bytes[edge] = true;
+        }
+        if new_var_edges != 0 {
+            info!("seed {} has {} new variable edges, stability is down to {:.2}%", index, new_var_edges, self.stability());
+        }
+        self.seed_pool.set_calibration(index, calibration);
+        Ok(())
+    }
+
+    //Calibrate the seeds that give_feedback and give_exec_info kept, and the initial seeds
+    //given back through them. Those calls have no executor to run the seeds with, so a user
+    //of get_seeds or get_a_seed calls this now and then; fuzz_one, sync_from_peers and the
+    //seeds they keep are calibrated right away.
+    //Returns the number of seeds calibrated.
+    pub fn calibrate_pending<E: Executor>(&mut self, executor:&mut E)->Result<usize, ExecutorError> {
+        let mut calibrated = 0;
+        while let Some(&id) = self.uncalibrated.first() {
+            //minimize_pool may have removed the seed
+            let index = self.seed_pool.index_of(id).filter(|&index| self.seed_pool.get(index).is_some_and(|seed| !seed.cal_done));
+            if let Some(index) = index {
+                self.calibrate_seed(index, executor)?;
+                calibrated += 1;
             }
+            self.uncalibrated.remove(0);
         }
-        is_changed
+        Ok(calibrated)
+    }
+
+    //Number of seeds calibrate_pending has still to run
+    pub fn pending_calibrations(&self)->usize {
+        self.uncalibrated.len()
+    }
+
+    //The Trim state: replace the seed about to be fuzzed by its trimmed version
+    fn trim_seed_to_mutate<E: Executor>(&mut self, executor:&mut E)->Result<(), ExecutorError> {
+        let trimmed = {
+            let seed = self.seed_pool.get_a_seed_to_mutate().get_seed_slice();
+            let total_execs = &mut self.total_execs;
+            let seed_generator = &mut self.seed_generator;
+            let trimmed = tmin::trim(seed, |seed, cov_info| {
+                *total_execs += 1;
+                executor.run_target(seed_generator.post_process(seed), cov_info)
+            })?;
+            debug!("trimmed seed {} from {} to {} bytes", self.seed_pool.seed_index(), seed.len(), trimmed.len());
+            trimmed
+        };
+        self.seed_generator.seed_trimmed(&trimmed);
+        self.seed_pool.replace_trimmed(trimmed);
+        Ok(())
     }
 
-    //You give it to me
+    //The CmpLog state: hand the comparisons of the seed about to be fuzzed to the generator
+    fn log_cmps_of_seed_to_mutate(&mut self) {
+        let cmps = {
+            let seed = self.seed_pool.get_a_seed_to_mutate().get_seed_slice();
+            self.cmp_logger.as_mut().map_or(Vec::new(), |cmp_logger| cmp_logger(seed))
+        };
+        debug!("seed {} has {} logged comparisons", self.seed_pool.seed_index(), cmps.len());
+        self.seed_generator.seed_cmp_log(&cmps);
+    }
+
+    //Run runs inputs with executor, keeping the ones that find new coverage.
+    //Returns the number of runs that crashed.
+    pub fn fuzz<E: Executor>(&mut self, executor:&mut E, runs:u64)->Result<u64, ExecutorError> {
+        let mut crashes = 0;
+        for _i in 0..runs {
+            if self.fuzz_one(executor)?.exec_info.status == ExecStatus::Crash {
+                crashes += 1;
+            }
+        }
+        Ok(crashes)
+    }
+
+    //Get n inputs at once, e.g. one for each executor of a pool.
+    //Each one has to be answered by give_feedback with its id, in any order.
+    pub fn get_seeds(&mut self, n:usize)->Vec<TaggedSeed> {
+        let mut seeds = Vec::with_capacity(n);
+        for _i in 0..n {
+            let input = self.get_a_seed_ref().to_vec();
+            let id = self.next_seed_id;
+            self.next_seed_id += 1;
+            let seed_in_flight = SeedInFlight {
+                seed_vec: self.current_seed().to_vec(),
+                ini_id: self.current_ini_id(),
+                parent: self.current_parent(),
+                mutation: self.current_mutation(),
+                state: self.current_state(),
+                tree: self.current_tree(),
+                input: if self.seed_generator.has_post_processors() { Some(input.clone()) } else { None },
+            };
+            self.seeds_in_flight.insert(id, seed_in_flight);
+            seeds.push(TaggedSeed { id, seed_vec: input });
+        }
+        seeds
+    }
+
+    //Feedback of the input with this id. Returns true if it was kept in the seed pool.
+    //The kept seed is not calibrated yet, see calibrate_pending.
+    pub fn give_feedback(&mut self, id:SeedId, cov_info:&[u32; config::MAP_SIZE], exec_info:&ExecInfo)->Result<bool, FeedbackError> {
+        let SeedInFlight { seed_vec, ini_id, parent, mutation, state, tree, input } = match self.seeds_in_flight.remove(&id) {
+            Some(seed_in_flight) => seed_in_flight,
+            None => return Err(FeedbackError::UnknownSeed(id)),
+        };
+        self.total_execs += 1;
+        let run_input = input.as_ref().unwrap_or(&seed_vec);
+        SeedManagement::report_exec(&mut self.events, run_input, exec_info);
+        if exec_info.status == ExecStatus::Crash {
+            let parent_chain = parent.map_or(Vec::new(), |parent| self.seed_pool.parent_chain(parent));
+            SeedManagement::triage_crash(&mut self.triage, &mut self.stack_hasher, run_input, cov_info, &parent_chain);
+        }
+        //crashes and hangs are not worth mutating further, except in crash mode
+        let is_kept = exec_info.status == self.kept_status() && self.has_new_bits(cov_info) && ini_id.is_none();
+        if is_kept {
+            self.keep_seed(seed_vec, false, parent, mutation, tree);
+        }
+        self.add_uncalibrated(ini_id, exec_info.status, is_kept);
+        if let Some((state, havoc_ops)) = state {
+            self.yields.record(state, &havoc_ops, exec_info.status, is_kept);
+            self.seed_generator.reward_havoc_ops(&havoc_ops, is_kept);
+        }
+        Ok(is_kept)
+    }
+
+    //Number of inputs from get_seeds still waiting for feedback
+    pub fn seeds_in_flight(&self)->usize {
+        self.seeds_in_flight.len()
+    }
+
+    //Import the inputs of the other instances that add coverage here, run with executor.
+    //The imports are calibrated like the seeds found here.
+    //Returns the number of imported inputs. An executor error ends the sync, the input
+    //it happened on is tried again by the next one.
+    pub fn sync_from_peers<E: Executor>(&mut self, sync_dir:&mut SyncDir, executor:&mut E)->Result<usize, ExecutorError> {
+        let mut cov_info = [0u32; config::MAP_SIZE];
+        sync_dir.sync(|peer, seed| self.import_seed(peer, seed, &mut cov_info, &mut *executor))
+    }
+
+    //Run seed from peer and put it into the pool if it adds coverage, then calibrate it
+    fn import_seed<E: Executor>(&mut self, peer:&str, seed:&[u8], cov_info:&mut [u32; config::MAP_SIZE], executor:&mut E)
+        ->Result<bool, ExecutorError> {
+        let exec_info = executor.run_target(self.seed_generator.post_process(seed), cov_info)?;
+        self.total_execs += 1;
+        if exec_info.status != self.kept_status() || !self.has_new_bits(cov_info) {
+            return Ok(false);
+        }
+        self.seed_pool.push_a_seed(seed.to_vec());
+        let index = self.seed_pool.len() - 1;
+        self.seed_pool.set_origin(index, None, Some(format!("Imported({})", peer)));
+        self.paths_imported += 1;
+        self.events.emit(&FuzzEvent::NewSeed { index, seed, imported: true });
+        self.calibrate_seed(index, executor)?;
+        Ok(true)
+    }
+
+    //Shrink the pool to a subset with the same coverage, see corpus::minimize.
+    //In crash mode the seeds have to keep crashing. If no seed ends the way it should,
+    //the smallest one is left, the pool is never empty.
+    //The generator starts over with the first seed that is left.
+    //Returns the number of seeds removed.
+    pub fn minimize_pool<F, E>(&mut self, run_target:F)->Result<usize, E>
+        where F: FnMut(&[u8], &mut [u32; config::MAP_SIZE])->Result<ExecInfo, E> {
+        let keep = {
+            let seeds: Vec<&[u8]> = (0..self.seed_pool.len())
+                .filter_map(|index| self.seed_pool.get(index))
+                .map(|seed| seed.get_seed_slice())
+                .collect();
+            let keep = corpus::minimize(&seeds, self.kept_status(), run_target)?;
+            if keep.is_empty() {
+                warn!("no seed of the pool ends with {:?}, keeping the smallest one", self.kept_status());
+                (0..seeds.len()).min_by_key(|&index| seeds[index].len()).into_iter().collect()
+            }
+            else {
+                keep
+            }
+        };
+        let removed = self.seed_pool.len() - keep.len();
+        self.seed_pool.retain_indexes(&keep);
+        self.seed_generator.reset();
+        info!("pool minimized to {} seeds, {} removed", keep.len(), removed);
+        Ok(removed)
+    }
+
+    pub fn has_new_bits_and_change(&mut self, cov_info:[u32; config::MAP_SIZE])->bool {
+        self.has_new_bits(&cov_info)
+    }
+
+    fn has_new_bits(&mut self, cov_info:&[u32; config::MAP_SIZE])->bool {
+        has_new_bits(&mut self.covered_bit_map, cov_info)
+    }
+
+    //You give it to me, for a run that went fine. In crash mode use give_exec_info.
     pub fn give_coverage_info(&mut self, cov_info:[u32; config::MAP_SIZE]) {
-        if self.has_new_bits_and_change(cov_info) {
-            self.seed_pool.push_a_seed(self.seed_current.clone());
+        self.give_exec_info(&cov_info, &ExecInfo::default());
+    }
+
+    //Coverage and outcome of the input from the last get_a_seed.
+    //Returns true if it was kept in the seed pool. The kept seed is not calibrated yet,
+    //see calibrate_pending.
+    pub fn give_exec_info(&mut self, cov_info:&[u32; config::MAP_SIZE], exec_info:&ExecInfo)->bool {
+        self.total_execs += 1;
+        let parent = self.current_parent();
+        if exec_info.status != ExecStatus::Normal {
+            let input = self.current_input().to_vec();
+            SeedManagement::report_exec(&mut self.events, &input, exec_info);
+            if exec_info.status == ExecStatus::Crash {
+                let parent_chain = parent.map_or(Vec::new(), |parent| self.seed_pool.parent_chain(parent));
+                SeedManagement::triage_crash(&mut self.triage, &mut self.stack_hasher, &input, cov_info, &parent_chain);
+            }
+        }
+        let is_kept = exec_info.status == self.kept_status() && self.has_new_bits(cov_info) && !self.seed_current_is_ini;
+        if is_kept {
+            let seed_vec = self.current_seed().to_vec();
+            let mutation = self.current_mutation();
+            let tree = self.current_tree();
+            self.keep_seed(seed_vec, false, parent, mutation, tree);
+        }
+        let ini_id = self.current_ini_id();
+        self.add_uncalibrated(ini_id, exec_info.status, is_kept);
+        self.record_yield(exec_info.status, is_kept);
+        is_kept
+    }
+
+    //Leave the seed just kept, or the initial seed ini_id that ended as it should, to calibrate_pending
+    fn add_uncalibrated(&mut self, ini_id:Option<usize>, status:ExecStatus, is_kept:bool) {
+        let id = if is_kept {
+            self.seed_pool.get(self.seed_pool.len() - 1).map(|seed| seed.id)
+        }
+        else if status == self.kept_status() {
+            ini_id.filter(|&id| self.seed_pool.get_by_id(id).is_some_and(|seed| !seed.cal_done))
+        }
+        else {
+            None
+        };
+        if let Some(id) = id {
+            if !self.uncalibrated.contains(&id) {
+                self.uncalibrated.push(id);
+            }
         }
     }
 }
 
+//Mark the edges of cov_info in covered_bit_map, true if one of them was not there yet
+pub(crate) fn has_new_bits(covered_bit_map:&mut [u32; config::MAP_SIZE], cov_info:&[u32; config::MAP_SIZE])->bool {
+    let mut is_changed: bool = false;
+    for (covered, cov) in covered_bit_map.iter_mut().zip(cov_info.iter()) {
+        if *covered == 0 && *cov != 0{
+            *covered = 1;
+            is_changed = true;
+        }
+    }
+    is_changed
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use std::time::Duration;
+    use executor::InProcessExecutor;
+
+    //An in-process executor and the map of 256 edges it reads
+    struct TestExecutor<F: Fn(&[u8])> {
+        executor: InProcessExecutor<F>,
+        //the harness writes here through a pointer, it is dropped after the executor
+        _map: Vec<u8>,
+    }
+
+    impl<F: Fn(&[u8])> Executor for TestExecutor<F> {
+        fn run_target(&mut self, seed: &[u8], cov_info: &mut [u32; config::MAP_SIZE])->Result<ExecInfo, ExecutorError> {
+            self.executor.run_target(seed, cov_info)
+        }
+    }
+
+    //cover marks the edges of an input in the map
+    fn in_process_executor<F: Fn(&[u8], &mut [u8])>(cover: F)->TestExecutor<impl Fn(&[u8])> {
+        let mut map = vec![0u8; 256];
+        let (map_ptr, map_len) = (map.as_mut_ptr(), map.len());
+        let harness = move |data: &[u8]| cover(data, unsafe { std::slice::from_raw_parts_mut(map_ptr, map_len) });
+        TestExecutor { executor: unsafe { InProcessExecutor::new(harness, map_ptr, map_len) }, _map: map }
+    }
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn feedback_can_come_in_any_order() {
+        let mut seed_m = SeedManagement::new("124");
+        let seeds = seed_m.get_seeds(4);
+        assert_eq!(seeds.len(), 4);
+        assert_eq!(seed_m.seeds_in_flight(), 4);
+        //the first two are the initial seeds, they are in the pool already
+        let pool_len = seed_m.seed_pool.len();
+
+        let mut cov_info = [0u32; config::MAP_SIZE];
+        let exec_info = ExecInfo::new(ExecStatus::Normal, 100);
+        for seed in seeds.iter().rev() {
+            //the initial seeds and the third input find something new
+            cov_info[0] = if seed.id == seeds[1].id { 1 } else { 0 };
+            cov_info[1] = if seed.id == seeds[2].id { 1 } else { 0 };
+            seed_m.give_feedback(seed.id, &cov_info, &exec_info).unwrap();
+        }
+        assert_eq!(seed_m.seeds_in_flight(), 0);
+        assert_eq!(seed_m.seed_pool.len(), pool_len + 1);
+        assert_eq!(seed_m.seed_pool.get(pool_len).unwrap().get_seed_slice(), &seeds[2].seed_vec[..]);
+    }
+
+    #[test]
+    fn feedback_is_accepted_once() {
+        let mut seed_m = SeedManagement::new("124");
+        let seeds = seed_m.get_seeds(1);
+        let cov_info = [0u32; config::MAP_SIZE];
+        let exec_info = ExecInfo::default();
+        assert_eq!(seed_m.give_feedback(seeds[0].id, &cov_info, &exec_info), Ok(false));
+        assert_eq!(seed_m.give_feedback(seeds[0].id, &cov_info, &exec_info), Err(FeedbackError::UnknownSeed(seeds[0].id)));
+        assert_eq!(seed_m.give_feedback(1000, &cov_info, &exec_info), Err(FeedbackError::UnknownSeed(1000)));
+    }
+
+    #[test]
+    fn sync_imports_new_coverage_once() {
+        use std::fs;
+        let sync_dir = tempfile::tempdir().unwrap();
+        for peer in ["fuzzer01", "fuzzer02"].iter() {
+            fs::create_dir_all(sync_dir.path().join(peer).join("queue")).unwrap();
+        }
+        let write_seed = |peer: &str, name: &str, seed: &[u8]| {
+            fs::write(sync_dir.path().join(peer).join("queue").join(name), seed).unwrap();
+        };
+        write_seed("fuzzer01", "id:000000,orig:a", &[1]);
+        write_seed("fuzzer01", "id:000001,src:000000,op:havoc", &[2]);
+        write_seed("fuzzer02", "id:000000,orig:a", &[1]);
+        write_seed("fuzzer02", "not_a_seed", &[3]);
+
+        //the first byte of an input is the edge it covers, and one of 4 edges at random
+        let runs = std::sync::atomic::AtomicUsize::new(0);
+        let mut executor = in_process_executor(move |data, map| {
+            map[data[0] as usize] = 1;
+            map[128 + runs.fetch_add(1, std::sync::atomic::Ordering::Relaxed) % 4] = 1;
+        });
+        let mut seed_m = SeedManagement::new("124");
+        let pool_len = seed_m.seed_pool.len();
+        let mut sync = SyncDir::new(sync_dir.path(), "fuzzer03");
+        sync.set_settle_time(Duration::from_secs(0));
+        assert_eq!(seed_m.sync_from_peers(&mut sync, &mut executor).unwrap(), 2);
+        assert_eq!(seed_m.seed_pool.len(), pool_len + 2);
+        //the imports are calibrated and know their peer
+        let lineage = seed_m.lineage();
+        assert_eq!(lineage[pool_len].parent, None);
+        assert_eq!(lineage[pool_len].mutation, Some("Imported(fuzzer01)".to_string()));
+        assert!(seed_m.seed_pool.get(pool_len + 1).unwrap().cal_done);
+        assert_eq!(seed_m.nondeterminism_report().len(), 4);
+        assert_eq!(sync.peer_progress("fuzzer01"), 2);
+        assert_eq!(seed_m.sync_from_peers(&mut sync, &mut executor).unwrap(), 0);
+
+        write_seed("fuzzer02", "id:000001,src:000000,op:flip1", &[4]);
+        assert_eq!(seed_m.sync_from_peers(&mut sync, &mut executor).unwrap(), 1);
+
+        //the progress survives a restart
+        let mut seed_m = SeedManagement::new("124");
+        let mut sync = SyncDir::new(sync_dir.path(), "fuzzer03");
+        sync.set_settle_time(Duration::from_secs(0));
+        assert_eq!(seed_m.sync_from_peers(&mut sync, &mut executor).unwrap(), 0);
+        assert_eq!(sync.peer_progress("fuzzer02"), 2);
+    }
+
+    #[test]
+    fn fuzz_keeps_inputs_with_new_edges() {
+        //one edge per value of the first byte
+        let mut executor = in_process_executor(|data, map| map[data[0] as usize] = 1);
+        let mut seed_m = SeedManagement::new("124");
+        let pool_len = seed_m.seed_pool.len();
+        assert_eq!(seed_m.fuzz(&mut executor, 100).unwrap(), 0);
+        //only the first byte matters, so every kept seed is trimmed
+        for index in pool_len..seed_m.seed_pool.len() {
+            assert!(seed_m.pool_seed(index).unwrap().len() <= config::TRIM_MIN_BYTES);
+        }
+        let covered_edges = seed_m.covered_bit_map.iter().filter(|covered| **covered != 0).count();
+        assert!(covered_edges > 1);
+        //the edge of the initial seeds is not a new seed
+        assert_eq!(seed_m.seed_pool.len(), pool_len + covered_edges - 1);
+
+        let stats = seed_m.stats();
+        assert!(stats.execs_done >= 100);
+        assert_eq!(stats.paths_found, covered_edges - 1);
+        assert_eq!(stats.edges_found, covered_edges);
+        assert!(stats.last_path.is_some());
+        //every seed is favored
+        assert_eq!((stats.paths_favored, stats.pending_favs), (stats.paths_total, stats.pending_total));
+    }
+
+    #[test]
+    fn kept_seeds_know_where_they_come_from() {
+        let mut executor = in_process_executor(|data, map| map[data[0] as usize] = 1);
+        let mut seed_m = SeedManagement::with_seeds(vec![vec![1, 2]]).unwrap();
+        seed_m.fuzz(&mut executor, 100).unwrap();
+        //the first input after the initial seed flips its first bit
+        let lineage = seed_m.lineage();
+        assert_eq!(lineage[0], LineageNode { id: 0, parent: None, mutation: None, len: 2 });
+        assert_eq!(lineage[1].parent, Some(0));
+        assert_eq!(lineage[1].mutation, Some("StateFlip1(0)".to_string()));
+        for node in lineage[1..].iter() {
+            assert!(node.mutation.is_some());
+            assert_eq!(seed_m.seed_pool.parent_chain(node.id).last(), Some(&0));
+        }
+
+        //from feedback as well
+        let seeds = seed_m.get_seeds(1);
+        let mut cov_info = [0u32; config::MAP_SIZE];
+        cov_info[1000] = 1;
+        assert_eq!(seed_m.give_feedback(seeds[0].id, &cov_info, &ExecInfo::default()), Ok(true));
+        let node = seed_m.lineage().pop().unwrap();
+        assert!(node.parent.is_some() && node.mutation.is_some());
+    }
+
+    #[test]
+    fn yields_count_runs_and_finds_of_each_state() {
+        let mut executor = in_process_executor(|data, map| map[data[0] as usize] = 1);
+        let mut seed_m = SeedManagement::with_seeds(vec![vec![1, 2]]).unwrap();
+        seed_m.set_trim(false);
+        //how often the new seeds get selected depends on the rng
+        seed_m.set_seed_generator(SeedGenerator::with_rng_seed(1));
+        seed_m.fuzz(&mut executor, 2000).unwrap();
+
+        let stats = seed_m.stats();
+        let yields = &stats.yields;
+        //the initial seed is no state's run
+        assert_eq!(yields.states.values().map(|state| state.execs).sum::<u64>(), 1999);
+        assert_eq!(yields.states.values().map(|state| state.seeds).sum::<u64>(), stats.paths_found as u64);
+        //flipping the first bit of the first byte finds a new edge
+        assert_eq!(yields.states["StateFlip1"].execs, 16);
+        assert!(yields.states["StateFlip1"].seeds >= 1);
+        let havoc_execs = yields.states.get("StateHavoc").map_or(0, |state| state.execs);
+        assert_eq!(yields.havoc_ops.values().map(|op| op.execs).sum::<u64>(), havoc_execs);
+    }
+
+    #[test]
+    fn cmp_logger_solves_magic_values() {
+        fn magic(data: &[u8])->u32 {
+            data[0] as u32 | (data[1] as u32) << 8 | (data[2] as u32) << 16 | (data[3] as u32) << 24
+        }
+        let mut executor = in_process_executor(|data, map| map[if magic(data) == 0xdead_beef { 2 } else { 1 }] = 1);
+        let mut seed_m = SeedManagement::with_seeds(vec![b"abcdefgh".to_vec()]).unwrap();
+        seed_m.set_trim(false);
+        seed_m.set_cmp_logger(|seed: &[u8]| vec![CmpOperands { size: 4, arg1: magic(seed) as u64, arg2: 0xdead_beef }]);
+        //the initial seed, then the first input-to-state replacement
+        seed_m.fuzz(&mut executor, 2).unwrap();
+        assert_eq!(seed_m.stats().paths_found, 1);
+        assert_eq!(seed_m.pool_seed(1), Some(&b"\xef\xbe\xad\xdeefgh"[..]));
+        assert_eq!(seed_m.lineage()[1].mutation, Some("StateCmpLog(0)".to_string()));
+    }
+
+    #[test]
+    fn post_processors_repair_the_inputs_that_run() {
+        use seed_generator::fixup::{self, Checksum, Field};
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let bad_checksums = Arc::new(AtomicUsize::new(0));
+        let bad = bad_checksums.clone();
+        //a crc32 of the rest of the input comes first, each value of the byte after it is an edge
+        let mut executor = in_process_executor(move |data, map| {
+            if data.len() < 5 || data[..4] != fixup::crc32(&data[4..]).to_le_bytes() {
+                bad.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            map[data[4] as usize] = 1;
+        });
+        let mut seed_m = SeedManagement::with_seeds(vec![b"\0\0\0\0hello".to_vec()]).unwrap();
+        seed_m.set_trim(false);
+        let crc = Field::new(0, 4, false).unwrap();
+        seed_m.add_post_processor(fixup::checksum_fixer(Checksum::Crc32, crc, 4, None));
+        seed_m.fuzz(&mut executor, 200).unwrap();
+
+        assert_eq!(bad_checksums.load(Ordering::Relaxed), 0);
+        assert!(seed_m.stats().paths_found > 3);
+        //the pool keeps the inputs as they were mutated
+        assert_eq!(&seed_m.pool_seed(1).unwrap()[..4], &[0, 0, 0, 0]);
+        assert_eq!(&seed_m.current_input()[4..], &seed_m.current_seed()[4..]);
+        assert_eq!(seed_m.current_input()[..4], fixup::crc32(&seed_m.current_seed()[4..]).to_le_bytes());
+    }
+
+    #[test]
+    fn grammar_mode_derives_the_inputs() {
+        use grammar::parse_grammar;
+        //every input length is an edge
+        let mut executor = in_process_executor(|data, map| map[data.len().min(255)] = 1);
+        let grammar = parse_grammar("<expr> ::= <num> | \"(\" <expr> \")\" | <expr> \"+\" <expr>\n<num> ::= \"0\" | \"1\"").unwrap();
+        let mut seed_m = SeedManagement::with_seeds(vec![b"not a sum".to_vec()]).unwrap();
+        seed_m.set_grammar_generator(GrammarGenerator::with_rng_seed(grammar, 3));
+        seed_m.fuzz(&mut executor, 500).unwrap();
+
+        let stats = seed_m.stats();
+        assert!(stats.paths_found > 5);
+        assert_eq!(stats.stage, "grammar");
+        //the initial seed has no tree, its inputs are new derivations
+        assert!(stats.yields.states["GrammarGenerate"].execs >= 1);
+        assert!(stats.yields.states.keys().any(|state| *state != "GrammarGenerate"));
+        //new derivations come from no seed
+        for node in seed_m.lineage().iter().skip(1) {
+            let mutation = node.mutation.as_ref().unwrap();
+            assert!(mutation.starts_with("Grammar"));
+            assert_eq!(node.parent.is_none(), mutation == "GrammarGenerate");
+        }
+        for index in 1..seed_m.pool_len() {
+            assert!(seed_m.pool_seed(index).unwrap().iter().all(|byte| b"01()+".contains(byte)));
+        }
+    }
+
+    #[test]
+    fn bandit_havoc_learns_from_the_runs() {
+        use seed_generator::{HavocScheduling, Stages};
+        let mut executor = in_process_executor(|data, map| map[data[0] as usize] = 1);
+        let mut seed_generator = SeedGenerator::with_rng_seed(7);
+        seed_generator.set_stages(Stages::havoc_only());
+        seed_generator.set_havoc_scheduling(HavocScheduling::Bandit);
+        let mut seed_m = SeedManagement::with_seeds(vec![vec![1, 2, 3, 4]]).unwrap();
+        seed_m.set_trim(false);
+        seed_m.set_seed_generator(seed_generator);
+        seed_m.fuzz(&mut executor, 2000).unwrap();
+
+        let weights = seed_m.havoc_weights();
+        let stats = seed_m.stats();
+        //the scheduler sees the same runs and finds as the yields
+        for (op, op_yield) in stats.yields.havoc_ops.iter() {
+            let weight = weights.iter().find(|weight| weight.op == *op).unwrap();
+            assert_eq!((weight.uses, weight.finds), (op_yield.execs, op_yield.seeds));
+        }
+        assert!(weights.iter().any(|weight| weight.finds > 0));
+        assert!(weights.iter().any(|weight| weight.weight != weights[0].weight));
+    }
+
+    #[test]
+    fn seeds_are_trimmed_before_fuzzing() {
+        let mut executor = in_process_executor(|data, map| map[data[0] as usize] = 1);
+        let mut seed_m = SeedManagement::with_seeds(vec![vec![5; 64]]).unwrap();
+        //the initial seed runs as it is, the next input comes from the trimmed one
+        seed_m.fuzz(&mut executor, 2).unwrap();
+        assert_eq!(seed_m.pool_seed(0), Some(&[5u8; config::TRIM_MIN_BYTES][..]));
+        assert_eq!(seed_m.current_seed().len(), config::TRIM_MIN_BYTES);
+        assert!(seed_m.stats().execs_done > 2);
+
+        //no trimming when it is off
+        let mut seed_m = SeedManagement::with_seeds(vec![vec![5; 64]]).unwrap();
+        seed_m.set_trim(false);
+        seed_m.fuzz(&mut executor, 2).unwrap();
+        assert_eq!(seed_m.pool_seed(0).unwrap().len(), 64);
+        //the initial seed is calibrated on its first run, new seeds when they are kept
+        let stats = seed_m.stats();
+        assert_eq!(stats.execs_done, 2 + config::CAL_CYCLES as u64 * (1 + stats.paths_found as u64));
+    }
+
+    #[test]
+    fn variable_edges_do_not_make_new_seeds() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        //one edge per value of the first byte below 64, and one of 16 edges at random
+        let runs = AtomicUsize::new(0);
+        let mut executor = in_process_executor(move |data, map| {
+            map[data[0] as usize % 64] = 1;
+            map[128 + runs.fetch_add(1, Ordering::Relaxed) % 16] = 1;
+        });
+        let mut seed_m = SeedManagement::new("124");
+        let pool_len = seed_m.seed_pool.len();
+        seed_m.fuzz(&mut executor, 200).unwrap();
+
+        let seed = seed_m.seed_pool.get(0).unwrap();
+        assert!(seed.cal_done);
+        assert_eq!(seed.var_edges, (128..144).collect::<Vec<usize>>());
+        assert_eq!(seed.bitmap_size, 2);
+        //only the first byte finds new seeds
+        let covered_edges = seed_m.covered_bit_map[..64].iter().filter(|covered| **covered != 0).count();
+        assert_eq!(seed_m.seed_pool.len(), pool_len + covered_edges - 1);
+        assert!(seed_m.seed_pool.get(pool_len).unwrap().cal_done);
+
+        //both initial seeds hit the same 16 edges, the seeds found later do as well
+        let report = seed_m.nondeterminism_report();
+        assert_eq!(report.len(), 16);
+        assert_eq!(report[0].edge, 128);
+        assert_eq!(report[0].seeds, (0..seed_m.seed_pool.len()).collect::<Vec<usize>>());
+        let stats = seed_m.stats();
+        assert_eq!(stats.var_edges, 16);
+        assert_eq!(seed_m.stability(), (covered_edges as f64) * 100.0 / (covered_edges + 16) as f64);
+    }
+
+    #[test]
+    fn listeners_get_events() {
+        use std::sync::{Arc, Mutex};
+        let mut executor = in_process_executor(|data, map| map[data[0] as usize] = 1);
+        let mut seed_m = SeedManagement::new("124");
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let events_seen = events.clone();
+        seed_m.on_event(move |event| {
+            let name = match *event {
+                FuzzEvent::NewSeed { .. } => "new seed",
+                FuzzEvent::StageChange { .. } => "stage change",
+                FuzzEvent::Crash { .. } => "crash",
+                FuzzEvent::Hang { .. } => "hang",
+                FuzzEvent::CycleDone { .. } => "cycle done",
+            };
+            events_seen.lock().unwrap().push(name);
+        });
+        seed_m.fuzz(&mut executor, 100).unwrap();
+        let seeds = seed_m.get_seeds(1);
+        seed_m.give_feedback(seeds[0].id, &[0u32; config::MAP_SIZE], &ExecInfo::new(ExecStatus::Crash, 0)).unwrap();
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.iter().filter(|name| **name == "new seed").count(), seed_m.stats().paths_found);
+        assert!(events.contains(&"stage change"));
+        assert_eq!(events.last(), Some(&"crash"));
+    }
+
+    #[test]
+    fn minimized_pool_keeps_the_coverage() {
+        let mut seed_m = SeedManagement::with_seeds(vec![vec![1, 2], vec![1], vec![2, 2, 2], vec![3]]).unwrap();
+        //one edge per byte value
+        let run_target = |seed: &[u8], cov_info: &mut [u32; config::MAP_SIZE]| {
+            for &byte in seed.iter() {
+                cov_info[byte as usize] = 1;
+            }
+            Ok::<ExecInfo, ()>(ExecInfo::default())
+        };
+        assert_eq!(seed_m.minimize_pool(run_target), Ok(2));
+        assert_eq!(seed_m.pool_len(), 2);
+        assert_eq!(seed_m.seed_pool.get(0).unwrap().get_seed_slice(), &[1, 2]);
+        assert_eq!(seed_m.seed_pool.get(1).unwrap().get_seed_slice(), &[3]);
+        //fuzzing goes on with what is left
+        assert!(!seed_m.get_seeds(10).is_empty());
+    }
+
+    #[test]
+    fn minimized_crashes_keep_crashing() {
+        //one edge per byte value, a byte from 0x80 on crashes
+        let run_target = |seed: &[u8], cov_info: &mut [u32; config::MAP_SIZE]| {
+            for &byte in seed.iter() {
+                cov_info[byte as usize] = 1;
+            }
+            let status = if seed.iter().any(|&byte| byte >= 0x80) { ExecStatus::Crash } else { ExecStatus::Normal };
+            Ok::<ExecInfo, ()>(ExecInfo::new(status, 0))
+        };
+        let mut seed_m = SeedManagement::with_crashes(vec![vec![0x80, 1], vec![0x80], vec![0x90]]).unwrap();
+        assert_eq!(seed_m.minimize_pool(run_target), Ok(1));
+        assert_eq!(seed_m.pool_seed(0), Some(&[0x80, 1][..]));
+        assert_eq!(seed_m.pool_seed(1), Some(&[0x90][..]));
+
+        //none of them crashes anymore, the smallest one is left to fuzz
+        let mut seed_m = SeedManagement::with_crashes(vec![vec![1, 2], vec![2]]).unwrap();
+        assert_eq!(seed_m.minimize_pool(run_target), Ok(1));
+        assert_eq!(seed_m.pool_seed(0), Some(&[2][..]));
+        assert!(!seed_m.get_a_seed().is_empty());
+    }
+
+    #[test]
+    fn crashes_are_put_into_buckets() {
+        //one edge per value of the first byte, values from 0x80 on crash
+        let mut executor = in_process_executor(|data, map| {
+            map[data[0] as usize] = 1;
+            assert!(data[0] < 0x80);
+        });
+        let mut seed_m = SeedManagement::with_seeds(vec![vec![b'a'; 4]]).unwrap();
+        seed_m.set_stack_hasher(|seed| Some(format!("{:x}", seed[0] >> 6)));
+        let (mut crashes, mut unique_crashes) = (0, 0);
+        for _ in 0..500 {
+            let result = seed_m.fuzz_one(&mut executor).unwrap();
+            if result.exec_info.status == ExecStatus::Crash {
+                crashes += 1;
+            }
+            if result.new_crash_bucket.is_some() {
+                unique_crashes += 1;
+            }
+        }
+        assert!(crashes > 0);
+
+        let triage = seed_m.crash_triage();
+        let coverage_buckets = triage.coverage_buckets();
+        assert_eq!(coverage_buckets.len(), unique_crashes);
+        //every crashing first byte is an edge of its own
+        assert_eq!(coverage_buckets.iter().map(|bucket| bucket.count).sum::<u64>(), crashes);
+        for bucket in coverage_buckets.iter() {
+            assert!(bucket.first_input[0] >= 0x80);
+            //back to the initial seed
+            assert_eq!(bucket.parent_chain.last(), Some(&0));
+        }
+        let stack_hash_buckets = triage.stack_hash_buckets();
+        assert!(stack_hash_buckets.len() <= 2);
+        assert_eq!(stack_hash_buckets.iter().map(|bucket| bucket.count).sum::<u64>(), crashes);
+    }
+
+    #[test]
+    fn crash_mode_keeps_crashes_only() {
+        //one edge per value of the first byte, values from 0x80 on crash
+        let mut executor = in_process_executor(|data, map| {
+            map[data[0] as usize] = 1;
+            assert!(data[0] < 0x80);
+        });
+        let mut seed_m = SeedManagement::with_crashes(vec![vec![0x90; 4]]).unwrap();
+        assert!(seed_m.crash_mode());
+        seed_m.fuzz(&mut executor, 300).unwrap();
+        assert!(seed_m.pool_len() > 1);
+        for index in 0..seed_m.pool_len() {
+            assert!(seed_m.pool_seed(index).unwrap()[0] >= 0x80);
+        }
+
+        //a run that goes fine is not kept, however new its coverage
+        seed_m.get_a_seed();
+        let mut cov_info = [0u32; config::MAP_SIZE];
+        cov_info[1000] = 1;
+        assert!(!seed_m.give_exec_info(&cov_info, &ExecInfo::default()));
+        seed_m.get_a_seed();
+        cov_info[1001] = 1;
+        assert!(seed_m.give_exec_info(&cov_info, &ExecInfo::new(ExecStatus::Crash, 0)));
+    }
+
+    #[test]
+    fn a_pool_needs_a_seed() {
+        assert_eq!(SeedManagement::with_seeds(Vec::new()).err(), Some(NoSeedsError));
+        assert_eq!(SeedManagement::with_crashes(Vec::new()).err(), Some(NoSeedsError));
+    }
+
+    #[test]
+    fn crashes_given_back_are_reported_and_triaged() {
+        let mut seed_m = SeedManagement::new("124");
+        let crashes = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let crashes_seen = crashes.clone();
+        seed_m.on_event(move |event| {
+            if let FuzzEvent::Crash { seed } = *event {
+                crashes_seen.lock().unwrap().push(seed.to_vec());
+            }
+        });
+        let mut cov_info = [0u32; config::MAP_SIZE];
+        for _ in 0..seed_m.pool_len() {
+            seed_m.get_a_seed();
+            seed_m.give_exec_info(&cov_info, &ExecInfo::default());
+        }
+        let seed = seed_m.get_a_seed();
+        cov_info[7] = 1;
+        assert!(!seed_m.give_exec_info(&cov_info, &ExecInfo::new(ExecStatus::Crash, 0)));
+
+        assert_eq!(*crashes.lock().unwrap(), vec![seed.clone()]);
+        let buckets = seed_m.crash_triage().coverage_buckets();
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(buckets[0].first_input, seed);
+        assert_eq!(buckets[0].parent_chain, vec![0]);
+    }
+
+    #[test]
+    fn seeds_given_back_are_calibrated_later() {
+        let mut executor = in_process_executor(|data, map| map[data[0] as usize] = 1);
+        let mut seed_m = SeedManagement::new("124");
+        let pool_len = seed_m.pool_len();
+        let mut cov_info = [0u32; config::MAP_SIZE];
+        cov_info[1] = 1;
+        for _ in 0..pool_len {
+            seed_m.get_a_seed();
+            seed_m.give_exec_info(&cov_info, &ExecInfo::default());
+        }
+        let seeds = seed_m.get_seeds(2);
+        cov_info[2] = 1;
+        assert_eq!(seed_m.give_feedback(seeds[0].id, &cov_info, &ExecInfo::default()), Ok(true));
+        cov_info[3] = 1;
+        assert_eq!(seed_m.give_feedback(seeds[1].id, &cov_info, &ExecInfo::new(ExecStatus::Crash, 0)), Ok(false));
+        //the initial seeds and the one kept, not the crash
+        assert_eq!(seed_m.pending_calibrations(), pool_len + 1);
+        assert!(!seed_m.seed_pool.get(pool_len).unwrap().cal_done);
+
+        assert_eq!(seed_m.calibrate_pending(&mut executor).unwrap(), pool_len + 1);
+        assert_eq!(seed_m.pending_calibrations(), 0);
+        assert!((0..seed_m.pool_len()).all(|index| seed_m.seed_pool.get(index).unwrap().cal_done));
+        assert_eq!(seed_m.calibrate_pending(&mut executor).unwrap(), 0);
+    }
+
+    #[test]
+    fn crashes_are_not_kept() {
+        let mut seed_m = SeedManagement::new("124");
+        let seeds = seed_m.get_seeds(1);
+        let mut cov_info = [0u32; config::MAP_SIZE];
+        cov_info[1] = 1;
+        let exec_info = ExecInfo::new(ExecStatus::Crash, 0);
+        assert_eq!(seed_m.give_feedback(seeds[0].id, &cov_info, &exec_info), Ok(false));
+    }
 }
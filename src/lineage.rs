@@ -0,0 +1,95 @@
+/*
+   DeepSAFL - seed lineage
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+// Every seed of the pool knows the seed it was mutated from and the state that
+// did it. The graph of that, as DOT for graphviz or as JSON for scripts, shows
+// which stages find the seeds that lead somewhere.
+
+use std::fmt::Write;
+
+//One seed of the pool, see SeedManagement::lineage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineageNode {
+    pub id: usize,
+    //None for the initial and the imported seeds, and for new derivations of a grammar
+    pub parent: Option<usize>,
+    //e.g. "StateAddArith16((12, 7))" or "Havoc[delete]", "Imported(fuzzer01)" for a
+    //seed from the queue of a peer
+    pub mutation: Option<String>,
+    pub len: usize,
+}
+
+//Quote s as a string of JSON or DOT, they escape the same characters
+fn quote(s: &str)->String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if (c as u32) < 0x20 => { let _ = write!(quoted, "\\u{:04x}", c as u32); },
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+//A digraph with an edge from every parent to its children, labelled with the mutation
+pub fn to_dot(nodes: &[LineageNode])->String {
+    let mut dot = String::from("digraph lineage {\n    node [shape=box];\n");
+    for node in nodes.iter() {
+        let _ = writeln!(dot, "    {} [label={}];", node.id, quote(&format!("id:{:06}\n{} bytes", node.id, node.len)));
+    }
+    for node in nodes.iter() {
+        if let Some(parent) = node.parent {
+            let _ = writeln!(dot, "    {} -> {} [label={}];", parent, node.id, quote(node.mutation.as_ref().map_or("", |mutation| &mutation[..])));
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+//An array with an object per seed: {"id": 3, "parent": 0, "mutation": "...", "len": 12}
+pub fn to_json(nodes: &[LineageNode])->String {
+    let mut json = String::from("[\n");
+    for (i, node) in nodes.iter().enumerate() {
+        let parent = node.parent.map_or("null".to_string(), |parent| parent.to_string());
+        let mutation = node.mutation.as_ref().map_or("null".to_string(), |mutation| quote(mutation));
+        let _ = write!(json, "  {{\"id\": {}, \"parent\": {}, \"mutation\": {}, \"len\": {}}}", node.id, parent, mutation, node.len);
+        json.push_str(if i + 1 < nodes.len() { ",\n" } else { "\n" });
+    }
+    json.push_str("]\n");
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exports_dot_and_json() {
+        let nodes = vec![
+            LineageNode { id: 0, parent: None, mutation: None, len: 4 },
+            LineageNode { id: 1, parent: Some(0), mutation: Some("StateFlip1(3)".to_string()), len: 4 },
+            LineageNode { id: 2, parent: Some(1), mutation: Some("Havoc[\"delete\"]".to_string()), len: 2 },
+        ];
+        let dot = to_dot(&nodes);
+        assert!(dot.starts_with("digraph lineage {\n"));
+        assert!(dot.contains("    0 -> 1 [label=\"StateFlip1(3)\"];\n"));
+        assert!(dot.contains("    1 -> 2 [label=\"Havoc[\\\"delete\\\"]\"];\n"));
+        assert!(dot.contains("    2 [label=\"id:000002\\n2 bytes\"];\n"));
+
+        assert_eq!(to_json(&nodes),
+                   "[\n  {\"id\": 0, \"parent\": null, \"mutation\": null, \"len\": 4},\n  \
+                    {\"id\": 1, \"parent\": 0, \"mutation\": \"StateFlip1(3)\", \"len\": 4},\n  \
+                    {\"id\": 2, \"parent\": 1, \"mutation\": \"Havoc[\\\"delete\\\"]\", \"len\": 2}\n]\n");
+        assert_eq!(to_json(&[]), "[\n]\n");
+    }
+}
@@ -1,31 +1,234 @@
+extern crate env_logger;
+extern crate getopts;
+extern crate libc;
+extern crate rand;
 extern crate seed_management;
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+use std::time::{Duration, Instant};
+
+use getopts::Options;
+use rand::Rng;
+
 use seed_management::SeedManagement;
+use seed_management::cli::{self, parse_number};
 use seed_management::config;
+use seed_management::corpus;
+use seed_management::dictionary;
+use seed_management::executor::Forkserver;
+use seed_management::feedback::ExecStatus;
+use seed_management::grammar::{self, GrammarGenerator};
+use seed_management::lineage;
+use seed_management::output::OutputDir;
+use seed_management::seed_generator::{fixup, HavocScheduling, Schedule, SeedGenerator, Stages};
+use seed_management::stats::{FuzzStats, StatsFile};
 
-extern crate rand;
-use rand::Rng;
+fn usage(program: &str, opts: &Options)->String {
+    cli::usage(program, "[ options ]",
+               "The target reads its input from stdin, or from the file that replaces @@ in its arguments.\n\
+                Set RUST_LOG (e.g. RUST_LOG=seed_management=debug) to get a log on stderr.",
+               opts)
+}
+
+fn run(args: &[String])->Result<(), String> {
+    let program = args[0].clone();
+    let mut opts = Options::new();
+    opts.optopt("i", "", "input directory with test cases", "dir");
+    opts.optopt("o", "", "output directory for fuzzer findings", "dir");
+    cli::target_opts(&mut opts);
+    opts.optopt("x", "", "dictionary file or directory", "dict");
+    opts.optopt("s", "", "seed of the random number generator", "seed");
+    opts.optopt("p", "", "power schedule: explore, exploit, fast, lin, quad (default explore)", "schedule");
+    opts.optflag("d", "", "skip the deterministic stages");
+    opts.optflag("C", "", "crash exploration mode, the test cases have to crash");
+    opts.optflag("", "text", "text mode: havoc of the text seeds also changes numbers, separators, lines, words and case");
+    opts.optflag("", "infer-fields", "infer the integer and text fields of the seeds and mutate them accordingly");
+    opts.optopt("", "grammar", "derive the inputs from this BNF grammar instead of mutating bytes", "file");
+    opts.optmulti("", "fixup", "repair a length or checksum before each run, e.g. len32be:0 or crc32le:-4:4:-4 (repeatable)", "fixer");
+    opts.optopt("", "havoc", "how havoc picks its operations: uniform, bandit (default uniform)", "scheduling");
+    opts.optopt("", "stages", "stages to run, from flip,arith,interest,extras,havoc", "list");
+    opts.optopt("E", "", "stop after this many runs", "execs");
+    opts.optflag("h", "help", "print this help");
+
+    let matches = opts.parse(&args[1..]).map_err(|e| format!("{}\n\n{}", e, usage(&program, &opts)))?;
+    if matches.opt_present("h") {
+        println!("{}", usage(&program, &opts));
+        return Ok(());
+    }
+    let (in_dir, out_dir) = match (matches.opt_str("i"), matches.opt_str("o")) {
+        (Some(in_dir), Some(out_dir)) => (in_dir, out_dir),
+        _ => return Err(format!("-i and -o are required\n\n{}", usage(&program, &opts))),
+    };
+    let target_args = matches.free.clone();
+    if target_args.is_empty() {
+        return Err(format!("no target to fuzz\n\n{}", usage(&program, &opts)));
+    }
 
+    let options = cli::forkserver_options(&matches, Path::new(&out_dir).join(".cur_input"))?;
+    let timeout = options.timeout.as_millis() as u64;
+    let rng_seed = match matches.opt_str("s") {
+        Some(rng_seed) => parse_number(&rng_seed, "seed")?,
+        None => rand::thread_rng().gen(),
+    };
+    let max_execs: Option<u64> = match matches.opt_str("E") {
+        Some(max_execs) => Some(parse_number(&max_execs, "number of runs")?),
+        None => None,
+    };
 
-pub fn run_target(_seed: Vec<u8>)->[u32; 2048] {
-    let mut cov = [0;2048];
-    let mut rang = rand::thread_rng();
-    let tims = rang.gen_range(0, 20); 
-    for _i in 0..tims {
-        let pos = rang.gen_range(0, 2048); 
-        cov[pos] = 1;
+    let mut seed_generator = SeedGenerator::with_rng_seed(rng_seed);
+    if let Some(schedule) = matches.opt_str("p") {
+        seed_generator.set_schedule(schedule.parse::<Schedule>()?);
+    }
+    if let Some(scheduling) = matches.opt_str("havoc") {
+        seed_generator.set_havoc_scheduling(scheduling.parse::<HavocScheduling>()?);
+    }
+    if matches.opt_present("d") {
+        seed_generator.set_stages(Stages::havoc_only());
+    }
+    if matches.opt_present("text") {
+        seed_generator.set_text_mode(true);
     }
-    cov
+    if matches.opt_present("infer-fields") {
+        seed_generator.set_infer_fields(true);
+    }
+    if let Some(stage_list) = matches.opt_str("stages") {
+        seed_generator.set_stages(Stages::parse(&stage_list)?);
+    }
+    if let Some(dict) = matches.opt_str("x") {
+        let extras = dictionary::load_dictionary(&dict).map_err(|e| format!("cannot load dictionary {}: {}", dict, e))?;
+        eprintln!("[*] loaded {} dictionary tokens", extras.len());
+        seed_generator.set_extras(extras);
+    }
+    for spec in matches.opt_strs("fixup") {
+        seed_generator.add_post_processor(fixup::parse_fixer(&spec)?);
+    }
+
+    let grammar = match matches.opt_str("grammar") {
+        Some(path) => {
+            let grammar = grammar::load_grammar(&path).map_err(|e| format!("cannot load grammar {}: {}", path, e))?;
+            eprintln!("[*] loaded a grammar of {} rules", grammar.rule_cnt());
+            Some(grammar)
+        },
+        None => None,
+    };
+
+    let seeds = corpus::load_inputs(&in_dir).map_err(|e| format!("cannot read {}: {}", in_dir, e))?;
+    if seeds.is_empty() {
+        return Err(format!("no usable test cases in {}", in_dir));
+    }
+    let mut out = OutputDir::create(&out_dir).map_err(|e| format!("cannot create {}: {}", out_dir, e))?;
+    for (name, seed) in seeds.iter() {
+        out.save_seed(seed, Some(name)).map_err(|e| e.to_string())?;
+    }
+
+    let mut executor = Forkserver::start(&target_args, options).map_err(|e| e.to_string())?;
+    eprintln!("[*] fuzzing {} with {} seeds, rng seed {}", target_args[0], seeds.len(), rng_seed);
+
+    let seeds = seeds.into_iter().map(|(_, seed)| seed).collect();
+    let mut seed_m = if matches.opt_present("C") {
+        SeedManagement::with_crashes(seeds)
+    }
+    else {
+        SeedManagement::with_seeds(seeds)
+    }.map_err(|e| e.to_string())?;
+    seed_m.set_seed_generator(seed_generator);
+    if let Some(grammar) = grammar {
+        seed_m.set_grammar_generator(GrammarGenerator::with_rng_seed(grammar, rng_seed));
+    }
+
+    let mut stats_file = StatsFile::create(&out, &target_args.join(" "), timeout).map_err(|e| e.to_string())?;
+    stats_file.write_fuzzer_stats(&seed_m.stats(), &out).map_err(|e| e.to_string())?;
+    //a terminal gets the status screen, anything else a status line
+    let is_tty = unsafe { libc::isatty(libc::STDERR_FILENO) } == 1;
+    let mut last_ui = Instant::now();
+    let mut last_plot = Instant::now();
+    let mut last_stats = Instant::now();
+    let mut execs: u64 = 0;
+    while max_execs.is_none_or(|max_execs| execs < max_execs) {
+        let result = seed_m.fuzz_one(&mut executor).map_err(|e| e.to_string())?;
+        execs += 1;
+        //in crash mode the kept seeds are crashes as well
+        let saved = if result.is_kept {
+            //the trimmed seed
+            let seed = seed_m.pool_seed(seed_m.pool_len() - 1).unwrap_or_default();
+            out.save_seed(seed, None).map(|_| ())
+        }
+        else {
+            Ok(())
+        };
+        //the triage tells the unique crashes
+        let saved = saved.and_then(|_| match result.exec_info.status {
+            ExecStatus::Crash if result.new_crash_bucket.is_some() => out.save_crash(seed_m.current_input()).map(|_| ()),
+            ExecStatus::Timeout => out.save_hang(seed_m.current_input(), seed_m.last_coverage()).map(|_| ()),
+            ExecStatus::Crash | ExecStatus::Normal => Ok(()),
+        });
+        saved.map_err(|e| format!("cannot write to {}: {}", out_dir, e))?;
+
+        if last_ui.elapsed() < Duration::from_millis(1000 / config::UI_TARGET_HZ) {
+            continue;
+        }
+        last_ui = Instant::now();
+        let stats = seed_m.stats();
+        stats_file.update_speed(&stats);
+        show_status(&stats_file, &stats, &out, is_tty);
+        if last_plot.elapsed() >= Duration::from_secs(config::PLOT_UPDATE_SEC) {
+            last_plot = Instant::now();
+            stats_file.append_plot_data(&stats, &out).map_err(|e| e.to_string())?;
+        }
+        if last_stats.elapsed() >= Duration::from_secs(config::STATS_UPDATE_SEC) {
+            last_stats = Instant::now();
+            stats_file.write_fuzzer_stats(&stats, &out).map_err(|e| e.to_string())?;
+            write_lineage(&seed_m, &out).map_err(|e| e.to_string())?;
+        }
+    }
+
+    let stats = seed_m.stats();
+    stats_file.update_speed(&stats);
+    stats_file.append_plot_data(&stats, &out).map_err(|e| e.to_string())?;
+    stats_file.write_fuzzer_stats(&stats, &out).map_err(|e| e.to_string())?;
+    write_lineage(&seed_m, &out).map_err(|e| e.to_string())?;
+    write_havoc_weights(&seed_m, &out).map_err(|e| e.to_string())?;
+    show_status(&stats_file, &stats, &out, is_tty);
+    Ok(())
 }
 
-fn main() {
-    let mut seed_m = SeedManagement::new("124");
-    for _i in 0..100 {
-        let seed = seed_m.get_a_seed();
-        println!("{:?}", seed);
-        let cov = run_target(seed);
-        // seed_management.deal_cov(seed, cov);
+//lineage.dot and lineage.json, the ids are the ones of the queue files
+fn write_lineage(seed_m: &SeedManagement, out: &OutputDir)->io::Result<()> {
+    let nodes = seed_m.lineage();
+    fs::write(out.out_dir().join("lineage.dot"), lineage::to_dot(&nodes))?;
+    fs::write(out.out_dir().join("lineage.json"), lineage::to_json(&nodes))
+}
 
+//havoc_weights, a line per havoc operation with its probability, uses and finds
+fn write_havoc_weights(seed_m: &SeedManagement, out: &OutputDir)->io::Result<()> {
+    let mut table = String::from("# op          weight  uses        finds\n");
+    for weight in seed_m.havoc_weights() {
+        table.push_str(&format!("{:<13} {:.4}  {:<11} {}\n", weight.op, weight.weight, weight.uses, weight.finds));
     }
+    fs::write(out.out_dir().join("havoc_weights"), table)
+}
 
+fn show_status(stats_file: &StatsFile, stats: &FuzzStats, out: &OutputDir, is_tty: bool) {
+    if is_tty {
+        //back to the top left corner and clear the screen
+        eprint!("\x1b[H\x1b[2J{}", stats_file.status_screen(stats, out));
+    }
+    else {
+        eprintln!("[*] {} execs ({:.0}/sec), {} paths, {} pending, {} edges, {} unique crashes, {} unique hangs",
+                  stats.execs_done, stats_file.execs_per_sec(), stats.paths_total, stats.pending_total,
+                  stats.edges_found, out.unique_crashes(), out.unique_hangs());
+    }
 }
 
+fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    let args: Vec<String> = env::args().collect();
+    if let Err(e) = run(&args) {
+        eprintln!("[-] {}", e);
+        process::exit(1);
+    }
+}
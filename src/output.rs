@@ -0,0 +1,141 @@
+/*
+   DeepSAFL - output directory
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+// The same layout as afl-fuzz -o: queue/ keeps the seed pool, crashes/ and hangs/
+// keep the inputs that crash or time out with coverage not seen in an earlier one.
+// Which crashes are unique is up to the crash triage (FuzzResult::new_crash_bucket),
+// only the hangs are told apart here.
+// All the files are named id:NNNNNN[,...], so the directory can be a peer in a sync dir.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use config;
+use super::has_new_bits;
+
+#[derive(Debug)]
+pub struct OutputDir {
+    out_dir: PathBuf,
+    queue_cnt: u64,
+    unique_crashes: u64,
+    unique_hangs: u64,
+    last_crash: Option<SystemTime>,
+    last_hang: Option<SystemTime>,
+    hang_bit_map: Box<[u32; config::MAP_SIZE]>,
+}
+
+impl OutputDir {
+    //Create the directory; one that already has a queue is refused
+    pub fn create<P: AsRef<Path>>(out_dir: P)->io::Result<OutputDir> {
+        let out_dir = out_dir.as_ref().to_path_buf();
+        let queue_dir = out_dir.join("queue");
+        if queue_dir.is_dir() && fs::read_dir(&queue_dir)?.next().is_some() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists,
+                format!("{} already has a queue, use another output directory", out_dir.display())));
+        }
+        for sub_dir in ["queue", "crashes", "hangs"].iter() {
+            fs::create_dir_all(out_dir.join(sub_dir))?;
+        }
+        Ok(OutputDir {
+            out_dir,
+            queue_cnt: 0,
+            unique_crashes: 0,
+            unique_hangs: 0,
+            last_crash: None,
+            last_hang: None,
+            hang_bit_map: Box::new([0; config::MAP_SIZE]),
+        })
+    }
+
+    pub fn out_dir(&self)->&Path {
+        &self.out_dir
+    }
+
+    pub fn queue_cnt(&self)->u64 {
+        self.queue_cnt
+    }
+
+    pub fn unique_crashes(&self)->u64 {
+        self.unique_crashes
+    }
+
+    pub fn unique_hangs(&self)->u64 {
+        self.unique_hangs
+    }
+
+    //When the last unique crash was saved
+    pub fn last_crash(&self)->Option<SystemTime> {
+        self.last_crash
+    }
+
+    pub fn last_hang(&self)->Option<SystemTime> {
+        self.last_hang
+    }
+
+    //Write a seed of the pool to queue/, orig is the file name of an initial seed
+    pub fn save_seed(&mut self, seed: &[u8], orig: Option<&str>)->io::Result<PathBuf> {
+        let name = match orig {
+            Some(orig) => format!("id:{:06},orig:{}", self.queue_cnt, orig),
+            None => format!("id:{:06}", self.queue_cnt),
+        };
+        let path = self.out_dir.join("queue").join(name);
+        fs::write(&path, seed)?;
+        self.queue_cnt += 1;
+        Ok(path)
+    }
+
+    //Write a unique crash to crashes/, one that opened a coverage bucket of the triage
+    pub fn save_crash(&mut self, seed: &[u8])->io::Result<PathBuf> {
+        let path = self.out_dir.join("crashes").join(format!("id:{:06}", self.unique_crashes));
+        fs::write(&path, seed)?;
+        self.unique_crashes += 1;
+        self.last_crash = Some(SystemTime::now());
+        Ok(path)
+    }
+
+    //Write a timed out input to hangs/ if it covers an edge no earlier hang did
+    pub fn save_hang(&mut self, seed: &[u8], cov_info: &[u32; config::MAP_SIZE])->io::Result<Option<PathBuf>> {
+        if !has_new_bits(&mut self.hang_bit_map, cov_info) {
+            return Ok(None);
+        }
+        let path = self.out_dir.join("hangs").join(format!("id:{:06}", self.unique_hangs));
+        fs::write(&path, seed)?;
+        self.unique_hangs += 1;
+        self.last_hang = Some(SystemTime::now());
+        Ok(Some(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile;
+
+    #[test]
+    fn saves_seeds_and_unique_crashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut out = OutputDir::create(dir.path().join("out")).unwrap();
+        let path = out.save_seed(b"abc", Some("seed.txt")).unwrap();
+        assert!(path.ends_with("queue/id:000000,orig:seed.txt"));
+        assert!(out.save_seed(b"abd", None).unwrap().ends_with("queue/id:000001"));
+        assert_eq!(fs::read(path).unwrap(), b"abc");
+
+        let mut cov_info = [0u32; config::MAP_SIZE];
+        cov_info[1] = 1;
+        assert!(out.save_crash(b"x").unwrap().ends_with("crashes/id:000000"));
+        //a hang is not a crash
+        assert!(out.save_hang(b"z", &cov_info).unwrap().is_some());
+        assert!(out.save_hang(b"y", &cov_info).unwrap().is_none());
+        assert_eq!((out.unique_crashes(), out.unique_hangs()), (1, 1));
+
+        //the queue is not overwritten by a second run
+        assert!(OutputDir::create(dir.path().join("out")).is_err());
+    }
+}
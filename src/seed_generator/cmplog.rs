@@ -0,0 +1,123 @@
+/*
+   DeepSAFL - input-to-state replacements
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+// The idea of RedQueen: the operands of the comparisons a target makes often come
+// straight from the input. Where one operand of a comparison shows up in the seed,
+// writing the other operand there passes the comparison, which no bit flip or
+// INTERESTING_* value is likely to do for a 4 or 8 byte magic value. Operands are
+// looked for in both byte orders, and the other operand is also written plus and
+// minus one, for the comparisons that are not an equality.
+
+use std::collections::HashSet;
+
+use super::config;
+
+//Gets the comparisons the target makes while it runs an input, e.g. by running
+//a build of the target that logs them
+pub type CmpLogger = Box<dyn FnMut(&[u8])->Vec<CmpOperands> + Send>;
+
+//The operands of one comparison of the target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CmpOperands {
+    //width of the operands in bytes: 1, 2, 4 or 8
+    pub size: u8,
+    pub arg1: u64,
+    pub arg2: u64,
+}
+
+//Write bytes over the seed at pos
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CmpReplacement {
+    pub pos: u64,
+    pub bytes: Vec<u8>,
+}
+
+//The size low bytes of value, little endian or big endian
+fn to_bytes(value: u64, size: usize, big_endian: bool)->Vec<u8> {
+    let mut bytes = value.to_le_bytes()[..size].to_vec();
+    if big_endian {
+        bytes.reverse();
+    }
+    bytes
+}
+
+//Every replacement of an operand of cmps found in seed by the other operand, in the
+//order of cmps, at most config::CMPLOG_MAX_REPLACEMENTS of them
+pub fn replacements(seed: &[u8], cmps: &[CmpOperands])->Vec<CmpReplacement> {
+    let mut replacements = Vec::new();
+    let mut seen = HashSet::new();
+    for cmp in cmps.iter() {
+        let size = cmp.size as usize;
+        if ![1, 2, 4, 8].contains(&size) {
+            debug!("comparison of {} bytes ignored", size);
+            continue;
+        }
+        if size > seed.len() {
+            continue;
+        }
+        let mask = if size == 8 { u64::MAX } else { (1u64 << (size * 8)) - 1 };
+        for &(pattern, other) in [(cmp.arg1, cmp.arg2), (cmp.arg2, cmp.arg1)].iter() {
+            //one byte has no byte order
+            let byte_orders: &[bool] = if size == 1 { &[false] } else { &[false, true] };
+            for &big_endian in byte_orders.iter() {
+                let pattern = to_bytes(pattern & mask, size, big_endian);
+                let positions: Vec<usize> = (0..seed.len() - size + 1).filter(|&pos| seed[pos..pos + size] == pattern[..]).collect();
+                if positions.is_empty() {
+                    continue;
+                }
+                for &value in [other, other.wrapping_add(1), other.wrapping_sub(1)].iter() {
+                    let bytes = to_bytes(value & mask, size, big_endian);
+                    if bytes == pattern {
+                        continue;
+                    }
+                    for &pos in positions.iter() {
+                        let replacement = CmpReplacement { pos: pos as u64, bytes: bytes.clone() };
+                        if seen.insert(replacement.clone()) {
+                            replacements.push(replacement);
+                            if replacements.len() >= config::CMPLOG_MAX_REPLACEMENTS {
+                                return replacements;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    replacements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replacement(pos: u64, bytes: &[u8])->CmpReplacement {
+        CmpReplacement { pos, bytes: bytes.to_vec() }
+    }
+
+    #[test]
+    fn replaces_operands_in_both_byte_orders() {
+        //0x1234 little endian at 1, big endian at 4
+        let seed = [0xaa, 0x34, 0x12, 0xaa, 0x12, 0x34];
+        let cmps = [CmpOperands { size: 2, arg1: 0x1234, arg2: 0xbeef }];
+        assert_eq!(replacements(&seed, &cmps), vec![
+            replacement(1, &[0xef, 0xbe]), replacement(1, &[0xf0, 0xbe]), replacement(1, &[0xee, 0xbe]),
+            replacement(4, &[0xbe, 0xef]), replacement(4, &[0xbe, 0xf0]), replacement(4, &[0xbe, 0xee]),
+        ]);
+
+        //the second operand is in the seed, it is replaced by the first one
+        let cmps = [CmpOperands { size: 4, arg1: 0xdeadbeef, arg2: 0x12aa_1234 }];
+        assert_eq!(replacements(&seed, &cmps)[0], replacement(1, &[0xef, 0xbe, 0xad, 0xde]));
+
+        //equal operands only give the variants, unusable sizes give nothing
+        let cmps = [CmpOperands { size: 1, arg1: 0xaa, arg2: 0xaa }, CmpOperands { size: 3, arg1: 0xaa, arg2: 0 }];
+        assert_eq!(replacements(&seed, &cmps), vec![
+            replacement(0, &[0xab]), replacement(3, &[0xab]), replacement(0, &[0xa9]), replacement(3, &[0xa9]),
+        ]);
+        assert!(replacements(&seed[..1], &[CmpOperands { size: 8, arg1: 0, arg2: 1 }]).is_empty());
+    }
+}
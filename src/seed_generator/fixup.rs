@@ -0,0 +1,306 @@
+/*
+   DeepSAFL - format-aware post processing
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+// Most mutations of a binary format break a length field or a checksum, and the target
+// rejects the input before it gets anywhere interesting. Post processors repair the
+// mutated input right before it is run: the built-in fixers write the CRC32 or Adler32
+// of a range, or the number of bytes that follow a length field. The pool keeps the
+// unrepaired input, so the mutations keep working on what they made.
+
+use std::fmt;
+use std::str::FromStr;
+
+//Repairs an input before it is run. It should only depend on the input, as the
+//inputs are repaired again when they are trimmed or calibrated.
+pub type PostProcessor = Box<dyn FnMut(&mut Vec<u8>) + Send>;
+
+//The post processors of a generator, in the order they were added
+#[derive(Default)]
+pub struct PostProcessors {
+    processors: Vec<PostProcessor>,
+    //the last repaired input
+    fixed: Vec<u8>,
+}
+
+impl fmt::Debug for PostProcessors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PostProcessors({})", self.processors.len())
+    }
+}
+
+impl PostProcessors {
+    pub fn push(&mut self, processor: PostProcessor) {
+        self.processors.push(processor);
+    }
+
+    pub fn is_empty(&self)->bool {
+        self.processors.is_empty()
+    }
+
+    //input after every post processor, input itself if there is none
+    pub fn apply<'a>(&'a mut self, input: &'a [u8])->&'a [u8] {
+        if self.processors.is_empty() {
+            return input;
+        }
+        self.fixed.clear();
+        self.fixed.extend_from_slice(input);
+        for processor in self.processors.iter_mut() {
+            processor(&mut self.fixed);
+        }
+        &self.fixed
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    //CRC-32 of zip, gzip and png
+    Crc32,
+    //Adler-32 of zlib
+    Adler32,
+}
+
+impl Checksum {
+    pub fn name(&self)->&'static str {
+        match *self {
+            Checksum::Crc32 => "crc32",
+            Checksum::Adler32 => "adler32",
+        }
+    }
+
+    pub fn compute(&self, data: &[u8])->u32 {
+        match *self {
+            Checksum::Crc32 => crc32(data),
+            Checksum::Adler32 => adler32(data),
+        }
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Checksum {
+    type Err = String;
+
+    fn from_str(name: &str)->Result<Checksum, String> {
+        match name {
+            "crc32" => Ok(Checksum::Crc32),
+            "adler32" => Ok(Checksum::Adler32),
+            _ => Err(format!("unknown checksum '{}'", name)),
+        }
+    }
+}
+
+pub fn crc32(data: &[u8])->u32 {
+    let mut crc = !0u32;
+    for &byte in data.iter() {
+        crc ^= byte as u32;
+        for _bit in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+pub fn adler32(data: &[u8])->u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data.iter() {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+//Position offset in an input of len bytes, a negative offset counts from the end
+fn resolve(offset: i64, len: usize)->Option<usize> {
+    if offset < 0 {
+        len.checked_sub(offset.unsigned_abs() as usize)
+    }
+    else if offset as usize <= len {
+        Some(offset as usize)
+    }
+    else {
+        None
+    }
+}
+
+//Where a fixer writes its value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+    //a negative offset counts from the end of the input
+    offset: i64,
+    //1, 2, 4 or 8 bytes
+    width: usize,
+    big_endian: bool,
+}
+
+impl Field {
+    //The field of width bytes at offset, the width has to be 1, 2, 4 or 8
+    pub fn new(offset: i64, width: usize, big_endian: bool)->Result<Field, String> {
+        match width {
+            1 | 2 | 4 | 8 => Ok(Field { offset, width, big_endian }),
+            _ => Err(format!("a field is 1, 2, 4 or 8 bytes wide, not {}", width)),
+        }
+    }
+
+    pub fn offset(&self)->i64 {
+        self.offset
+    }
+
+    pub fn width(&self)->usize {
+        self.width
+    }
+
+    pub fn big_endian(&self)->bool {
+        self.big_endian
+    }
+
+    //Start of the field in an input of len bytes, None if it does not fit
+    fn position(&self, len: usize)->Option<usize> {
+        resolve(self.offset, len).filter(|&pos| pos + self.width <= len)
+    }
+
+    //Write the width low bytes of value at pos
+    fn write(&self, input: &mut [u8], pos: usize, value: u64) {
+        let mut bytes = value.to_le_bytes()[..self.width].to_vec();
+        if self.big_endian {
+            bytes.reverse();
+        }
+        input[pos..pos + self.width].copy_from_slice(&bytes);
+    }
+}
+
+//Write the checksum of input[start..end] into the 4 byte field. end None is the end of
+//the input, negative positions count from the end. Inputs the field or the range do
+//not fit in are left alone.
+pub fn checksum_fixer(checksum: Checksum, field: Field, start: i64, end: Option<i64>)->PostProcessor {
+    let field = Field { width: 4, ..field };
+    Box::new(move |input: &mut Vec<u8>| {
+        let len = input.len();
+        let range = (resolve(start, len), end.map_or(Some(len), |end| resolve(end, len)));
+        if let (Some(pos), (Some(start), Some(end))) = (field.position(len), range) {
+            if start <= end {
+                let value = checksum.compute(&input[start..end]);
+                field.write(input, pos, value as u64);
+            }
+        }
+    })
+}
+
+//Write the number of bytes after the field, plus adjust, into the field, e.g. adjust
+//is the field's width for a length that counts the field itself. The value is cut to
+//the width of the field, inputs the field does not fit in are left alone.
+pub fn length_fixer(field: Field, adjust: i64)->PostProcessor {
+    Box::new(move |input: &mut Vec<u8>| {
+        if let Some(pos) = field.position(input.len()) {
+            let value = (input.len() - pos - field.width) as i64 + adjust;
+            field.write(input, pos, value as u64);
+        }
+    })
+}
+
+fn parse_position(text: &str)->Result<i64, String> {
+    text.parse().map_err(|_| format!("bad position '{}'", text))
+}
+
+//A built-in fixer from its description:
+//  crc32le:OFFSET[:START[:END]] (or crc32be, adler32le, adler32be) writes the checksum
+//    of START..END (default: from the start of the input up to the field) at OFFSET
+//  len8:OFFSET[:ADJUST] (or len16le, len16be, len32le, ..., len64be) writes the
+//    number of bytes after the field, plus ADJUST, at OFFSET
+//A negative OFFSET, START or END counts from the end of the input.
+pub fn parse_fixer(spec: &str)->Result<PostProcessor, String> {
+    let parts: Vec<&str> = spec.split(':').collect();
+    if parts.len() < 2 {
+        return Err(format!("fixer '{}' has no offset", spec));
+    }
+    let kind = parts[0];
+    let offset = parse_position(parts[1])?;
+    let (name, big_endian) = if let Some(name) = kind.strip_suffix("le") {
+        (name, false)
+    }
+    else if let Some(name) = kind.strip_suffix("be") {
+        (name, true)
+    }
+    else {
+        (kind, false)
+    };
+    if let Some(bits) = name.strip_prefix("len") {
+        let width = match bits {
+            "8" if kind == "len8" => 1,
+            "16" if kind != name => 2,
+            "32" if kind != name => 4,
+            "64" if kind != name => 8,
+            _ => return Err(format!("unknown fixer '{}'", kind)),
+        };
+        if parts.len() > 3 {
+            return Err(format!("fixer '{}' takes OFFSET[:ADJUST]", spec));
+        }
+        let adjust = match parts.get(2) {
+            Some(adjust) => adjust.parse().map_err(|_| format!("bad adjust '{}'", adjust))?,
+            None => 0,
+        };
+        return Ok(length_fixer(Field::new(offset, width, big_endian)?, adjust));
+    }
+    if kind == name {
+        return Err(format!("fixer '{}' needs a byte order, le or be", kind));
+    }
+    let checksum: Checksum = name.parse().map_err(|_| format!("unknown fixer '{}'", kind))?;
+    if parts.len() > 4 {
+        return Err(format!("fixer '{}' takes OFFSET[:START[:END]]", spec));
+    }
+    let field = Field::new(offset, 4, big_endian)?;
+    match (parts.get(2), parts.get(3)) {
+        (Some(start), end) => {
+            let end = match end {
+                Some(end) => Some(parse_position(end)?),
+                None => None,
+            };
+            Ok(checksum_fixer(checksum, field, parse_position(start)?, end))
+        },
+        (None, _) => Ok(checksum_fixer(checksum, field, 0, Some(offset))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums_match_the_reference_values() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        assert_eq!("adler32".parse::<Checksum>(), Ok(Checksum::Adler32));
+    }
+
+    #[test]
+    fn fixers_repair_lengths_and_checksums() {
+        let mut post_processors = PostProcessors::default();
+        assert_eq!(post_processors.apply(b"abc"), b"abc");
+
+        //a 2 byte big endian length of the data, then a little endian crc32 of it at the end
+        post_processors.push(parse_fixer("len16be:0:-4").unwrap());
+        post_processors.push(parse_fixer("crc32le:-4:2:-4").unwrap());
+        let fixed = post_processors.apply(b"\x00\x00123456789\0\0\0\0").to_vec();
+        assert_eq!(&fixed[..2], &[0, 9]);
+        assert_eq!(&fixed[2..11], b"123456789");
+        assert_eq!(&fixed[11..], &0xcbf4_3926u32.to_le_bytes());
+
+        //too short for the crc, only the length is written
+        assert_eq!(post_processors.apply(b"\xff\xff\x01"), b"\xff\xfd\x01");
+
+        assert!(Field::new(0, 3, false).is_err() && Field::new(0, 16, true).is_err());
+        assert_eq!(Field::new(-4, 8, true).map(|field| (field.offset(), field.width())), Ok((-4, 8)));
+
+        for spec in ["crc32:0", "len16:0", "len24le:0", "crc32le", "md5le:0", "len8:x", "crc32be:0:1:2:3"].iter() {
+            assert!(parse_fixer(spec).is_err(), "{}", spec);
+        }
+    }
+}
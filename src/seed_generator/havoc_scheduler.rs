@@ -0,0 +1,176 @@
+/*
+   DeepSAFL - havoc operator scheduling
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+// afl-fuzz picks every havoc operation with the same probability. With the bandit
+// scheduling each operation is picked in proportion to its estimated success rate
+// (new seeds per use, with one success in two uses as the prior), mixed with a bit
+// of uniform choice so no operation is ever given up on, in the spirit of MOpt.
+
+use std::fmt;
+use std::str::FromStr;
+
+use rand::Rng;
+
+use super::config;
+use super::mutator::HAVOC_OPS;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HavocScheduling {
+    //every operation is as likely as any other, like afl-fuzz
+    #[default]
+    Uniform,
+    //operations that found seeds are picked more often
+    Bandit,
+}
+
+impl HavocScheduling {
+    pub fn name(&self)->&'static str {
+        match *self {
+            HavocScheduling::Uniform => "uniform",
+            HavocScheduling::Bandit => "bandit",
+        }
+    }
+}
+
+impl fmt::Display for HavocScheduling {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for HavocScheduling {
+    type Err = String;
+
+    fn from_str(name: &str)->Result<HavocScheduling, String> {
+        match name {
+            "uniform" => Ok(HavocScheduling::Uniform),
+            "bandit" => Ok(HavocScheduling::Bandit),
+            _ => Err(format!("unknown havoc scheduling '{}'", name)),
+        }
+    }
+}
+
+//One line of the weight table, see HavocScheduler::weights
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OperatorWeight {
+    pub op: &'static str,
+    //probability of being picked when the seed is long enough for every operation
+    pub weight: f64,
+    pub uses: u64,
+    pub finds: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct HavocScheduler {
+    scheduling: HavocScheduling,
+    uses: [u64; HAVOC_OPS.len()],
+    finds: [u64; HAVOC_OPS.len()],
+}
+
+impl HavocScheduler {
+    pub fn new(scheduling: HavocScheduling)->HavocScheduler {
+        HavocScheduler { scheduling, ..HavocScheduler::default() }
+    }
+
+    pub fn scheduling(&self)->HavocScheduling {
+        self.scheduling
+    }
+
+    //Estimated success rate of operation op
+    fn score(&self, op: usize)->f64 {
+        (self.finds[op] + 1) as f64 / (self.uses[op] + 2) as f64
+    }
+
+    //Probabilities of the first op_cnt operations
+    fn probabilities(&self, op_cnt: usize)->Vec<f64> {
+        let uniform = 1.0 / op_cnt as f64;
+        if self.scheduling == HavocScheduling::Uniform {
+            return vec![uniform; op_cnt];
+        }
+        let total: f64 = (0..op_cnt).map(|op| self.score(op)).sum();
+        (0..op_cnt).map(|op| {
+            config::HAVOC_BANDIT_EXPLORE * uniform + (1.0 - config::HAVOC_BANDIT_EXPLORE) * self.score(op) / total
+        }).collect()
+    }
+
+    //One of the first op_cnt operations
+    pub fn choose<R: Rng>(&self, op_cnt: u32, rang: &mut R)->u32 {
+        if self.scheduling == HavocScheduling::Uniform {
+            return rang.gen_range(0, op_cnt);
+        }
+        let mut left = rang.gen::<f64>();
+        for (op, probability) in self.probabilities(op_cnt as usize).into_iter().enumerate() {
+            if left < probability {
+                return op as u32;
+            }
+            left -= probability;
+        }
+        //rounding
+        op_cnt - 1
+    }
+
+    //An input made by the havoc operations ops was run, found tells if it went into the pool
+    pub fn reward(&mut self, ops: &[&'static str], found: bool) {
+        for name in ops.iter() {
+            //delete has two numbers, like in afl-fuzz
+            for op in (0..HAVOC_OPS.len()).filter(|&op| HAVOC_OPS[op] == *name) {
+                self.uses[op] += 1;
+                if found {
+                    self.finds[op] += 1;
+                }
+            }
+        }
+    }
+
+    //Every operation with the probability it has now and what it did so far
+    pub fn weights(&self)->Vec<OperatorWeight> {
+        self.probabilities(HAVOC_OPS.len()).into_iter().enumerate().map(|(op, weight)| OperatorWeight {
+            op: HAVOC_OPS[op],
+            weight,
+            uses: self.uses[op],
+            finds: self.finds[op],
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{SeedableRng, StdRng};
+
+    #[test]
+    fn uniform_weights_stay_the_same() {
+        let mut scheduler = HavocScheduler::new(HavocScheduling::Uniform);
+        scheduler.reward(&["flip1"], true);
+        let weights = scheduler.weights();
+        assert_eq!(weights.len(), HAVOC_OPS.len());
+        assert!(weights.iter().all(|weight| weight.weight == weights[0].weight));
+        assert_eq!((weights[0].uses, weights[0].finds), (1, 1));
+        assert_eq!("bandit".parse::<HavocScheduling>(), Ok(HavocScheduling::Bandit));
+    }
+
+    #[test]
+    fn bandit_prefers_operations_that_find_seeds() {
+        let mut scheduler = HavocScheduler::new(HavocScheduling::Bandit);
+        for i in 0..1000 {
+            scheduler.reward(&["flip8"], i % 10 == 0);
+            scheduler.reward(&HAVOC_OPS.iter().cloned().filter(|&op| op != "flip8").collect::<Vec<_>>(), false);
+        }
+        let weights = scheduler.weights();
+        let weight = |name: &str| weights.iter().find(|weight| weight.op == name).unwrap().weight;
+        assert!(weight("flip8") > 10.0 * weight("arith8+"));
+        assert!(weight("arith8+") > 0.0);
+        assert!((weights.iter().map(|weight| weight.weight).sum::<f64>() - 1.0).abs() < 1e-9);
+
+        let mut rang = StdRng::from_seed(&[1usize, 2][..]);
+        let flip8_cnt = (0..1000).filter(|_| scheduler.choose(config::HAVOC_WAY as u32, &mut rang) == 3).count();
+        assert!(flip8_cnt > 300);
+        //ops the seed is too short for are never chosen
+        assert!((0..1000).all(|_| scheduler.choose(9, &mut rang) < 9));
+    }
+}
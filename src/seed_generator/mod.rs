@@ -10,202 +10,503 @@ mod state_parser;
 
 use self::state_parser::StateParser;
 use self::state_parser::FuzzingState;
+pub use self::state_parser::{Stage, Stages};
 
 use super::config;
 mod selector;
 use self::selector::Selector;
 
 pub mod mutator;
+pub use self::mutator::MutationError;
+mod mutation_buffer;
+pub use self::mutation_buffer::MutationBuffer;
+pub mod schedule;
+pub use self::schedule::Schedule;
+pub mod cmplog;
+pub use self::cmplog::{CmpLogger, CmpOperands};
+use self::cmplog::CmpReplacement;
+pub mod havoc_scheduler;
+pub use self::havoc_scheduler::{HavocScheduler, HavocScheduling, OperatorWeight};
+pub mod fixup;
+pub mod profile;
+pub use self::profile::{FieldKind, InferredField, SeedProfile};
+pub mod text;
+pub use self::fixup::{PostProcessor, PostProcessors};
 use super::input_seed;
 
 use input_seed::InputSeed;
 
+use std::fmt;
+
 use rand;
+use rand::{Rng, SeedableRng, StdRng};
+
+//What mutate_in_place did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mutation {
+    //seed_buf holds the next input
+    Mutated,
+    //nothing left to do with this seed (finished or not selected), go on with the next one
+    NextSeed,
+    //the seed was just selected and wants trimming, call seed_trimmed with the result
+    Trim,
+    //the input-to-state stage is next, call seed_cmp_log with the comparisons of a run of the seed
+    CmpLog,
+}
 
-#[derive(Debug)]
 pub struct SeedGenerator {
     state_parser: StateParser,
     seed_selector: Selector,
+    seed_buf: MutationBuffer,
+    //all the random choices of this generator come from here, so a run can be replayed
+    rang: StdRng,
+    schedule: Schedule,
+    //dictionary tokens
+    extras: Vec<Vec<u8>>,
+    //the last mutate_in_place selected a new seed
+    seed_started: bool,
+    //stop in the Trim state of seeds that were not trimmed yet
+    trim: bool,
+    //stop in the CmpLog state
+    cmplog: bool,
+    //what the input-to-state stage writes into the current seed
+    cmp_replacements: Vec<CmpReplacement>,
+    //the havoc operations of the last input, if havoc made it
+    havoc_ops: Vec<&'static str>,
+    havoc_scheduler: HavocScheduler,
+    //repair the inputs before they are run
+    post_processors: PostProcessors,
+    //infer the fields of every seed to bias the stages
+    infer_fields: bool,
+    //text mode: havoc of text seeds also picks the text operations
+    text_mode: bool,
+    //the current seed is text and text mode is on
+    seed_is_text: bool,
+}
+
+impl fmt::Debug for SeedGenerator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SeedGenerator")
+            .field("state_parser", &self.state_parser)
+            .field("seed_selector", &self.seed_selector)
+            .field("seed_buf", &self.seed_buf)
+            .field("schedule", &self.schedule)
+            .field("havoc_scheduling", &self.havoc_scheduler.scheduling())
+            .field("extras", &self.extras.len())
+            .field("post_processors", &self.post_processors)
+            .finish()
+    }
+}
+
+impl Default for SeedGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SeedGenerator {
 
     pub fn new() -> SeedGenerator{
+        SeedGenerator::with_rng_seed(rand::thread_rng().gen())
+    }
+
+    //A generator whose mutations only depend on rng_seed and the seeds it is given
+    pub fn with_rng_seed(rng_seed:u64) -> SeedGenerator{
         SeedGenerator{
             state_parser:StateParser::new(),
             seed_selector: Selector{},
+            seed_buf: MutationBuffer::new(),
+            rang: StdRng::from_seed(&[rng_seed as usize, (rng_seed >> 32) as usize][..]),
+            schedule: Schedule::default(),
+            extras: Vec::new(),
+            seed_started: false,
+            trim: false,
+            cmplog: false,
+            cmp_replacements: Vec::new(),
+            havoc_ops: Vec::new(),
+            havoc_scheduler: HavocScheduler::default(),
+            post_processors: PostProcessors::default(),
+            infer_fields: false,
+            text_mode: false,
+            seed_is_text: false,
         }
     }
 
-    pub fn origin(input_seed: InputSeed) -> SeedGenerator{
-        SeedGenerator{
-            state_parser:StateParser::new(),
-            seed_selector: Selector{},
+    //Only go through these stages, starting with the next seed
+    pub fn set_stages(&mut self, stages: Stages) {
+        self.state_parser.set_stages(stages);
+    }
+
+    pub fn set_schedule(&mut self, schedule: Schedule) {
+        self.schedule = schedule;
+    }
+
+    //How havoc picks its operations, forgets what the operations did so far
+    pub fn set_havoc_scheduling(&mut self, scheduling: HavocScheduling) {
+        self.havoc_scheduler = HavocScheduler::new(scheduling);
+    }
+
+    //Tell the havoc scheduler whether the inputs made by the havoc operations ops
+    //found something, e.g. the ops of havoc_ops() once the last input has run
+    pub fn reward_havoc_ops(&mut self, ops: &[&'static str], found: bool) {
+        self.havoc_scheduler.reward(ops, found);
+    }
+
+    //Every havoc operation with its probability and its uses and finds so far
+    pub fn havoc_weights(&self) -> Vec<OperatorWeight> {
+        self.havoc_scheduler.weights()
+    }
+
+    //Dictionary tokens for the extras stage and havoc, starting with the next seed
+    pub fn set_extras(&mut self, extras: Vec<Vec<u8>>) {
+        self.state_parser.set_extras_cnt(extras.len() as u32);
+        self.extras = extras;
+    }
+
+    //Run post_processor on every mutated input after the ones added before, e.g. a
+    //fixup::checksum_fixer. get_current_seed stays the unrepaired input.
+    pub fn add_post_processor<F>(&mut self, post_processor: F) where F: FnMut(&mut Vec<u8>) + Send + 'static {
+        self.post_processors.push(Box::new(post_processor));
+    }
+
+    pub fn has_post_processors(&self) -> bool {
+        !self.post_processors.is_empty()
+    }
+
+    //input repaired by the post processors, valid until the next call
+    pub fn post_process<'a>(&'a mut self, input: &'a [u8]) -> &'a [u8] {
+        self.post_processors.apply(input)
+    }
+
+    //The input of the last successful mutation repaired by the post processors
+    pub(crate) fn post_process_current(&mut self) -> &[u8] {
+        self.post_processors.apply(self.seed_buf.as_slice())
+    }
+
+    //Whether mutate_in_place stops at the Trim state. Off by default, somebody
+    //has to run the target to trim a seed.
+    pub fn set_trim(&mut self, trim: bool) {
+        self.trim = trim;
+    }
+
+    //Whether mutate_in_place stops at the CmpLog state. Off by default, somebody
+    //has to log the comparisons of the target.
+    pub fn set_cmplog(&mut self, cmplog: bool) {
+        self.cmplog = cmplog;
+    }
+
+    //The comparisons of a run of the seed of the last Mutation::CmpLog, the
+    //input-to-state stage writes their operands into the seed
+    pub fn seed_cmp_log(&mut self, cmps: &[CmpOperands]) {
+        self.cmp_replacements = cmplog::replacements(self.seed_buf.as_slice(), cmps);
+        trace!("{} input-to-state replacements from {} comparisons", self.cmp_replacements.len(), cmps.len());
+        self.state_parser.set_cmp_cnt(self.cmp_replacements.len() as u64);
+    }
+
+    //The seed of the last Mutation::Trim is now trimmed, the stages go over that
+    pub fn seed_trimmed(&mut self, trimmed: &[u8]) {
+        self.seed_buf.load(trimmed);
+        self.state_parser.set_trimmed_len(trimmed.len() as u64);
+        self.analyze_seed(trimmed);
+    }
+
+    //Whether every seed gets its fields inferred (see profile::infer_fields), starting
+    //with the next seed. Off by default, the deterministic stages then try everything.
+    pub fn set_infer_fields(&mut self, infer_fields: bool) {
+        self.infer_fields = infer_fields;
+    }
+
+    //Text mode: havoc of the seeds that are mostly printable (see text::is_text) also
+    //picks the text operations, starting with the next seed. Off by default.
+    pub fn set_text_mode(&mut self, text_mode: bool) {
+        self.text_mode = text_mode;
+    }
+
+    //The inferred fields of the seed being mutated
+    pub fn seed_fields(&self) -> &[InferredField] {
+        self.state_parser.profile().fields()
+    }
+
+    //Whether havoc of the seed being mutated picks the text operations
+    pub fn seed_is_text(&self) -> bool {
+        self.seed_is_text
+    }
+
+    //Infer the fields of a new seed and whether it is text, as far as that is on
+    fn analyze_seed(&mut self, seed: &[u8]) {
+        self.seed_is_text = self.text_mode && text::is_text(seed);
+        let profile = if self.infer_fields {
+            let profile = profile::infer_fields(seed);
+            trace!("{} fields inferred in a seed of {} bytes", profile.fields().len(), seed.len());
+            profile
+        }
+        else {
+            SeedProfile::default()
+        };
+        self.state_parser.set_profile(profile);
+    }
+
+    //Whether the last call selected a new seed, its fuzz_level is due an update
+    pub fn started_new_seed(&self) -> bool {
+        self.seed_started
+    }
+
+    //Forget the seed being mutated, the next call starts with Select
+    pub fn reset(&mut self) {
+        self.state_parser.change_to_next_state(FuzzingState::Ready);
+    }
+
+    //Name of the state that made the last input, e.g. "bitflip 1/1"
+    pub fn stage_name(&self) -> &'static str {
+        self.state_parser.current_state().name()
+    }
+
+    //The state and parameters that made the last input, e.g. "StateAddArith16((12, 7))",
+    //or "Havoc[delete]" with the operations that havoc applied
+    pub fn mutation_desc(&self) -> String {
+        match *self.state_parser.current_state() {
+            FuzzingState::StateHavoc(_) => format!("Havoc[{}]", self.havoc_ops.join(", ")),
+            ref state => format!("{:?}", state),
+        }
+    }
+
+    //The state that made the last input without its parameters, e.g. "StateFlip1"
+    pub fn state_family(&self) -> &'static str {
+        self.state_parser.current_state().family()
+    }
+
+    //The havoc operations of the last input, empty if havoc did not make it
+    pub fn havoc_ops(&self) -> &[&'static str] {
+        match *self.state_parser.current_state() {
+            FuzzingState::StateHavoc(_) => &self.havoc_ops,
+            _ => &[],
         }
     }
 
+    //How far the generator is in that stage, (done, total)
+    pub fn stage_progress(&self) -> (u64, u64) {
+        self.state_parser.progress()
+    }
+
+    pub fn origin(_input_seed: InputSeed) -> SeedGenerator{
+        SeedGenerator::new()
+    }
+
     // pub fn change_seed(&mut self, seed_string:String) {
     //     self.seed_string = seed_string.clone();
     // }
 
-    //return Result<U(seed),E>
-    pub fn get_a_mutated_seed(&mut self, input_seed: &InputSeed) -> Option<Vec<u8>> { 
-        let origin_seed_vec = input_seed.get_seed_vec();
-        assert!(origin_seed_vec.len() > 0);
-        let seed_len = origin_seed_vec.len() as u64;
-
-        let mut state = self.state_parser.get_next_mutate_state(seed_len);
-        println!("{:?}", state);
-        //first check if the seed is new and need to select
-        if state == FuzzingState::Select {
-            if !self.seed_selector.whether_select(input_seed) {
-                self.state_parser.change_to_next_state(FuzzingState::Ready);
-                println!("{:?}", "not select, try next seed, return E");
-                return None;
+    //Ok(Some(seed)): a mutated seed, repaired by the post processors
+    //Ok(None): nothing left to do with this seed (finished or not selected), try the next one
+    //Err(e): the seed could not be mutated, the state is reset so the next call starts over
+    pub fn get_a_mutated_seed(&mut self, input_seed: &InputSeed) -> Result<Option<Vec<u8>>, MutationError> {
+        self.get_a_mutated_seed_in_place(input_seed).map(|seed| seed.map(|seed| seed.to_vec()))
+    }
+
+    //Same as get_a_mutated_seed, but the input is built in a buffer owned by the generator
+    //and is only valid until the next call
+    pub fn get_a_mutated_seed_in_place(&mut self, input_seed: &InputSeed) -> Result<Option<&[u8]>, MutationError> {
+        loop {
+            match self.mutate_in_place(input_seed)? {
+                Mutation::Mutated => return Ok(Some(self.post_process_current())),
+                Mutation::NextSeed => return Ok(None),
+                //nobody here to run the target, go on untrimmed
+                Mutation::Trim => self.seed_trimmed(input_seed.get_seed_slice()),
+                Mutation::CmpLog => self.seed_cmp_log(&[]),
             }
-            // if the seed is selected successfully, change it to next state and mutate it immediately
-            println!("{:?}", "select successfully");
+        }
+    }
+
+    //The input produced by the last successful mutation, before the post processors
+    pub fn get_current_seed(&self) -> &[u8] {
+        self.seed_buf.as_slice()
+    }
 
-            self.state_parser.change_to_next_state(state);
-            state = self.state_parser.get_next_mutate_state(seed_len);
-            //println!("{:?}", state);
+    pub(crate) fn mutate_in_place(&mut self, input_seed: &InputSeed) -> Result<Mutation, MutationError> {
+        let origin_seed = input_seed.get_seed_slice();
+        if origin_seed.is_empty() {
+            self.state_parser.change_to_next_state(FuzzingState::Ready);
+            return Err(MutationError::EmptySeed);
         }
+        let seed_len = origin_seed.len() as u64;
+        self.seed_started = false;
 
-        let mut rang = rand::thread_rng();
-        let mut mutated_seed_vec: Vec<u8> = Vec::new();
+        loop {
+            let mut state = self.state_parser.get_next_mutate_state(seed_len, &mut self.rang);
+            trace!("next state {:?}", state);
+            //first check if the seed is new and need to select
+            if state == FuzzingState::Select {
+                if !self.seed_selector.whether_select(input_seed, &mut self.rang) {
+                    self.state_parser.change_to_next_state(FuzzingState::Ready);
+                    trace!("seed not selected, trying the next one");
+                    return Ok(Mutation::NextSeed);
+                }
+                // if the seed is selected successfully, change it to next state and mutate it immediately
+                trace!("seed of {} bytes selected", seed_len);
+                self.seed_buf.load(origin_seed);
+                self.analyze_seed(origin_seed);
+                self.state_parser.set_perf_score(self.schedule.perf_score(input_seed));
+                self.seed_started = true;
 
-        match state {
-            FuzzingState::Select => {
-                println!("{:?}", "I think this would never happen");
-            },
-            FuzzingState::CalHavocTimes => {
-                println!("{:?}", "Congratulations, we calculate the havoc times successfully");
                 self.state_parser.change_to_next_state(state);
-                return self.get_a_mutated_seed(input_seed);
-            },
-            FuzzingState::StateFlip1(i) => {
-                mutated_seed_vec = mutator::flip_one_bit(&origin_seed_vec,i);
-            },
-            FuzzingState::StateFlip2(i) => {
-                mutated_seed_vec = mutator::flip_two_bits(&origin_seed_vec,i);
-            },
-            FuzzingState::StateFlip4(i) => {
-                mutated_seed_vec = mutator::flip_four_bits(&origin_seed_vec,i);
-            },
-            FuzzingState::StateFlip8(i) => {
-                mutated_seed_vec = mutator::flip_one_byte(&origin_seed_vec,i);
-            },
-            FuzzingState::StateFlip16(i) => {
-                mutated_seed_vec = mutator::flip_two_bytes(&origin_seed_vec,i);
-            },
-            FuzzingState::StateFlip32(i) => {
-                mutated_seed_vec = mutator::flip_four_bytes(&origin_seed_vec,i);
-            },
-            FuzzingState::StateAddArith8((i,arith_j)) => {
-                let mutated_seed_opt = mutator::arithmetic_add_one_byte_option(&origin_seed_vec,i,arith_j);
-                if mutated_seed_opt.is_none() {
-                    self.state_parser.change_to_next_state(state);
-                    return self.get_a_mutated_seed(input_seed);
-                }
-                mutated_seed_vec = mutated_seed_opt.unwrap();
-            },
-            FuzzingState::StateSubArith8((i,arith_j)) => {
-                let mutated_seed_opt = mutator::arithmetic_sub_one_byte_option(&origin_seed_vec,i,arith_j);
-                if mutated_seed_opt.is_none() {
-                    self.state_parser.change_to_next_state(state);
-                    return self.get_a_mutated_seed(input_seed);
-                }
-                mutated_seed_vec = mutated_seed_opt.unwrap();
-            },
-            FuzzingState::StateAddArith16((i,arith_j)) => {
-                let mutated_seed_opt: Option<Vec<u8>> = mutator::arithmetic_add_two_bytes_option(&origin_seed_vec,i,arith_j);
-                if mutated_seed_opt.is_none() {
-                    self.state_parser.change_to_next_state(state);
-                    return self.get_a_mutated_seed(input_seed);
-                }
-                mutated_seed_vec = mutated_seed_opt.unwrap();
-            },
-            FuzzingState::StateSubArith16((i,arith_j)) => {
-                let mutated_seed_opt: Option<Vec<u8>> = mutator::arithmetic_sub_two_bytes_option(&origin_seed_vec,i,arith_j);
-                if mutated_seed_opt.is_none() {
-                    self.state_parser.change_to_next_state(state);
-                    return self.get_a_mutated_seed(input_seed);
-                }
-                mutated_seed_vec = mutated_seed_opt.unwrap();
-            },
-            FuzzingState::StateAddArith16AnotherEndian((i,arith_j)) => {
-                let mutated_seed_opt: Option<Vec<u8>> = mutator::arithmetic_add_two_bytes_another_endian_option(&origin_seed_vec,i,arith_j);
-                if mutated_seed_opt.is_none() {
-                    self.state_parser.change_to_next_state(state);
-                    return self.get_a_mutated_seed(input_seed);
-                }
-                mutated_seed_vec = mutated_seed_opt.unwrap();
-            },
-            FuzzingState::StateSubArith16AnotherEndian((i,arith_j)) => {
-                let mutated_seed_opt: Option<Vec<u8>> = mutator::arithmetic_sub_two_bytes_another_endian_option(&origin_seed_vec,i,arith_j);
-                if mutated_seed_opt.is_none() {
-                    self.state_parser.change_to_next_state(state);
-                    return self.get_a_mutated_seed(input_seed);
-                }
-                mutated_seed_vec = mutated_seed_opt.unwrap();
-            },
-            FuzzingState::StateAddArith32((i,arith_j)) => {
-                let mutated_seed_opt: Option<Vec<u8>> = mutator::arithmetic_add_four_bytes_option(&origin_seed_vec,i,arith_j);
-                if mutated_seed_opt.is_none() {
-                    self.state_parser.change_to_next_state(state);
-                    return self.get_a_mutated_seed(input_seed);
+                state = self.state_parser.get_next_mutate_state(seed_len, &mut self.rang);
+                //println!("{:?}", state);
+            }
+
+            if state == FuzzingState::Trim {
+                self.state_parser.change_to_next_state(state);
+                if self.trim && !input_seed.trim_done {
+                    return Ok(Mutation::Trim);
                 }
-                mutated_seed_vec = mutated_seed_opt.unwrap();
-            },
-            FuzzingState::StateSubArith32((i,arith_j)) => {
-                let mutated_seed_opt: Option<Vec<u8>> = mutator::arithmetic_sub_four_bytes_option(&origin_seed_vec,i,arith_j);
-                if mutated_seed_opt.is_none() {
-                    self.state_parser.change_to_next_state(state);
-                    return self.get_a_mutated_seed(input_seed);
+                continue;
+            }
+
+            if state == FuzzingState::CmpLog {
+                self.state_parser.change_to_next_state(state);
+                //nothing is left of the last seed, whatever the caller does
+                self.seed_cmp_log(&[]);
+                if self.cmplog {
+                    return Ok(Mutation::CmpLog);
                 }
-                mutated_seed_vec = mutated_seed_opt.unwrap();
-            },
-            FuzzingState::StateAddArith32AnotherEndian((i,arith_j)) => {
-                let mutated_seed_opt: Option<Vec<u8>> = mutator::arithmetic_add_four_bytes_another_endian_option(&origin_seed_vec,i,arith_j);
-                if mutated_seed_opt.is_none() {
+                continue;
+            }
+
+            let seed_buf = &mut self.seed_buf;
+            let rang = &mut self.rang;
+            let extras = &self.extras;
+            let cmp_replacements = &self.cmp_replacements;
+            //Ok(false) means the mutation is redundant (e.g. could be a bitflip), skip it
+            let is_mutated = match state {
+                FuzzingState::Select => {
+                    warn!("state parser went back to Select in the middle of a seed");
+                    Ok(false)
+                },
+                FuzzingState::CalHavocTimes => {
+                    trace!("havoc times calculated");
+                    Ok(false)
+                },
+                FuzzingState::StateCmpLog(i) => {
+                    match cmp_replacements.get(i as usize) {
+                        Some(replacement) => seed_buf.mutate_at(origin_seed, replacement.pos, replacement.bytes.len() as u64, |buf| {
+                            mutator::extras_overwrite_in_place(buf, replacement.pos, &replacement.bytes)
+                        }),
+                        None => Ok(false),
+                    }
+                },
+                //a bit flip touches two bytes at most
+                FuzzingState::StateFlip1(i) => {
+                    seed_buf.mutate_at(origin_seed, i >> 3, 2, |buf| mutator::flip_bits_in_place(buf, i, 1)).map(|_| true)
+                },
+                FuzzingState::StateFlip2(i) => {
+                    seed_buf.mutate_at(origin_seed, i >> 3, 2, |buf| mutator::flip_bits_in_place(buf, i, 2)).map(|_| true)
+                },
+                FuzzingState::StateFlip4(i) => {
+                    seed_buf.mutate_at(origin_seed, i >> 3, 2, |buf| mutator::flip_bits_in_place(buf, i, 4)).map(|_| true)
+                },
+                FuzzingState::StateFlip8(i) => {
+                    seed_buf.mutate_at(origin_seed, i, 1, |buf| mutator::flip_bytes_in_place(buf, i, 1)).map(|_| true)
+                },
+                FuzzingState::StateFlip16(i) => {
+                    seed_buf.mutate_at(origin_seed, i, 2, |buf| mutator::flip_bytes_in_place(buf, i, 2)).map(|_| true)
+                },
+                FuzzingState::StateFlip32(i) => {
+                    seed_buf.mutate_at(origin_seed, i, 4, |buf| mutator::flip_bytes_in_place(buf, i, 4)).map(|_| true)
+                },
+                FuzzingState::StateAddArith8((i,arith_j)) => {
+                    seed_buf.mutate_at(origin_seed, i, 1, |buf| mutator::arithmetic_add_option_in_place(buf, i, 1, arith_j as u32, false))
+                },
+                FuzzingState::StateSubArith8((i,arith_j)) => {
+                    seed_buf.mutate_at(origin_seed, i, 1, |buf| mutator::arithmetic_sub_option_in_place(buf, i, 1, arith_j as u32, false))
+                },
+                FuzzingState::StateAddArith16((i,arith_j)) => {
+                    seed_buf.mutate_at(origin_seed, i, 2, |buf| mutator::arithmetic_add_option_in_place(buf, i, 2, arith_j as u32, false))
+                },
+                FuzzingState::StateSubArith16((i,arith_j)) => {
+                    seed_buf.mutate_at(origin_seed, i, 2, |buf| mutator::arithmetic_sub_option_in_place(buf, i, 2, arith_j as u32, false))
+                },
+                FuzzingState::StateAddArith16AnotherEndian((i,arith_j)) => {
+                    seed_buf.mutate_at(origin_seed, i, 2, |buf| mutator::arithmetic_add_option_in_place(buf, i, 2, arith_j as u32, true))
+                },
+                FuzzingState::StateSubArith16AnotherEndian((i,arith_j)) => {
+                    seed_buf.mutate_at(origin_seed, i, 2, |buf| mutator::arithmetic_sub_option_in_place(buf, i, 2, arith_j as u32, true))
+                },
+                FuzzingState::StateAddArith32((i,arith_j)) => {
+                    seed_buf.mutate_at(origin_seed, i, 4, |buf| mutator::arithmetic_add_option_in_place(buf, i, 4, arith_j, false))
+                },
+                FuzzingState::StateSubArith32((i,arith_j)) => {
+                    seed_buf.mutate_at(origin_seed, i, 4, |buf| mutator::arithmetic_sub_option_in_place(buf, i, 4, arith_j, false))
+                },
+                FuzzingState::StateAddArith32AnotherEndian((i,arith_j)) => {
+                    seed_buf.mutate_at(origin_seed, i, 4, |buf| mutator::arithmetic_add_option_in_place(buf, i, 4, arith_j, true))
+                },
+                FuzzingState::StateSubArith32AnotherEndian((i,arith_j)) => {
+                    seed_buf.mutate_at(origin_seed, i, 4, |buf| mutator::arithmetic_sub_option_in_place(buf, i, 4, arith_j, true))
+                },
+                FuzzingState::StateInterest8((i,index_count)) => {
+                    seed_buf.mutate_at(origin_seed, i, 1, |buf| mutator::interesting_replace_in_place(buf, i, 1, index_count, false)).map(|_| true)
+                },
+                FuzzingState::StateInterest16((i,index_count)) => {
+                    seed_buf.mutate_at(origin_seed, i, 2, |buf| mutator::interesting_replace_in_place(buf, i, 2, index_count, false)).map(|_| true)
+                },
+                FuzzingState::StateInterest16AnotherEndian((i,index_count)) => {
+                    seed_buf.mutate_at(origin_seed, i, 2, |buf| mutator::interesting_replace_in_place(buf, i, 2, index_count, true)).map(|_| true)
+                },
+                FuzzingState::StateInterest32((i,index_count)) => {
+                    seed_buf.mutate_at(origin_seed, i, 4, |buf| mutator::interesting_replace_in_place(buf, i, 4, index_count, false)).map(|_| true)
+                },
+                FuzzingState::StateInterest32AnotherEndian((i,index_count)) => {
+                    seed_buf.mutate_at(origin_seed, i, 4, |buf| mutator::interesting_replace_in_place(buf, i, 4, index_count, true)).map(|_| true)
+                },
+                FuzzingState::StateExtrasOverwrite((i,extra_index)) => {
+                    match extras.get(extra_index as usize) {
+                        Some(token) => seed_buf.mutate_all(origin_seed, |buf| mutator::extras_overwrite_in_place(buf, i, token)),
+                        None => Ok(false),
+                    }
+                },
+                FuzzingState::StateExtrasInsert((i,extra_index)) => {
+                    match extras.get(extra_index as usize) {
+                        Some(token) => seed_buf.mutate_all(origin_seed, |buf| mutator::extras_insert_in_place(buf, i, token)),
+                        None => Ok(false),
+                    }
+                },
+                FuzzingState::StateHavoc((_outer_cnt, _inner_cnt)) => {
+                    let havoc_ops = &mut self.havoc_ops;
+                    let havoc_scheduler = &self.havoc_scheduler;
+                    let profile = self.state_parser.profile();
+                    let seed_is_text = self.seed_is_text;
+                    seed_buf.mutate_all(origin_seed, |buf| {
+                        let choose_op = |op_cnt, rang: &mut StdRng| {
+                            havoc_scheduler.choose(if seed_is_text { mutator::text_op_cnt(op_cnt) } else { op_cnt }, rang)
+                        };
+                        mutator::havoc_mutate_with_extras_chosen_in_place(buf, extras, rang, choose_op, |op, len, rang| profile.havoc_placement(op, len, rang))
+                    }).map(|op| {
+                        havoc_ops.clear();
+                        havoc_ops.push(op);
+                        true
+                    })
+                },
+                _=> {
+                    trace!("seed finished");
+                    //start from the beginning with the next seed
+                    self.state_parser.change_to_next_state(FuzzingState::Ready);
+                    return Ok(Mutation::NextSeed);
+                },
+            };
+
+            match is_mutated {
+                Ok(true) => {
                     self.state_parser.change_to_next_state(state);
-                    return self.get_a_mutated_seed(input_seed);
-                }
-                mutated_seed_vec = mutated_seed_opt.unwrap();
-            },
-            FuzzingState::StateSubArith32AnotherEndian((i,arith_j)) => {
-                let mutated_seed_opt: Option<Vec<u8>> = mutator::arithmetic_sub_four_bytes_another_endian_option(&origin_seed_vec,i,arith_j);
-                if mutated_seed_opt.is_none() {
+                    return Ok(Mutation::Mutated);
+                },
+                Ok(false) => {
                     self.state_parser.change_to_next_state(state);
-                    return self.get_a_mutated_seed(input_seed);
-                }
-                mutated_seed_vec = mutated_seed_opt.unwrap();
-            },
-            FuzzingState::StateInterest8((i,index_count)) => {
-                mutated_seed_vec = mutator::interesting8_replace(&origin_seed_vec,i,index_count);
-            },
-            FuzzingState::StateInterest16((i,index_count)) => {
-                mutated_seed_vec = mutator::interesting16_replace(&origin_seed_vec,i,index_count);
-            },
-            FuzzingState::StateInterest16AnotherEndian((i,index_count)) => {
-                mutated_seed_vec = mutator::interesting16_replace_another_endian(&origin_seed_vec,i,index_count);
-            },
-            FuzzingState::StateInterest32((i,index_count)) => {
-                mutated_seed_vec = mutator::interesting32_replace(&origin_seed_vec,i,index_count);
-            },
-            FuzzingState::StateInterest32AnotherEndian((i,index_count)) => {
-                mutated_seed_vec = mutator::interesting32_replace_another_endian(&origin_seed_vec,i,index_count);
-            },
-            FuzzingState::StateHavoc((_outer_cnt, _inner_cnt)) => {
-                mutated_seed_vec = mutator::havoc_mutate(&origin_seed_vec,&mut rang);
-            },
-            _=> {
-                println!("{:?}", "end");
-                return None;
-            },
-        }
-        self.state_parser.change_to_next_state(state);
-        return Some(mutated_seed_vec);
+                },
+                Err(e) => {
+                    self.state_parser.change_to_next_state(FuzzingState::Ready);
+                    return Err(e);
+                },
+            }
+        }
     }
 }
 
@@ -228,8 +529,230 @@ impl SeedGenerator {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn empty_seed_is_an_error() {
+        let mut seed_generator = SeedGenerator::new();
+        let input_seed = InputSeed::new(Vec::new());
+        assert_eq!(seed_generator.get_a_mutated_seed(&input_seed), Err(MutationError::EmptySeed));
+    }
+
+    #[test]
+    fn finished_seed_restarts_from_ready() {
+        let mut seed_generator = SeedGenerator::new();
+        let input_seed = InputSeed::new(vec![7]);
+        let mut finished = false;
+        //one byte: ~100 deterministic steps plus at most 1024*128 havoc steps
+        for _ in 0..200_000 {
+            if seed_generator.get_a_mutated_seed(&input_seed).unwrap().is_none() {
+                finished = true;
+                break;
+            }
+        }
+        assert!(finished);
+        assert!(seed_generator.get_a_mutated_seed(&input_seed).unwrap().is_some());
+    }
+
+    #[test]
+    fn describes_the_last_mutation() {
+        let mut seed_generator = SeedGenerator::with_rng_seed(1);
+        let input_seed = InputSeed::new(vec![7, 7]);
+        seed_generator.get_a_mutated_seed(&input_seed).unwrap();
+        assert_eq!(seed_generator.mutation_desc(), "StateFlip1(0)");
+
+        seed_generator.set_stages(Stages::havoc_only());
+        seed_generator.reset();
+        seed_generator.get_a_mutated_seed(&input_seed).unwrap();
+        let desc = seed_generator.mutation_desc();
+        assert!(desc.starts_with("Havoc[") && desc.ends_with(']') && desc.len() > "Havoc[]".len());
+    }
+
+    #[test]
+    fn max_file_seed_does_not_panic() {
+        let mut seed_generator = SeedGenerator::new();
+        let input_seed = InputSeed::new(vec![0xff; config::MAX_FILE as usize]);
+        for _ in 0..16 {
+            let output_seed = seed_generator.get_a_mutated_seed(&input_seed).unwrap().unwrap();
+            assert_eq!(output_seed.len() as u64, config::MAX_FILE);
+        }
+    }
+
+    //Number of inputs made from input_seed before the generator moves on
+    fn count_inputs(seed_generator: &mut SeedGenerator, input_seed: &InputSeed) -> usize {
+        let mut count = 0;
+        while seed_generator.get_a_mutated_seed(input_seed).unwrap().is_some() {
+            count += 1;
+        }
+        count
+    }
+
+    #[test]
+    fn only_selected_stages_run() {
+        let mut seed_generator = SeedGenerator::with_rng_seed(1);
+        seed_generator.set_stages(Stages::parse("flip").unwrap());
+        //flip1 32, flip2 31, flip4 29, flip8 4, flip16 3, flip32 1
+        assert_eq!(count_inputs(&mut seed_generator, &InputSeed::new(vec![1, 2, 3, 4])), 100);
+        assert!(Stages::parse("flip,bogus").is_err());
+        assert!(Stages::parse("").is_err());
+    }
+
+    #[test]
+    fn dictionary_tokens_are_overwritten_and_inserted() {
+        let mut seed_generator = SeedGenerator::with_rng_seed(1);
+        seed_generator.set_stages(Stages::parse("extras").unwrap());
+        seed_generator.set_extras(vec![b"ab".to_vec(), b"xyz".to_vec()]);
+        let input_seed = InputSeed::new(vec![0, 0, 0]);
+        let mut inputs = Vec::new();
+        while let Some(output_seed) = seed_generator.get_a_mutated_seed(&input_seed).unwrap() {
+            inputs.push(output_seed);
+        }
+        //overwrite: "ab" at 0 and 1, "xyz" at 0; insert: both tokens at 4 positions
+        assert_eq!(inputs.len(), 3 + 8);
+        assert!(inputs.contains(&b"ab\0".to_vec()));
+        assert!(inputs.contains(&b"xyz".to_vec()));
+        assert!(inputs.contains(&b"\0\0\0xyz".to_vec()));
+        //without a dictionary there is nothing to do
+        seed_generator.set_extras(Vec::new());
+        assert_eq!(count_inputs(&mut seed_generator, &input_seed), 0);
+    }
+
+    #[test]
+    fn stages_run_over_the_trimmed_seed() {
+        let mut seed_generator = SeedGenerator::with_rng_seed(1);
+        seed_generator.set_stages(Stages::parse("flip").unwrap());
+        seed_generator.set_trim(true);
+        let mut input_seed = InputSeed::new(vec![1; 64]);
+        assert_eq!(seed_generator.mutate_in_place(&input_seed), Ok(Mutation::Trim));
+        input_seed.seed_vec.truncate(4);
+        input_seed.trim_done = true;
+        seed_generator.seed_trimmed(&input_seed.seed_vec);
+        assert_eq!(seed_generator.get_a_mutated_seed(&input_seed).unwrap().unwrap().len(), 4);
+        //the 4-byte count of only_selected_stages_run, the first input included
+        assert_eq!(count_inputs(&mut seed_generator, &input_seed) + 1, 100);
+        //a trimmed seed is not trimmed again
+        assert!(seed_generator.mutate_in_place(&input_seed) != Ok(Mutation::Trim));
+    }
+
+    #[test]
+    fn inferred_fields_narrow_the_integer_stages() {
+        //the state family of every input of the arith and interest stages
+        fn families(seed: &[u8], infer_fields: bool)->Vec<&'static str> {
+            let mut seed_generator = SeedGenerator::with_rng_seed(1);
+            seed_generator.set_stages(Stages::parse("arith,interest").unwrap());
+            seed_generator.set_infer_fields(infer_fields);
+            let input_seed = InputSeed::new(seed.to_vec());
+            let mut families = Vec::new();
+            loop {
+                match seed_generator.get_a_mutated_seed_in_place(&input_seed).unwrap() {
+                    Some(_) => families.push(seed_generator.state_family()),
+                    //not selected
+                    None if families.is_empty() => {},
+                    None => return families,
+                }
+            }
+        }
+        //a little endian integer, then text
+        let seed = b"\x05\x00hello";
+        let all = families(seed, false);
+        let inferred = families(seed, true);
+        assert!(all.contains(&"StateAddArith32") && all.contains(&"StateInterest16"));
+        assert!(inferred.len() < all.len());
+        assert!(inferred.iter().all(|family| !family.contains("32") && (!family.contains("16") || family.ends_with("AnotherEndian"))));
+        assert!(inferred.contains(&"StateAddArith16AnotherEndian") && inferred.contains(&"StateInterest16AnotherEndian"));
+        //the 8 bit stages are left alone
+        let count = |families: &[&str], family: &str| families.iter().filter(|&&name| name == family).count();
+        assert_eq!(count(&inferred, "StateAddArith8"), count(&all, "StateAddArith8"));
+
+        let mut seed_generator = SeedGenerator::new();
+        seed_generator.set_infer_fields(true);
+        seed_generator.seed_trimmed(seed);
+        assert_eq!(seed_generator.seed_fields().len(), 2);
+    }
+
+    #[test]
+    fn text_mode_adds_the_text_operations() {
+        //the havoc operations of the first inputs made from seed
+        fn havoc_ops(seed: &[u8], text_mode: bool)->Vec<&'static str> {
+            let mut seed_generator = SeedGenerator::with_rng_seed(1);
+            seed_generator.set_stages(Stages::havoc_only());
+            seed_generator.set_text_mode(text_mode);
+            let input_seed = InputSeed::new(seed.to_vec());
+            let mut ops = Vec::new();
+            while ops.len() < 1000 {
+                if seed_generator.get_a_mutated_seed_in_place(&input_seed).unwrap().is_some() {
+                    ops.extend_from_slice(seed_generator.havoc_ops());
+                }
+            }
+            ops
+        }
+        let text = b"name=value; other=42\n";
+        assert!(havoc_ops(text, true).iter().any(|op| op.starts_with("text-")));
+        assert!(!havoc_ops(text, false).iter().any(|op| op.starts_with("text-")));
+        assert!(!havoc_ops(b"\x7fELF\x02\x01\x01\0\0\0\0\0", true).iter().any(|op| op.starts_with("text-")));
+    }
+
+    #[test]
+    fn logged_comparisons_are_written_into_the_seed() {
+        let mut seed_generator = SeedGenerator::with_rng_seed(1);
+        seed_generator.set_stages(Stages::parse("cmplog,flip").unwrap());
+        seed_generator.set_cmplog(true);
+        let input_seed = InputSeed::new(vec![0x11, 0x22, 0x33, 0x44, 0x55]);
+        assert_eq!(seed_generator.mutate_in_place(&input_seed), Ok(Mutation::CmpLog));
+        seed_generator.seed_cmp_log(&[CmpOperands { size: 4, arg1: 0x5544_3322, arg2: 0xcafe_f00d }]);
+        let mut inputs = Vec::new();
+        while let Some(output_seed) = seed_generator.get_a_mutated_seed(&input_seed).unwrap() {
+            if seed_generator.state_family() != "StateCmpLog" {
+                break;
+            }
+            inputs.push(output_seed);
+        }
+        assert_eq!(inputs, vec![
+            vec![0x11, 0x0d, 0xf0, 0xfe, 0xca], vec![0x11, 0x0e, 0xf0, 0xfe, 0xca], vec![0x11, 0x0c, 0xf0, 0xfe, 0xca],
+        ]);
+        //the flip stage goes on with the seed as it was
+        assert_eq!(seed_generator.get_current_seed(), &[0x91, 0x22, 0x33, 0x44, 0x55]);
+
+        //without comparisons the stage is skipped
+        seed_generator.reset();
+        assert_eq!(seed_generator.mutate_in_place(&input_seed), Ok(Mutation::CmpLog));
+        assert_eq!(seed_generator.mutate_in_place(&input_seed), Ok(Mutation::Mutated));
+        assert_eq!(seed_generator.state_family(), "StateFlip1");
+    }
+
+    #[test]
+    fn same_rng_seed_same_inputs() {
+        let input_seed = InputSeed::new(vec![1, 2, 3]);
+        let mut first = SeedGenerator::with_rng_seed(7);
+        let mut second = SeedGenerator::with_rng_seed(7);
+        first.set_stages(Stages::havoc_only());
+        second.set_stages(Stages::havoc_only());
+        for _ in 0..100 {
+            assert_eq!(first.get_a_mutated_seed(&input_seed), second.get_a_mutated_seed(&input_seed));
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn no_seed_length_panics(seed_vec in prop::collection::vec(any::<u8>(), 0..16)) {
+            let mut seed_generator = SeedGenerator::new();
+            let input_seed = InputSeed::new(seed_vec.clone());
+            //enough steps to walk every deterministic stage of a 15-byte seed into havoc
+            for _ in 0..8000 {
+                match seed_generator.get_a_mutated_seed(&input_seed) {
+                    Ok(Some(output_seed)) => prop_assert!(!output_seed.is_empty()),
+                    Ok(None) => {},
+                    Err(e) => prop_assert!(seed_vec.is_empty() && e == MutationError::EmptySeed),
+                }
+            }
+        }
+    }
 }
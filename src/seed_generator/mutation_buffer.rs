@@ -0,0 +1,178 @@
+/*
+   DeepSAFL - reusable mutation buffer
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+use super::mutator::MutationError;
+
+//The deterministic stages change at most 8 bytes at one position
+const UNDO_MAX: usize = 8;
+
+//A copy of the seed being fuzzed that is mutated in place.
+//The allocation is kept across seeds, so producing an input normally costs no allocation:
+//single-position mutations are undone by writing back the bytes they overwrote,
+//and only havoc-like mutations need the seed to be copied in again.
+#[derive(Debug, Default)]
+pub struct MutationBuffer {
+    seed_vec: Vec<u8>,
+    undo_pos: usize,
+    undo_len: usize,
+    undo_bytes: [u8; UNDO_MAX],
+    //the content is the loaded seed, apart from the bytes undo() puts back
+    clean: bool,
+}
+
+impl MutationBuffer {
+    pub fn new() -> MutationBuffer {
+        MutationBuffer::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> MutationBuffer {
+        MutationBuffer {
+            seed_vec: Vec::with_capacity(capacity),
+            ..MutationBuffer::default()
+        }
+    }
+
+    //Replace the content with input_seed, reusing the allocation
+    pub fn load(&mut self, input_seed: &[u8]) {
+        self.seed_vec.clear();
+        self.seed_vec.extend_from_slice(input_seed);
+        self.undo_len = 0;
+        self.clean = true;
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.seed_vec
+    }
+
+    pub fn len(&self) -> usize {
+        self.seed_vec.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seed_vec.is_empty()
+    }
+
+    //Put back the bytes changed by the last mutate_at.
+    //Returns false if the buffer cannot be brought back this way (after mutate_all, or before load).
+    pub fn undo(&mut self) -> bool {
+        if !self.clean {
+            return false;
+        }
+        let undo_end = self.undo_pos + self.undo_len;
+        self.seed_vec[self.undo_pos..undo_end].copy_from_slice(&self.undo_bytes[..self.undo_len]);
+        self.undo_len = 0;
+        true
+    }
+
+    //Bring the buffer back to input_seed, by undo if possible, by copying it again otherwise
+    pub fn restore(&mut self, input_seed: &[u8]) {
+        if !self.undo() {
+            self.load(input_seed);
+        }
+    }
+
+    //Apply a mutation that changes at most byte_cnt bytes from byte_pos to input_seed.
+    //The previous mutation is undone first, and this one can be undone in turn if
+    //byte_cnt is 8 or less. A wider one is like mutate_all, the next mutation copies
+    //the seed again.
+    pub fn mutate_at<T, F>(&mut self, input_seed: &[u8], byte_pos: u64, byte_cnt: u64, mutate: F)
+        -> Result<T, MutationError>
+        where F: FnOnce(&mut [u8]) -> Result<T, MutationError> {
+        self.restore(input_seed);
+        let len = self.seed_vec.len();
+        let undo_pos = byte_pos as usize;
+        if byte_pos >= len as u64 {
+            //let the mutator report what is wrong, it checks before writing
+            return mutate(&mut self.seed_vec);
+        }
+        if byte_cnt > UNDO_MAX as u64 {
+            self.clean = false;
+            return mutate(&mut self.seed_vec);
+        }
+        let undo_len = ::std::cmp::min(byte_cnt as usize, len - undo_pos);
+        self.undo_bytes[..undo_len].copy_from_slice(&self.seed_vec[undo_pos..undo_pos + undo_len]);
+        self.undo_pos = undo_pos;
+        self.undo_len = undo_len;
+        mutate(&mut self.seed_vec)
+    }
+
+    //Apply a mutation to input_seed that may change any byte or the length (havoc).
+    //It cannot be undone, the next mutation starts from a fresh copy of the seed.
+    pub fn mutate_all<T, F>(&mut self, input_seed: &[u8], mutate: F) -> Result<T, MutationError>
+        where F: FnOnce(&mut Vec<u8>) -> Result<T, MutationError> {
+        self.restore(input_seed);
+        self.clean = false;
+        mutate(&mut self.seed_vec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use seed_generator::mutator;
+
+    #[test]
+    fn undo_restores_single_position_mutations() {
+        let input_seed = vec![1u8, 2, 3, 4, 5];
+        let mut seed_buf = MutationBuffer::new();
+        seed_buf.load(&input_seed);
+        seed_buf.mutate_at(&input_seed, 1, 4, |buf| mutator::flip_bytes_in_place(buf, 1, 4)).unwrap();
+        assert_eq!(seed_buf.as_slice(), &[1, 0xfd, 0xfc, 0xfb, 0xfa]);
+        seed_buf.mutate_at(&input_seed, 4, 1, |buf| mutator::flip_bytes_in_place(buf, 4, 1)).unwrap();
+        assert_eq!(seed_buf.as_slice(), &[1, 2, 3, 4, 0xfa]);
+        assert!(seed_buf.undo());
+        assert_eq!(seed_buf.as_slice(), &input_seed[..]);
+    }
+
+    #[test]
+    fn havoc_is_followed_by_a_fresh_copy() {
+        let input_seed = vec![1u8, 2, 3, 4, 5];
+        let mut seed_buf = MutationBuffer::new();
+        seed_buf.load(&input_seed);
+        seed_buf.mutate_all(&input_seed, |buf| mutator::delete_bytes_in_place(buf, 0, 2)).unwrap();
+        assert_eq!(seed_buf.as_slice(), &[3, 4, 5]);
+        assert!(!seed_buf.undo());
+        seed_buf.mutate_at(&input_seed, 0, 1, |buf| mutator::flip_bytes_in_place(buf, 0, 1)).unwrap();
+        assert_eq!(seed_buf.as_slice(), &[0xfe, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn wide_mutations_are_followed_by_a_fresh_copy() {
+        let input_seed: Vec<u8> = (0..16).collect();
+        let mut seed_buf = MutationBuffer::new();
+        seed_buf.load(&input_seed);
+        seed_buf.mutate_at(&input_seed, 2, 12, |buf| {
+            buf[2..14].iter_mut().for_each(|byte| *byte = 0xff);
+            Ok(())
+        }).unwrap();
+        assert_eq!(seed_buf.as_slice()[13], 0xff);
+        assert!(!seed_buf.undo());
+        seed_buf.restore(&input_seed);
+        assert_eq!(seed_buf.as_slice(), &input_seed[..]);
+    }
+
+    #[test]
+    fn first_mutation_loads_the_seed() {
+        let input_seed = vec![1u8, 2];
+        let mut seed_buf = MutationBuffer::new();
+        assert!(!seed_buf.undo());
+        seed_buf.mutate_at(&input_seed, 0, 1, |buf| mutator::flip_bytes_in_place(buf, 0, 1)).unwrap();
+        assert_eq!(seed_buf.as_slice(), &[0xfe, 2]);
+    }
+
+    #[test]
+    fn failed_mutation_leaves_the_seed_intact() {
+        let input_seed = vec![1u8, 2];
+        let mut seed_buf = MutationBuffer::new();
+        seed_buf.load(&input_seed);
+        let result = seed_buf.mutate_at(&input_seed, 1, 4, |buf| mutator::flip_bytes_in_place(buf, 1, 4));
+        assert!(result.is_err());
+        assert!(seed_buf.undo());
+        assert_eq!(seed_buf.as_slice(), &input_seed[..]);
+    }
+}
@@ -13,623 +13,387 @@
 
 use std;
 use std::cmp;
+use std::error;
+use std::fmt;
 use std::mem;
 use super::config;
+use super::text;
 use rand;
 use rand::Rng;
 
 
-fn flipbit(origin_seed:&mut Vec<u8>, pos:u64) {
-    let pos_byte = (pos >> 3) as usize;
-    let pos_bit = pos & 7;
-    origin_seed[pos_byte] ^= 128 >> pos_bit;
-}
-
-pub fn flip_one_bit(input_seed: &Vec<u8>, pos:u64)->Vec<u8> {
-    let mut output_seed = input_seed.clone();
-    flipbit(&mut output_seed,pos);
-    output_seed
+//Why a mutation could not be applied to a seed.
+//Mutators never panic on bad input, they return one of these instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MutationError {
+    //The seed has no byte to mutate
+    EmptySeed,
+    //The mutation touches [pos, pos+width) bytes but the seed is only len bytes long
+    OutOfBounds { pos: u64, width: u64, len: u64 },
+    //The index is past the end of the INTERESTING_* table
+    InterestingIndex(u8),
+    //A block operation was asked to work on zero bytes
+    EmptyBlock,
+    //The mutated seed would grow to this length, beyond config::MAX_FILE
+    ExceedMaxFile(u64),
+    //Only 1, 2 and 4 bytes (or bits) can be mutated at once
+    UnsupportedWidth(u64),
+    //There is no havoc operation with this number, see HAVOC_OPS
+    UnknownHavocOp(u32),
+}
+
+impl fmt::Display for MutationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MutationError::EmptySeed => write!(f, "the seed is empty"),
+            MutationError::OutOfBounds { pos, width, len } => {
+                write!(f, "{} byte(s) at {} are out of a seed of {} byte(s)", width, pos, len)
+            },
+            MutationError::InterestingIndex(index) => write!(f, "interesting index {} is out of range", index),
+            MutationError::EmptyBlock => write!(f, "the block length is zero"),
+            MutationError::ExceedMaxFile(len) => {
+                write!(f, "the seed would grow to {} bytes, MAX_FILE is {}", len, config::MAX_FILE)
+            },
+            MutationError::UnsupportedWidth(width) => write!(f, "cannot mutate {} bytes at once", width),
+            MutationError::UnknownHavocOp(op) => {
+                write!(f, "havoc operation {} is out of range, there are {}", op, HAVOC_OPS.len())
+            },
+        }
+    }
 }
 
-pub fn flip_one_bit_option(input_seed: &Vec<u8>, pos:u64)->Option<Vec<u8>> {
-    let mut output_seed = input_seed.clone();
-    flipbit(&mut output_seed,pos);
-    Some(output_seed)
-}
+impl error::Error for MutationError {}
 
-pub fn flip_two_bits(input_seed: &Vec<u8>, pos:u64)->Vec<u8> {
-    let mut output_seed = input_seed.clone();
-    flipbit(&mut output_seed,pos);
-    flipbit(&mut output_seed,pos+1);
-    output_seed
+//Check that [byte_pos, byte_pos+width) lies inside the seed
+fn check_bytes(input_seed: &[u8], byte_pos:u64, width:u64) -> Result<(), MutationError> {
+    let len = input_seed.len() as u64;
+    if len == 0 {
+        return Err(MutationError::EmptySeed);
+    }
+    match byte_pos.checked_add(width) {
+        Some(end) if end <= len => Ok(()),
+        _ => Err(MutationError::OutOfBounds { pos: byte_pos, width, len }),
+    }
 }
 
-pub fn flip_two_bits_option(input_seed: &Vec<u8>, pos:u64)->Option<Vec<u8>>{
-    let mut output_seed = input_seed.clone();
-    flipbit(&mut output_seed,pos);
-    flipbit(&mut output_seed,pos+1);
-    Some(output_seed)
+//Same as check_bytes, but for bit_cnt bits starting at bit pos
+fn check_bits(input_seed: &[u8], pos:u64, bit_cnt:u64) -> Result<(), MutationError> {
+    let last_bit = pos.checked_add(bit_cnt - 1).ok_or(MutationError::OutOfBounds {
+        pos: pos >> 3,
+        width: 1,
+        len: input_seed.len() as u64,
+    })?;
+    check_bytes(input_seed, pos >> 3, (last_bit >> 3) - (pos >> 3) + 1)
 }
 
-pub fn flip_four_bits(input_seed: &Vec<u8>, pos:u64)->Vec<u8> {
-    let mut output_seed = input_seed.clone();
-    flipbit(&mut output_seed,pos);
-    flipbit(&mut output_seed,pos+1);
-    flipbit(&mut output_seed,pos+2);
-    flipbit(&mut output_seed,pos+3);
-    output_seed
+fn check_width(width:u64) -> Result<(), MutationError> {
+    match width {
+        1 | 2 | 4 => Ok(()),
+        _ => Err(MutationError::UnsupportedWidth(width)),
+    }
 }
 
-pub fn flip_four_bits_option(input_seed: &Vec<u8>, pos:u64)->Option<Vec<u8>>{
-    let mut output_seed = input_seed.clone();
-    flipbit(&mut output_seed,pos);
-    flipbit(&mut output_seed,pos+1);
-    flipbit(&mut output_seed,pos+2);
-    flipbit(&mut output_seed,pos+3);
-    Some(output_seed)
+//Read width bytes at byte_pos as an integer, big endian unless another_endian
+fn load_value(seed_buf:&[u8], byte_pos:u64, width:u64, another_endian:bool) -> u32 {
+    let bytes = &seed_buf[byte_pos as usize..(byte_pos+width) as usize];
+    let fold = |value:u32, byte:&u8| (value << 8) | u32::from(*byte);
+    if another_endian {
+        bytes.iter().rev().fold(0, fold)
+    }
+    else {
+        bytes.iter().fold(0, fold)
+    }
 }
 
-fn flipbyte(origin_seed:&mut Vec<u8>, pos:u64) {
-    origin_seed[pos as usize] ^= 0xFF;
+//Write the low width bytes of value at byte_pos, big endian unless another_endian
+fn store_value(seed_buf:&mut [u8], byte_pos:u64, width:u64, another_endian:bool, value:u32) {
+    let bytes = &mut seed_buf[byte_pos as usize..(byte_pos+width) as usize];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        let shift = if another_endian { i as u64 } else { width - 1 - i as u64 };
+        *byte = (value >> (shift * 8)) as u8;
+    }
 }
 
-pub fn flip_one_byte(input_seed:&Vec<u8>, byte_pos:u64)->Vec<u8> {
-    assert!(byte_pos < input_seed.len() as u64);
-    let mut output_seed = input_seed.clone();
-    flipbyte(&mut output_seed,byte_pos);
-    output_seed
+fn width_mask(width:u64) -> u32 {
+    if width >= 4 { 0xffffffff } else { (1 << (width * 8)) - 1 }
 }
 
-pub fn flip_one_byte_option(input_seed:&Vec<u8>, byte_pos:u64)->Option<Vec<u8>>{
-    assert!(byte_pos < input_seed.len() as u64);
-    let mut output_seed = input_seed.clone();
-    flipbyte(&mut output_seed,byte_pos);
-    Some(output_seed)
-}
+// Mutations on a buffer in place. They check their bounds before touching the buffer,
+// so on Err the buffer is left unchanged.
 
-fn set_one_byte(input_seed:&Vec<u8>, byte_pos:u64, byte_new:u8)->Vec<u8> {
-    assert!(byte_pos < input_seed.len() as u64);
-    assert!(byte_new != 0);
-    let mut output_seed = input_seed.clone();
-    output_seed[byte_pos as usize] = byte_new;
-    output_seed
+fn flipbit(origin_seed:&mut [u8], pos:u64) {
+    let pos_byte = (pos >> 3) as usize;
+    let pos_bit = pos & 7;
+    origin_seed[pos_byte] ^= 128 >> pos_bit;
 }
 
-pub fn flip_two_bytes(input_seed: &Vec<u8>, byte_pos:u64)->Vec<u8> {
-    assert!(byte_pos < input_seed.len() as u64 -1);
-    let mut output_seed = input_seed.clone();
-    flipbyte(&mut output_seed,byte_pos);
-    flipbyte(&mut output_seed,byte_pos+1);
-    output_seed
+//Flip bit_cnt (1, 2 or 4) consecutive bits starting at bit pos
+pub fn flip_bits_in_place(seed_buf:&mut [u8], pos:u64, bit_cnt:u64) -> Result<(), MutationError> {
+    check_width(bit_cnt)?;
+    check_bits(seed_buf, pos, bit_cnt)?;
+    for i in 0..bit_cnt {
+        flipbit(seed_buf, pos+i);
+    }
+    Ok(())
 }
 
-pub fn flip_two_bytes_option(input_seed: &Vec<u8>, byte_pos:u64)->Option<Vec<u8>> {
-    assert!(byte_pos < input_seed.len() as u64 -1);
-    let mut output_seed = input_seed.clone();
-    flipbyte(&mut output_seed,byte_pos);
-    flipbyte(&mut output_seed,byte_pos+1);
-    Some(output_seed)
+fn flipbyte(origin_seed:&mut [u8], pos:u64) {
+    origin_seed[pos as usize] ^= 0xFF;
 }
 
-pub fn flip_four_bytes(input_seed: &Vec<u8>, byte_pos:u64)->Vec<u8> {
-    assert!(byte_pos < (input_seed.len() as u64) -3); //Attention: You need to ensure the byte_pos is legal
-    let mut output_seed = input_seed.clone();
-    flipbyte(&mut output_seed,byte_pos);
-    flipbyte(&mut output_seed,byte_pos+1);
-    flipbyte(&mut output_seed,byte_pos+2);
-    flipbyte(&mut output_seed,byte_pos+3);
-    output_seed
+//Flip byte_cnt (1, 2 or 4) consecutive bytes starting at byte_pos
+pub fn flip_bytes_in_place(seed_buf:&mut [u8], byte_pos:u64, byte_cnt:u64) -> Result<(), MutationError> {
+    check_width(byte_cnt)?;
+    check_bytes(seed_buf, byte_pos, byte_cnt)?;
+    for i in 0..byte_cnt {
+        flipbyte(seed_buf, byte_pos+i);
+    }
+    Ok(())
 }
 
-pub fn flip_four_bytes_option(input_seed: &Vec<u8>, byte_pos:u64)->Option<Vec<u8>> {
-    assert!(byte_pos < (input_seed.len() as u64) -3); //Attention: You need to ensure the byte_pos is legal
-    let mut output_seed = input_seed.clone();
-    flipbyte(&mut output_seed,byte_pos);
-    flipbyte(&mut output_seed,byte_pos+1);
-    flipbyte(&mut output_seed,byte_pos+2);
-    flipbyte(&mut output_seed,byte_pos+3);
-    Some(output_seed)
+fn set_one_byte_in_place(seed_buf:&mut [u8], byte_pos:u64, byte_new:u8) -> Result<(), MutationError> {
+    check_bytes(seed_buf, byte_pos, 1)?;
+    seed_buf[byte_pos as usize] = byte_new;
+    Ok(())
 }
 
-fn could_be_bitflip(xor_val_orign: u32) -> bool {
-    let mut sh:u32 = 0;
-    let mut xor_val = xor_val_orign;
-    if(xor_val == 0) {
-        return true;
-    }
-    while((xor_val & 1) == 0) {
-        sh+=1;
-        xor_val >>= 1;
-    }
-    if(xor_val == 1 || xor_val == 3 || xor_val == 15) {
-        return true;
-    }
-    if((sh & 7) != 0) {
-        return false;
-    }
-    if(xor_val == 0xff || xor_val == 0xffff || xor_val == 0xffffffff) {
-        return true;
+//Add delta (wrapping) to the byte_cnt-byte integer at byte_pos.
+//With skip_bitflip, leave the buffer alone and return false if a bit flip stage already produced the result.
+fn arithmetic_in_place(seed_buf:&mut [u8], byte_pos:u64, byte_cnt:u64, delta:u32,
+                       another_endian:bool, skip_bitflip:bool) -> Result<bool, MutationError> {
+    check_width(byte_cnt)?;
+    check_bytes(seed_buf, byte_pos, byte_cnt)?;
+    let orig_old = load_value(seed_buf, byte_pos, byte_cnt, another_endian);
+    let orig_new = orig_old.wrapping_add(delta) & width_mask(byte_cnt);
+    if skip_bitflip && could_be_bitflip(orig_old ^ orig_new) {
+        return Ok(false);
     }
-    false
+    store_value(seed_buf, byte_pos, byte_cnt, another_endian, orig_new);
+    Ok(true)
 }
 
-// pub fn add_one_byte_could_be_bitflip(input_seed: &Vec<u8>, byte_pos:u64, arith_number:u8)-> bool {
-//     let orig = input_seed[byte_pos as usize];
-//     let xor_val = orig ^ (orig+arith_number);
-//     could_be_bitflip(xor_val as u32)
-// }
+pub fn arithmetic_add_in_place(seed_buf:&mut [u8], byte_pos:u64, byte_cnt:u64, arith_number:u32,
+                               another_endian:bool) -> Result<(), MutationError> {
+    arithmetic_in_place(seed_buf, byte_pos, byte_cnt, arith_number, another_endian, false).map(|_| ())
+}
 
-pub fn arithmetic_add_one_byte(input_seed: &Vec<u8>, byte_pos:u64, arith_number:u8)->Vec<u8> {
-    let mut output_seed = input_seed.clone();
-    let orig = output_seed[byte_pos as usize];
-    output_seed[byte_pos as usize] = orig+arith_number;
-    output_seed
+pub fn arithmetic_sub_in_place(seed_buf:&mut [u8], byte_pos:u64, byte_cnt:u64, arith_number:u32,
+                               another_endian:bool) -> Result<(), MutationError> {
+    arithmetic_in_place(seed_buf, byte_pos, byte_cnt, arith_number.wrapping_neg(), another_endian, false).map(|_| ())
 }
 
-pub fn arithmetic_add_one_byte_option(input_seed: &Vec<u8>, byte_pos:u64, arith_number:u8) -> Option<Vec<u8>> {
-    let orig = input_seed[byte_pos as usize];
-    let xor_val = orig ^ (orig+arith_number);
-    if could_be_bitflip(xor_val as u32) {
-        None
-    }
-    else {
-        let mut output_seed = input_seed.clone();
-        output_seed[byte_pos as usize] = orig+arith_number;
-        Some(output_seed)
-    }
+//Ok(false): the result could be a bitflip, nothing was changed
+pub fn arithmetic_add_option_in_place(seed_buf:&mut [u8], byte_pos:u64, byte_cnt:u64, arith_number:u32,
+                                      another_endian:bool) -> Result<bool, MutationError> {
+    arithmetic_in_place(seed_buf, byte_pos, byte_cnt, arith_number, another_endian, true)
 }
 
-pub fn sub_one_byte_could_be_bitflip(input_seed: &Vec<u8>, byte_pos:u64, arith_number:u8)-> bool {
-    let orig = input_seed[byte_pos as usize];
-    let xor_val = orig ^ (orig-arith_number);
-    could_be_bitflip(xor_val as u32)
+//Ok(false): the result could be a bitflip, nothing was changed
+pub fn arithmetic_sub_option_in_place(seed_buf:&mut [u8], byte_pos:u64, byte_cnt:u64, arith_number:u32,
+                                      another_endian:bool) -> Result<bool, MutationError> {
+    arithmetic_in_place(seed_buf, byte_pos, byte_cnt, arith_number.wrapping_neg(), another_endian, true)
 }
 
-pub fn arithmetic_sub_one_byte(input_seed: &Vec<u8>, byte_pos:u64, arith_number:u8)->Vec<u8> {
-    let mut output_seed = input_seed.clone();
-    let orig = output_seed[byte_pos as usize];
-    output_seed[byte_pos as usize] = orig-arith_number;
-    output_seed
+//Overwrite byte_cnt bytes at byte_pos with INTERESTING_8/16/32[index_number]
+pub fn interesting_replace_in_place(seed_buf:&mut [u8], byte_pos:u64, byte_cnt:u64, index_number:u8,
+                                    another_endian:bool) -> Result<(), MutationError> {
+    check_width(byte_cnt)?;
+    check_bytes(seed_buf, byte_pos, byte_cnt)?;
+    let replace_number = match byte_cnt {
+        1 => config::INTERESTING_8.get(index_number as usize).map(|&n| n as u32),
+        2 => config::INTERESTING_16.get(index_number as usize).map(|&n| n as u32),
+        _ => config::INTERESTING_32.get(index_number as usize).map(|&n| n as u32),
+    };
+    let replace_number = replace_number.ok_or(MutationError::InterestingIndex(index_number))?;
+    store_value(seed_buf, byte_pos, byte_cnt, another_endian, replace_number);
+    Ok(())
 }
 
-pub fn arithmetic_sub_one_byte_option(input_seed: &Vec<u8>, byte_pos:u64, arith_number:u8)-> Option<Vec<u8>> {
-    let orig = input_seed[byte_pos as usize];
-    let xor_val = orig ^ (orig-arith_number);
-    if could_be_bitflip(xor_val as u32) {
-        None
+pub fn delete_bytes_in_place(seed_buf:&mut Vec<u8>, del_from:u64, del_len:u64) -> Result<(), MutationError> {
+    if del_len == 0 {
+        return Err(MutationError::EmptyBlock);
     }
-    else {
-        let mut output_seed = input_seed.clone();
-        output_seed[byte_pos as usize] = orig-arith_number;
-        Some(output_seed)
-    }    
+    //del include del_from, del_end is del_from+del_len-1
+    check_bytes(seed_buf, del_from, del_len)?;
+    seed_buf.drain((del_from as usize)..((del_from+del_len) as usize));
+    Ok(())
 }
 
-// pub fn convert_string_into_two_bytes(pack_data: & String){
-//     let ptr :*const u8 = pack_data.as_ptr();
-//     let ptr :*const u16 = ptr as *const u16;
-//     let s = unsafe{ *ptr};
-//     println!("{:?}", s);
-// }
-
-// pub fn cast_vec<T:Copy,U:Copy>(v:Vec<T>)->Vec<U>{
-//     use std::mem::size_of;
-//     use std::mem::forget;
-//     let (s_t, s_u) = (size_of::<T>(), size_of::<U>());
-//     let bytes_len = v.len() * s_t;
-//     assert!( bytes_len % s_u == 0);
-//     let mut v = v;
-//     v.shrink_to_fit();
-//     let len = bytes_len / s_u;
-//     let result;
-//     unsafe{
-//         result = Vec::from_raw_parts(v.as_mut_ptr() as *mut U, len, len);
-//         forget(v);
-//     }
-//     result
-// }
-
-//pub fn saturating_add(self, rhs: u16) -> u16
-//pub fn saturating_sub(self, rhs: u16) -> u16
-//pub fn wrapping_add(self, rhs: u16) -> u16
-//pub fn wrapping_sub(self, rhs: u16) -> u16
-//pub fn to_bytes(self) -> [u8; 2] let bytes = 0x1234_5678_u32.to_be().to_bytes();
-//pub fn from_bytes(bytes: [u8; 2]) -> u16 let int = u32::from_be(u32::from_bytes([0x12, 0x34, 0x56, 0x78]));
-
-// We find repeat codes in following two functions, might be optimized?
-
-
-
-
-// pub fn add_two_bytes_could_be_bitflip(input_seed: &Vec<u8>, byte_pos:u64, arith_number:u16)-> bool {
+// Mutations that return a new seed and leave the input alone.
 
-//     assert!(byte_pos < input_seed.len() as u64 -1); //Attention: You need to ensure the byte_pos is legal
-//     let first_byte = input_seed[byte_pos as usize] as u16;
-//     let second_byte = input_seed[(byte_pos+1) as usize] as u16;
-//     let orig_old = first_byte.wrapping_shl(8) + second_byte;
-//     let orig_new = orig_old + arith_number;
-
-//     let xor_val = orig_old ^ orig_new;
-//     could_be_bitflip(xor_val as u32)
-// }
-
-pub fn arithmetic_add_two_bytes(input_seed: &Vec<u8>, byte_pos:u64, arith_number:u16)->Vec<u8> {
-    assert!(byte_pos < input_seed.len() as u64 -1); //Attention: You need to ensure the byte_pos is legal
-    let mut output_seed = input_seed.clone();
-    let first_byte = output_seed[byte_pos as usize] as u16;
-    let second_byte = output_seed[(byte_pos+1) as usize] as u16;
-
-
-    let orig_old = first_byte.wrapping_shl(8) + second_byte;
-    let orig_new = orig_old + arith_number;
-    let first_byte_new = (orig_new >> 8) as u8;
-    let second_byte_new = (orig_new & 127) as u8;
-    output_seed[byte_pos as usize] = first_byte_new;
-    output_seed[(byte_pos+1) as usize] = second_byte_new;
-    
-    output_seed
+fn cloned<F>(input_seed: &[u8], mutate:F) -> Result<Vec<u8>, MutationError>
+    where F: FnOnce(&mut Vec<u8>) -> Result<(), MutationError> {
+    let mut output_seed = input_seed.to_vec();
+    mutate(&mut output_seed)?;
+    Ok(output_seed)
 }
 
-pub fn arithmetic_add_two_bytes_option(input_seed: &Vec<u8>, byte_pos:u64, arith_number:u16) -> Option<Vec<u8>> {
-    assert!(byte_pos < input_seed.len() as u64 -1); //Attention: You need to ensure the byte_pos is legal
-    let first_byte = input_seed[byte_pos as usize] as u16;
-    let second_byte = input_seed[(byte_pos+1) as usize] as u16;
-    let orig_old = first_byte.wrapping_shl(8) + second_byte;
-    let orig_new = orig_old + arith_number;
-
-    let xor_val = orig_old ^ orig_new;
-    if could_be_bitflip(xor_val as u32) {
-        None
+fn cloned_option<F>(input_seed: &[u8], mutate:F) -> Result<Option<Vec<u8>>, MutationError>
+    where F: FnOnce(&mut Vec<u8>) -> Result<bool, MutationError> {
+    let mut output_seed = input_seed.to_vec();
+    if mutate(&mut output_seed)? {
+        Ok(Some(output_seed))
     }
     else {
-        let mut output_seed = input_seed.clone();
-        let first_byte_new = (orig_new >> 8) as u8;
-        let second_byte_new = (orig_new & 127) as u8;
-        output_seed[byte_pos as usize] = first_byte_new;
-        output_seed[(byte_pos+1) as usize] = second_byte_new;
-        Some(output_seed)
+        Ok(None)
     }
 }
 
-pub fn arithmetic_sub_two_bytes(input_seed: &Vec<u8>, byte_pos:u64, arith_number:u16)->Vec<u8> {
-    assert!(byte_pos < input_seed.len() as u64 -1); //Attention: You need to ensure the byte_pos is legal
-    let mut output_seed = input_seed.clone();
-    let first_byte = output_seed[byte_pos as usize] as u16;
-    let second_byte = output_seed[(byte_pos+1) as usize] as u16;
-
-
-    let orig_old = first_byte.wrapping_shl(8) + second_byte;
-    let orig_new = orig_old - arith_number;
-    let first_byte_new = (orig_new >> 8) as u8;
-    let second_byte_new = (orig_new & 127) as u8;
-    output_seed[byte_pos as usize] = first_byte_new;
-    output_seed[(byte_pos+1) as usize] = second_byte_new;
-    
-    output_seed
+pub fn flip_one_bit(input_seed: &[u8], pos:u64)->Result<Vec<u8>, MutationError> {
+    cloned(input_seed, |seed_buf| flip_bits_in_place(seed_buf, pos, 1))
 }
 
-pub fn arithmetic_sub_two_bytes_option(input_seed: &Vec<u8>, byte_pos:u64, arith_number:u16)-> Option<Vec<u8>> {
-    assert!(byte_pos < input_seed.len() as u64 -1); //Attention: You need to ensure the byte_pos is legal
-    let first_byte = input_seed[byte_pos as usize] as u16;
-    let second_byte = input_seed[(byte_pos+1) as usize] as u16;
-    let orig_old = first_byte.wrapping_shl(8) + second_byte;
-    let orig_new = orig_old - arith_number;
-
-    let xor_val = orig_old ^ orig_new;
-    if could_be_bitflip(xor_val as u32) {
-        None
-    }
-    else {
-        let mut output_seed = input_seed.clone();
-        let first_byte_new = (orig_new >> 8) as u8;
-        let second_byte_new = (orig_new & 127) as u8;
-        output_seed[byte_pos as usize] = first_byte_new;
-        output_seed[(byte_pos+1) as usize] = second_byte_new;
-        Some(output_seed)
-    }
+pub fn flip_one_bit_option(input_seed: &[u8], pos:u64)->Result<Option<Vec<u8>>, MutationError> {
+    flip_one_bit(input_seed, pos).map(Some)
 }
 
-
-pub fn arithmetic_add_two_bytes_another_endian(input_seed: &Vec<u8>, byte_pos:u64, arith_number:u16)->Vec<u8> {
-    assert!(byte_pos < input_seed.len() as u64 -1); //Attention: You need to ensure the byte_pos is legal
-    let mut output_seed = input_seed.clone();
-    let second_byte = output_seed[byte_pos as usize] as u16;
-    let first_byte = output_seed[(byte_pos+1) as usize] as u16;
-
-
-    let orig_old = first_byte.wrapping_shl(8) + second_byte;
-    let orig_new = orig_old + arith_number;
-    let second_byte_new = (orig_new >> 8) as u8;
-    let first_byte_new = (orig_new & 127) as u8;
-    output_seed[byte_pos as usize] = first_byte_new;
-    output_seed[(byte_pos+1) as usize] = second_byte_new;
-    
-    output_seed
+pub fn flip_two_bits(input_seed: &[u8], pos:u64)->Result<Vec<u8>, MutationError> {
+    cloned(input_seed, |seed_buf| flip_bits_in_place(seed_buf, pos, 2))
 }
 
-pub fn arithmetic_add_two_bytes_another_endian_option(input_seed: &Vec<u8>, byte_pos:u64, arith_number:u16)-> Option<Vec<u8>>{
-    assert!(byte_pos < input_seed.len() as u64 -1); //Attention: You need to ensure the byte_pos is legal
-    let second_byte= input_seed[byte_pos as usize] as u16;
-    let first_byte = input_seed[(byte_pos+1) as usize] as u16;
-    let orig_old = first_byte.wrapping_shl(8) + second_byte;
-    let orig_new = orig_old + arith_number;
-
-    let xor_val = orig_old ^ orig_new;
-    if could_be_bitflip(xor_val as u32) {
-        None
-    }
-    else {
-        let mut output_seed = input_seed.clone();
-        let second_byte_new = (orig_new >> 8) as u8;
-        let first_byte_new = (orig_new & 127) as u8;
-        output_seed[byte_pos as usize] = first_byte_new;
-        output_seed[(byte_pos+1) as usize] = second_byte_new;
-        Some(output_seed)
-    }
+pub fn flip_two_bits_option(input_seed: &[u8], pos:u64)->Result<Option<Vec<u8>>, MutationError> {
+    flip_two_bits(input_seed, pos).map(Some)
 }
 
-pub fn arithmetic_sub_two_bytes_another_endian(input_seed: &Vec<u8>, byte_pos:u64, arith_number:u16)->Vec<u8> {
-    assert!(byte_pos < input_seed.len() as u64 -1); //Attention: You need to ensure the byte_pos is legal
-    let mut output_seed = input_seed.clone();
-    let second_byte = output_seed[byte_pos as usize] as u16;
-    let first_byte = output_seed[(byte_pos+1) as usize] as u16;
-
-
-    let orig_old = first_byte.wrapping_shl(8) + second_byte;
-    let orig_new = orig_old - arith_number;
-    let second_byte_new = (orig_new >> 8) as u8;
-    let first_byte_new = (orig_new & 127) as u8;
-    output_seed[byte_pos as usize] = first_byte_new;
-    output_seed[(byte_pos+1) as usize] = second_byte_new;
-    
-    output_seed
+pub fn flip_four_bits(input_seed: &[u8], pos:u64)->Result<Vec<u8>, MutationError> {
+    cloned(input_seed, |seed_buf| flip_bits_in_place(seed_buf, pos, 4))
 }
 
-pub fn arithmetic_sub_two_bytes_another_endian_option(input_seed: &Vec<u8>, byte_pos:u64, arith_number:u16)-> Option<Vec<u8>>{
-    assert!(byte_pos < input_seed.len() as u64 -1); //Attention: You need to ensure the byte_pos is legal
-    let second_byte= input_seed[byte_pos as usize] as u16;
-    let first_byte = input_seed[(byte_pos+1) as usize] as u16;
-    let orig_old = first_byte.wrapping_shl(8) + second_byte;
-    let orig_new = orig_old - arith_number;
-
-    let xor_val = orig_old ^ orig_new;
-    if could_be_bitflip(xor_val as u32) {
-        None
-    }
-    else {
-        let mut output_seed = input_seed.clone();
-        let second_byte_new = (orig_new >> 8) as u8;
-        let first_byte_new = (orig_new & 127) as u8;
-        output_seed[byte_pos as usize] = first_byte_new;
-        output_seed[(byte_pos+1) as usize] = second_byte_new;
-        Some(output_seed)
-    }
+pub fn flip_four_bits_option(input_seed: &[u8], pos:u64)->Result<Option<Vec<u8>>, MutationError> {
+    flip_four_bits(input_seed, pos).map(Some)
 }
 
-
-
-pub fn arithmetic_add_four_bytes(input_seed: &Vec<u8>, byte_pos:u64, arith_number:u32)->Vec<u8> {
-    assert!(byte_pos < (input_seed.len() as u64) -3); //Attention: You need to ensure the byte_pos is legal
-    let mut output_seed = input_seed.clone();
-    let first_byte = output_seed[byte_pos as usize] as u32;
-    let second_byte = output_seed[(byte_pos+1) as usize] as u32;
-    let third_byte = output_seed[(byte_pos+2) as usize] as u32;
-    let fourth_byte = output_seed[(byte_pos+3) as usize] as u32;
-
-
-    let orig_old = first_byte.wrapping_shl(24) + second_byte.wrapping_shl(16) +third_byte.wrapping_shl(8) + fourth_byte;
-    let orig_new = orig_old + arith_number;
-    let first_byte_new = (orig_new >> 24) as u8;
-    let second_byte_new = (((orig_new >> 16) & 127).wrapping_shl(24)>>24) as u8;
-    let third_byte_new = (((orig_new >> 8) & 127).wrapping_shl(24)>>24) as u8;
-    let fourth_byte_new = (orig_new & 127) as u8;
-
-    output_seed[byte_pos as usize] = first_byte_new;
-    output_seed[(byte_pos+1) as usize] = second_byte_new;
-    output_seed[(byte_pos+2) as usize] = third_byte_new;
-    output_seed[(byte_pos+3) as usize] = fourth_byte_new;
-    
-    output_seed
+pub fn flip_one_byte(input_seed:&[u8], byte_pos:u64)->Result<Vec<u8>, MutationError> {
+    cloned(input_seed, |seed_buf| flip_bytes_in_place(seed_buf, byte_pos, 1))
 }
 
-pub fn arithmetic_add_four_bytes_option(input_seed: &Vec<u8>, byte_pos:u64, arith_number:u32)-> Option<Vec<u8>> {
-    assert!(byte_pos < (input_seed.len() as u64) -3); //Attention: You need to ensure the byte_pos is legal
-    
-    let first_byte = input_seed[byte_pos as usize] as u32;
-    let second_byte = input_seed[(byte_pos+1) as usize] as u32;
-    let third_byte = input_seed[(byte_pos+2) as usize] as u32;
-    let fourth_byte = input_seed[(byte_pos+3) as usize] as u32;
-
-    let orig_old = first_byte.wrapping_shl(24) + second_byte.wrapping_shl(16) +third_byte.wrapping_shl(8) + fourth_byte;
-    let orig_new = orig_old + arith_number;
-
-    let xor_val = orig_old ^ orig_new;
-    if could_be_bitflip(xor_val) {
-        None
-    }
-    else {
-        let mut output_seed = input_seed.clone();
-        let first_byte_new = (orig_new >> 24) as u8;
-        let second_byte_new = (((orig_new >> 16) & 127).wrapping_shl(24)>>24) as u8;
-        let third_byte_new = (((orig_new >> 8) & 127).wrapping_shl(24)>>24) as u8;
-        let fourth_byte_new = (orig_new & 127) as u8;
-
-        output_seed[byte_pos as usize] = first_byte_new;
-        output_seed[(byte_pos+1) as usize] = second_byte_new;
-        output_seed[(byte_pos+2) as usize] = third_byte_new;
-        output_seed[(byte_pos+3) as usize] = fourth_byte_new;
-
-        Some(output_seed)
-    }
+pub fn flip_one_byte_option(input_seed:&[u8], byte_pos:u64)->Result<Option<Vec<u8>>, MutationError> {
+    flip_one_byte(input_seed, byte_pos).map(Some)
 }
 
-pub fn arithmetic_sub_four_bytes(input_seed: &Vec<u8>, byte_pos:u64, arith_number:u32)->Vec<u8> {
-    assert!(byte_pos < (input_seed.len() as u64) -3); //Attention: You need to ensure the byte_pos is legal
-    let mut output_seed = input_seed.clone();
-    let first_byte = output_seed[byte_pos as usize] as u32;
-    let second_byte = output_seed[(byte_pos+1) as usize] as u32;
-    let third_byte = output_seed[(byte_pos+2) as usize] as u32;
-    let fourth_byte = output_seed[(byte_pos+3) as usize] as u32;
-
-
-    let orig_old = first_byte.wrapping_shl(24) + second_byte.wrapping_shl(16) +third_byte.wrapping_shl(8) + fourth_byte;
-    let orig_new = orig_old - arith_number;
-    let first_byte_new = (orig_new >> 24) as u8;
-    let second_byte_new = (((orig_new >> 16) & 127).wrapping_shl(24)>>24) as u8;
-    let third_byte_new = (((orig_new >> 8) & 127).wrapping_shl(24)>>24) as u8;
-    let fourth_byte_new = (orig_new & 127) as u8;
+pub fn flip_two_bytes(input_seed: &[u8], byte_pos:u64)->Result<Vec<u8>, MutationError> {
+    cloned(input_seed, |seed_buf| flip_bytes_in_place(seed_buf, byte_pos, 2))
+}
 
-    output_seed[byte_pos as usize] = first_byte_new;
-    output_seed[(byte_pos+1) as usize] = second_byte_new;
-    output_seed[(byte_pos+2) as usize] = third_byte_new;
-    output_seed[(byte_pos+3) as usize] = fourth_byte_new;
-    
-    output_seed
+pub fn flip_two_bytes_option(input_seed: &[u8], byte_pos:u64)->Result<Option<Vec<u8>>, MutationError> {
+    flip_two_bytes(input_seed, byte_pos).map(Some)
 }
 
-pub fn arithmetic_sub_four_bytes_option(input_seed: &Vec<u8>, byte_pos:u64, arith_number:u32)->Option<Vec<u8>> {
-    assert!(byte_pos < (input_seed.len() as u64) -3); //Attention: You need to ensure the byte_pos is legal
-    let first_byte = input_seed[byte_pos as usize] as u32;
-    let second_byte = input_seed[(byte_pos+1) as usize] as u32;
-    let third_byte = input_seed[(byte_pos+2) as usize] as u32;
-    let fourth_byte = input_seed[(byte_pos+3) as usize] as u32;
+pub fn flip_four_bytes(input_seed: &[u8], byte_pos:u64)->Result<Vec<u8>, MutationError> {
+    cloned(input_seed, |seed_buf| flip_bytes_in_place(seed_buf, byte_pos, 4))
+}
 
-    let orig_old = first_byte.wrapping_shl(24) + second_byte.wrapping_shl(16) +third_byte.wrapping_shl(8) + fourth_byte;
-    let orig_new = orig_old - arith_number;
+pub fn flip_four_bytes_option(input_seed: &[u8], byte_pos:u64)->Result<Option<Vec<u8>>, MutationError> {
+    flip_four_bytes(input_seed, byte_pos).map(Some)
+}
 
-    let xor_val = orig_old ^ orig_new;
-    if could_be_bitflip(xor_val) {
-        None
+fn could_be_bitflip(xor_val_orign: u32) -> bool {
+    let mut sh:u32 = 0;
+    let mut xor_val = xor_val_orign;
+    if xor_val == 0 {
+        return true;
     }
-    else {
-        let mut output_seed = input_seed.clone();
-        let first_byte_new = (orig_new >> 24) as u8;
-        let second_byte_new = (((orig_new >> 16) & 127).wrapping_shl(24)>>24) as u8;
-        let third_byte_new = (((orig_new >> 8) & 127).wrapping_shl(24)>>24) as u8;
-        let fourth_byte_new = (orig_new & 127) as u8;
-
-        output_seed[byte_pos as usize] = first_byte_new;
-        output_seed[(byte_pos+1) as usize] = second_byte_new;
-        output_seed[(byte_pos+2) as usize] = third_byte_new;
-        output_seed[(byte_pos+3) as usize] = fourth_byte_new;
-
-        Some(output_seed)
+    while (xor_val & 1) == 0 {
+        sh+=1;
+        xor_val >>= 1;
+    }
+    if xor_val == 1 || xor_val == 3 || xor_val == 15 {
+        return true;
+    }
+    if (sh & 7) != 0 {
+        return false;
+    }
+    if xor_val == 0xff || xor_val == 0xffff || xor_val == 0xffffffff {
+        return true;
     }
+    false
 }
 
-pub fn arithmetic_add_four_bytes_another_endian(input_seed: &Vec<u8>, byte_pos:u64, arith_number:u32)->Vec<u8> {
-    assert!(byte_pos < (input_seed.len() as u64) -3); //Attention: You need to ensure the byte_pos is legal
-    let mut output_seed = input_seed.clone();
-    let fourth_byte = output_seed[byte_pos as usize] as u32;
-    let third_byte = output_seed[(byte_pos+1) as usize] as u32;
-    let second_byte = output_seed[(byte_pos+2) as usize] as u32;
-    let first_byte = output_seed[(byte_pos+3) as usize] as u32;
-
-
-    let orig_old = first_byte.wrapping_shl(24) + second_byte.wrapping_shl(16) +third_byte.wrapping_shl(8) + fourth_byte;
-    let orig_new = orig_old + arith_number;
-    let fourth_byte_new = (orig_new >> 24) as u8;
-    let third_byte_new = (((orig_new >> 16) & 127).wrapping_shl(24)>>24) as u8;
-    let second_byte_new = (((orig_new >> 8) & 127).wrapping_shl(24)>>24) as u8;
-    let first_byte_new = (orig_new & 127) as u8;
+pub fn arithmetic_add_one_byte(input_seed: &[u8], byte_pos:u64, arith_number:u8)->Result<Vec<u8>, MutationError> {
+    cloned(input_seed, |seed_buf| arithmetic_add_in_place(seed_buf, byte_pos, 1, arith_number as u32, false))
+}
 
-    output_seed[byte_pos as usize] = first_byte_new;
-    output_seed[(byte_pos+1) as usize] = second_byte_new;
-    output_seed[(byte_pos+2) as usize] = third_byte_new;
-    output_seed[(byte_pos+3) as usize] = fourth_byte_new;
-    
-    output_seed
+pub fn arithmetic_add_one_byte_option(input_seed: &[u8], byte_pos:u64, arith_number:u8) -> Result<Option<Vec<u8>>, MutationError> {
+    cloned_option(input_seed, |seed_buf| arithmetic_add_option_in_place(seed_buf, byte_pos, 1, arith_number as u32, false))
 }
 
-pub fn arithmetic_add_four_bytes_another_endian_option(input_seed: &Vec<u8>, byte_pos:u64, arith_number:u32)->Option<Vec<u8>> {
-    assert!(byte_pos < (input_seed.len() as u64) -3); //Attention: You need to ensure the byte_pos is legal
-    
-    let fourth_byte = input_seed[byte_pos as usize] as u32;
-    let third_byte = input_seed[(byte_pos+1) as usize] as u32;
-    let second_byte = input_seed[(byte_pos+2) as usize] as u32;
-    let first_byte = input_seed[(byte_pos+3) as usize] as u32;
+pub fn sub_one_byte_could_be_bitflip(input_seed: &[u8], byte_pos:u64, arith_number:u8)-> Result<bool, MutationError> {
+    check_bytes(input_seed, byte_pos, 1)?;
+    let orig = input_seed[byte_pos as usize];
+    let xor_val = orig ^ orig.wrapping_sub(arith_number);
+    Ok(could_be_bitflip(xor_val as u32))
+}
 
+pub fn arithmetic_sub_one_byte(input_seed: &[u8], byte_pos:u64, arith_number:u8)->Result<Vec<u8>, MutationError> {
+    cloned(input_seed, |seed_buf| arithmetic_sub_in_place(seed_buf, byte_pos, 1, arith_number as u32, false))
+}
 
-    let orig_old = first_byte.wrapping_shl(24) + second_byte.wrapping_shl(16) +third_byte.wrapping_shl(8) + fourth_byte;
-    let orig_new = orig_old + arith_number;
+pub fn arithmetic_sub_one_byte_option(input_seed: &[u8], byte_pos:u64, arith_number:u8)-> Result<Option<Vec<u8>>, MutationError> {
+    cloned_option(input_seed, |seed_buf| arithmetic_sub_option_in_place(seed_buf, byte_pos, 1, arith_number as u32, false))
+}
 
-    let xor_val = orig_old ^ orig_new;
+pub fn arithmetic_add_two_bytes(input_seed: &[u8], byte_pos:u64, arith_number:u16)->Result<Vec<u8>, MutationError> {
+    cloned(input_seed, |seed_buf| arithmetic_add_in_place(seed_buf, byte_pos, 2, arith_number as u32, false))
+}
 
-    if could_be_bitflip(xor_val) {
-        None
-    }
-    else {
-        let mut output_seed = input_seed.clone();
-        let fourth_byte_new = (orig_new >> 24) as u8;
-        let third_byte_new = (((orig_new >> 16) & 127).wrapping_shl(24)>>24) as u8;
-        let second_byte_new = (((orig_new >> 8) & 127).wrapping_shl(24)>>24) as u8;
-        let first_byte_new = (orig_new & 127) as u8;
+pub fn arithmetic_add_two_bytes_option(input_seed: &[u8], byte_pos:u64, arith_number:u16) -> Result<Option<Vec<u8>>, MutationError> {
+    cloned_option(input_seed, |seed_buf| arithmetic_add_option_in_place(seed_buf, byte_pos, 2, arith_number as u32, false))
+}
 
-        output_seed[byte_pos as usize] = first_byte_new;
-        output_seed[(byte_pos+1) as usize] = second_byte_new;
-        output_seed[(byte_pos+2) as usize] = third_byte_new;
-        output_seed[(byte_pos+3) as usize] = fourth_byte_new;
+pub fn arithmetic_sub_two_bytes(input_seed: &[u8], byte_pos:u64, arith_number:u16)->Result<Vec<u8>, MutationError> {
+    cloned(input_seed, |seed_buf| arithmetic_sub_in_place(seed_buf, byte_pos, 2, arith_number as u32, false))
+}
 
-        Some(output_seed)
-    }
+pub fn arithmetic_sub_two_bytes_option(input_seed: &[u8], byte_pos:u64, arith_number:u16)-> Result<Option<Vec<u8>>, MutationError> {
+    cloned_option(input_seed, |seed_buf| arithmetic_sub_option_in_place(seed_buf, byte_pos, 2, arith_number as u32, false))
+}
 
+pub fn arithmetic_add_two_bytes_another_endian(input_seed: &[u8], byte_pos:u64, arith_number:u16)->Result<Vec<u8>, MutationError> {
+    cloned(input_seed, |seed_buf| arithmetic_add_in_place(seed_buf, byte_pos, 2, arith_number as u32, true))
 }
 
-pub fn arithmetic_sub_four_bytes_another_endian(input_seed: &Vec<u8>, byte_pos:u64, arith_number:u32)->Vec<u8> {
-    assert!(byte_pos < (input_seed.len() as u64) -3); //Attention: You need to ensure the byte_pos is legal
-    let mut output_seed = input_seed.clone();
-    let fourth_byte = output_seed[byte_pos as usize] as u32;
-    let third_byte = output_seed[(byte_pos+1) as usize] as u32;
-    let second_byte = output_seed[(byte_pos+2) as usize] as u32;
-    let first_byte = output_seed[(byte_pos+3) as usize] as u32;
+pub fn arithmetic_add_two_bytes_another_endian_option(input_seed: &[u8], byte_pos:u64, arith_number:u16)-> Result<Option<Vec<u8>>, MutationError> {
+    cloned_option(input_seed, |seed_buf| arithmetic_add_option_in_place(seed_buf, byte_pos, 2, arith_number as u32, true))
+}
 
+pub fn arithmetic_sub_two_bytes_another_endian(input_seed: &[u8], byte_pos:u64, arith_number:u16)->Result<Vec<u8>, MutationError> {
+    cloned(input_seed, |seed_buf| arithmetic_sub_in_place(seed_buf, byte_pos, 2, arith_number as u32, true))
+}
 
-    let orig_old = first_byte.wrapping_shl(24) + second_byte.wrapping_shl(16) +third_byte.wrapping_shl(8) + fourth_byte;
-    let orig_new = orig_old - arith_number;
-    let fourth_byte_new = (orig_new >> 24) as u8;
-    let third_byte_new = (((orig_new >> 16) & 127).wrapping_shl(24)>>24) as u8;
-    let second_byte_new = (((orig_new >> 8) & 127).wrapping_shl(24)>>24) as u8;
-    let first_byte_new = (orig_new & 127) as u8;
+pub fn arithmetic_sub_two_bytes_another_endian_option(input_seed: &[u8], byte_pos:u64, arith_number:u16)-> Result<Option<Vec<u8>>, MutationError> {
+    cloned_option(input_seed, |seed_buf| arithmetic_sub_option_in_place(seed_buf, byte_pos, 2, arith_number as u32, true))
+}
 
-    output_seed[byte_pos as usize] = first_byte_new;
-    output_seed[(byte_pos+1) as usize] = second_byte_new;
-    output_seed[(byte_pos+2) as usize] = third_byte_new;
-    output_seed[(byte_pos+3) as usize] = fourth_byte_new;
-    
-    output_seed
+pub fn arithmetic_add_four_bytes(input_seed: &[u8], byte_pos:u64, arith_number:u32)->Result<Vec<u8>, MutationError> {
+    cloned(input_seed, |seed_buf| arithmetic_add_in_place(seed_buf, byte_pos, 4, arith_number, false))
 }
 
-pub fn arithmetic_sub_four_bytes_another_endian_option(input_seed: &Vec<u8>, byte_pos:u64, arith_number:u32)->Option<Vec<u8>> {
-    assert!(byte_pos < (input_seed.len() as u64) -3); //Attention: You need to ensure the byte_pos is legal
-    
-    let fourth_byte = input_seed[byte_pos as usize] as u32;
-    let third_byte = input_seed[(byte_pos+1) as usize] as u32;
-    let second_byte = input_seed[(byte_pos+2) as usize] as u32;
-    let first_byte = input_seed[(byte_pos+3) as usize] as u32;
+pub fn arithmetic_add_four_bytes_option(input_seed: &[u8], byte_pos:u64, arith_number:u32)-> Result<Option<Vec<u8>>, MutationError> {
+    cloned_option(input_seed, |seed_buf| arithmetic_add_option_in_place(seed_buf, byte_pos, 4, arith_number, false))
+}
 
+pub fn arithmetic_sub_four_bytes(input_seed: &[u8], byte_pos:u64, arith_number:u32)->Result<Vec<u8>, MutationError> {
+    cloned(input_seed, |seed_buf| arithmetic_sub_in_place(seed_buf, byte_pos, 4, arith_number, false))
+}
 
-    let orig_old = first_byte.wrapping_shl(24) + second_byte.wrapping_shl(16) +third_byte.wrapping_shl(8) + fourth_byte;
-    let orig_new = orig_old - arith_number;
+pub fn arithmetic_sub_four_bytes_option(input_seed: &[u8], byte_pos:u64, arith_number:u32)->Result<Option<Vec<u8>>, MutationError> {
+    cloned_option(input_seed, |seed_buf| arithmetic_sub_option_in_place(seed_buf, byte_pos, 4, arith_number, false))
+}
 
-    let xor_val = orig_old ^ orig_new;
+pub fn arithmetic_add_four_bytes_another_endian(input_seed: &[u8], byte_pos:u64, arith_number:u32)->Result<Vec<u8>, MutationError> {
+    cloned(input_seed, |seed_buf| arithmetic_add_in_place(seed_buf, byte_pos, 4, arith_number, true))
+}
 
-    if could_be_bitflip(xor_val) {
-        None
-    }
-    else {
-        let mut output_seed = input_seed.clone();
-        let fourth_byte_new = (orig_new >> 24) as u8;
-        let third_byte_new = (((orig_new >> 16) & 127).wrapping_shl(24)>>24) as u8;
-        let second_byte_new = (((orig_new >> 8) & 127).wrapping_shl(24)>>24) as u8;
-        let first_byte_new = (orig_new & 127) as u8;
+pub fn arithmetic_add_four_bytes_another_endian_option(input_seed: &[u8], byte_pos:u64, arith_number:u32)->Result<Option<Vec<u8>>, MutationError> {
+    cloned_option(input_seed, |seed_buf| arithmetic_add_option_in_place(seed_buf, byte_pos, 4, arith_number, true))
+}
 
-        output_seed[byte_pos as usize] = first_byte_new;
-        output_seed[(byte_pos+1) as usize] = second_byte_new;
-        output_seed[(byte_pos+2) as usize] = third_byte_new;
-        output_seed[(byte_pos+3) as usize] = fourth_byte_new;
+pub fn arithmetic_sub_four_bytes_another_endian(input_seed: &[u8], byte_pos:u64, arith_number:u32)->Result<Vec<u8>, MutationError> {
+    cloned(input_seed, |seed_buf| arithmetic_sub_in_place(seed_buf, byte_pos, 4, arith_number, true))
+}
 
-        Some(output_seed)
-    }
+pub fn arithmetic_sub_four_bytes_another_endian_option(input_seed: &[u8], byte_pos:u64, arith_number:u32)->Result<Option<Vec<u8>>, MutationError> {
+    cloned_option(input_seed, |seed_buf| arithmetic_sub_option_in_place(seed_buf, byte_pos, 4, arith_number, true))
 }
 
 
@@ -645,105 +409,45 @@ pub fn arithmetic_sub_four_bytes_another_endian_option(input_seed: &Vec<u8>, byt
 //      false
 // }
 
-pub fn interesting8_replace(input_seed: &Vec<u8>, byte_pos:u64, index_number:u8)->Vec<u8> {
-    assert!(byte_pos < input_seed.len() as u64); //Attention: You need to ensure the byte_pos is legal
-    let mut output_seed = input_seed.clone();
-
-    assert!(index_number < config::INTERESTING_8_CNT);
-    output_seed[byte_pos as usize] = config::INTERESTING_8[index_number as usize] as u8;
-    output_seed
+pub fn interesting8_replace(input_seed: &[u8], byte_pos:u64, index_number:u8)->Result<Vec<u8>, MutationError> {
+    cloned(input_seed, |seed_buf| interesting_replace_in_place(seed_buf, byte_pos, 1, index_number, false))
 }
 
-pub fn interesting16_replace(input_seed: &Vec<u8>, byte_pos:u64, index_number:u8)->Vec<u8> {
-    assert!(byte_pos < input_seed.len() as u64 -1); //Attention: You need to ensure the byte_pos is legal
-    let mut output_seed = input_seed.clone();
-
-    assert!(index_number < config::INTERESTING_16_CNT);
-    let replace_number = config::INTERESTING_16[index_number as usize];
-    let first_byte_new = (replace_number >> 8) as u8;
-    let second_byte_new = (replace_number & 127) as u8;
-
-    output_seed[byte_pos as usize] =  first_byte_new;
-    output_seed[(byte_pos+1) as usize] = second_byte_new;
-    output_seed
+pub fn interesting16_replace(input_seed: &[u8], byte_pos:u64, index_number:u8)->Result<Vec<u8>, MutationError> {
+    cloned(input_seed, |seed_buf| interesting_replace_in_place(seed_buf, byte_pos, 2, index_number, false))
 }
 
-pub fn interesting16_replace_another_endian(input_seed: &Vec<u8>, byte_pos:u64, index_number:u8)->Vec<u8> {
-    assert!(byte_pos < input_seed.len() as u64 -1); //Attention: You need to ensure the byte_pos is legal
-    let mut output_seed = input_seed.clone();
-
-    assert!(index_number < config::INTERESTING_16_CNT);
-    let replace_number = config::INTERESTING_16[index_number as usize];
-    let second_byte_new = (replace_number >> 8) as u8;
-    let first_byte_new = (replace_number & 127) as u8;
-
-    output_seed[byte_pos as usize] =  first_byte_new;
-    output_seed[(byte_pos+1) as usize] = second_byte_new;
-    output_seed
+pub fn interesting16_replace_another_endian(input_seed: &[u8], byte_pos:u64, index_number:u8)->Result<Vec<u8>, MutationError> {
+    cloned(input_seed, |seed_buf| interesting_replace_in_place(seed_buf, byte_pos, 2, index_number, true))
 }
 
-pub fn interesting32_replace(input_seed: &Vec<u8>, byte_pos:u64, index_number:u8)->Vec<u8> {
-    assert!(byte_pos < input_seed.len() as u64 -3); //Attention: You need to ensure the byte_pos is legal
-    let mut output_seed = input_seed.clone();
-
-    assert!(index_number < config::INTERESTING_32_CNT); //Attention: index should less than 32_cnt
-    let replace_number = config::INTERESTING_32[index_number as usize];
-
-    let first_byte_new = (replace_number >> 24) as u8;
-    let second_byte_new = (((replace_number >> 16) & 127).wrapping_shl(24)>>24) as u8;
-    let third_byte_new = (((replace_number >> 8) & 127).wrapping_shl(24)>>24) as u8;
-    let fourth_byte_new = (replace_number & 127) as u8;
-
-    output_seed[byte_pos as usize] = first_byte_new;
-    output_seed[(byte_pos+1) as usize] = second_byte_new;
-    output_seed[(byte_pos+2) as usize] = third_byte_new;
-    output_seed[(byte_pos+3) as usize] = fourth_byte_new;
-
-    output_seed
+pub fn interesting32_replace(input_seed: &[u8], byte_pos:u64, index_number:u8)->Result<Vec<u8>, MutationError> {
+    cloned(input_seed, |seed_buf| interesting_replace_in_place(seed_buf, byte_pos, 4, index_number, false))
 }
 
-pub fn interesting32_replace_another_endian(input_seed: &Vec<u8>, byte_pos:u64, index_number:u8)->Vec<u8> {
-    assert!(byte_pos < input_seed.len() as u64 -3); //Attention: You need to ensure the byte_pos is legal
-    let mut output_seed = input_seed.clone();
-
-    assert!(index_number < config::INTERESTING_32_CNT); //Attention: index should less than 32_cnt
-    let replace_number = config::INTERESTING_32[index_number as usize];
-
-    let fourth_byte_new = (replace_number >> 24) as u8;
-    let third_byte_new = (((replace_number >> 16) & 127).wrapping_shl(24)>>24) as u8;
-    let second_byte_new = (((replace_number >> 8) & 127).wrapping_shl(24)>>24) as u8;
-    let first_byte_new = (replace_number & 127) as u8;
-
-    output_seed[byte_pos as usize] = first_byte_new;
-    output_seed[(byte_pos+1) as usize] = second_byte_new;
-    output_seed[(byte_pos+2) as usize] = third_byte_new;
-    output_seed[(byte_pos+3) as usize] = fourth_byte_new;
-
-    output_seed
+pub fn interesting32_replace_another_endian(input_seed: &[u8], byte_pos:u64, index_number:u8)->Result<Vec<u8>, MutationError> {
+    cloned(input_seed, |seed_buf| interesting_replace_in_place(seed_buf, byte_pos, 4, index_number, true))
 }
 
-pub fn delete_byte(input_seed: &Vec<u8>, del_from:u64, del_len:u64)->Vec<u8> {
-    assert!(del_from < input_seed.len() as u64);
-    assert!(del_len >0);
-    assert!(del_from + del_len -1 < input_seed.len() as u64);//del include del_from, del_end is del_from+del_len-1
-
-    let mut output_seed = input_seed.clone();
-    output_seed.drain((del_from as usize)..((del_from+del_len) as usize));
-
-    output_seed
-
+pub fn delete_byte(input_seed: &[u8], del_from:u64, del_len:u64)->Result<Vec<u8>, MutationError> {
+    //check before cloning, a bad request should not cost a copy of the seed
+    check_bytes(input_seed, del_from, cmp::max(del_len, 1))?;
+    cloned(input_seed, |seed_buf| delete_bytes_in_place(seed_buf, del_from, del_len))
 }
 
-//This function in AFL needs other information, 
+//This function in AFL needs other information,
 //namely, 1.queue_cycle(cycles for passing the queue) 2.run_over10m(whether the fuzzer running for 10 minutes)
 //Here we first do not use these information, may be need update in future ... Why need?
 
 // Helper to choose random block len for block operations in fuzz_one().
 //    Doesn't return zero, provided that max_len is > 0.
 
-pub fn choose_block_len(limit:u64, rang:& mut rand::ThreadRng) -> u64 {
+pub fn choose_block_len<R: Rng>(limit:u64, rang:& mut R) -> u64 {
     let mut min_value:u64;
     let mut max_value:u64;
+    if limit == 0 {
+        return 0;
+    }
     let rlim = 3; //afl use MIN(queue_cycle, 3), here we simplify it to directly use 3
     match rang.gen_range(0, rlim) {
         0 => {
@@ -773,238 +477,500 @@ pub fn choose_block_len(limit:u64, rang:& mut rand::ThreadRng) -> u64 {
 
 }
 
-pub fn insert_clone_bytes(input_seed: &Vec<u8>, rang:& mut rand::ThreadRng)->Vec<u8> {
-    //We clone the input_seed from the clone_start_pos to clone_start_pos+clone_len-1
-    //We insert the clone bytes to the insert_pos
-    assert!(input_seed.len() as u64 + config::HAVOC_BLK_XL < config::MAX_FILE);//how to 
-    let (clone_start_pos, clone_len, insert_pos):(usize, usize, usize);
-    let len = input_seed.len() as u64;
-    let mut output_seed = input_seed.clone();
+pub fn insert_clone_bytes_in_place<R: Rng>(seed_buf:&mut Vec<u8>, rang:& mut R)->Result<(), MutationError> {
+    //We clone the seed_buf from the clone_start_pos to clone_start_pos+clone_len-1
+    //We insert the clone bytes to the insert
+    let (clone_start_pos, clone_len):(usize, usize);
+    let len = seed_buf.len() as u64;
+    if len == 0 {
+        return Err(MutationError::EmptySeed);
+    }
 
     //If is_clone_from_old_string does not equal to 0, we clone bytes from old string
-    //else, we random set a block of bytes. 
+    //else, we random set a block of bytes.
     let is_clone_from_old_string = rang.gen_range(0, 4);
 
 
-    if(is_clone_from_old_string == 0) {
+    if is_clone_from_old_string == 0 {
         clone_len = choose_block_len(config::HAVOC_BLK_XL, rang) as usize;
         clone_start_pos = 0;
     }
     else {
         clone_len = choose_block_len(len,rang) as usize;
-        assert!(0 < len-(clone_len as u64)+1);
         clone_start_pos = rang.gen_range(0, len-(clone_len as u64)+1) as usize;
     }
+    if len + clone_len as u64 > config::MAX_FILE {
+        return Err(MutationError::ExceedMaxFile(len + clone_len as u64));
+    }
     let insert = rang.gen_range(0, len) as usize;
-    let mut temp_seed = input_seed.clone();
-    let insert_block: Vec<_>;
+    let len = len as usize;
 
-    if(is_clone_from_old_string == 0) {
-        if(rang.gen_range(0,2) == 0) {
-            let pad = rang.gen_range(0,256 as u16) as u8;
-            insert_block = vec![pad;clone_len];
+    //make room for the block: [insert, len) moves to [insert+clone_len, len+clone_len)
+    seed_buf.resize(len + clone_len, 0);
+    seed_buf.copy_within(insert..len, insert + clone_len);
+
+    if is_clone_from_old_string == 0 {
+        let pad = if rang.gen_range(0,2) == 0 {
+            rang.gen_range(0,256_u16) as u8
         }
         else {
-            let pad_pos = rang.gen_range(0,len) as usize;
-            let pad = output_seed[pad_pos];
-            insert_block = vec![pad;clone_len];
+            let pad_pos = rang.gen_range(0,len);
+            //bytes behind insert have been moved
+            seed_buf[if pad_pos < insert { pad_pos } else { pad_pos + clone_len }]
+        };
+        for byte in &mut seed_buf[insert..insert + clone_len] {
+            *byte = pad;
         }
-        
     }
     else {
-        insert_block = temp_seed.drain(clone_start_pos..clone_start_pos+clone_len).collect();
+        //the part of the block in front of insert stayed where it was,
+        //the part behind it has moved clone_len bytes right
+        let clone_end_pos = clone_start_pos + clone_len;
+        let front_end = cmp::min(clone_end_pos, insert);
+        if clone_start_pos < front_end {
+            seed_buf.copy_within(clone_start_pos..front_end, insert);
+        }
+        let back_start = cmp::max(clone_start_pos, insert);
+        if back_start < clone_end_pos {
+            seed_buf.copy_within(back_start + clone_len..clone_end_pos + clone_len,
+                                 insert + (back_start - clone_start_pos));
+        }
     }
-     
-    output_seed.splice(insert..insert, insert_block.iter().cloned());
 
-    output_seed
+    Ok(())
+}
+
+pub fn insert_clone_bytes<R: Rng>(input_seed: &[u8], rang:& mut R)->Result<Vec<u8>, MutationError> {
+    cloned(input_seed, |seed_buf| insert_clone_bytes_in_place(seed_buf, rang))
 }
 
 // pub fn splice(input_seed: &Vec<u8>, random_input_seed: &Vec<u8>, rang:& mut rand::ThreadRng)->Vec<u8> {
 //     assert!(output_seed.len() < 2);
 //     output_seed = input_seed.clone();
-    
-    
+
+
 //     output_seed
 // }
 
-pub fn havoc_mutate(input_seed: &Vec<u8>, rang:& mut rand::ThreadRng)->Vec<u8> {
-    // let mut random_value = rang.gen_range(0,config::HAVOC_WAY as u64);
-    let len = input_seed.len() as u64;
+//Names of the havoc operations, by the number havoc_op_in_place takes
+pub const HAVOC_OPS: [&str; config::HAVOC_WAY as usize + config::HAVOC_TEXT_WAY as usize] = [
+    "flip1", "flip2", "flip4", "flip8", "arith8+", "arith8-", "interest8", "rand8", "clone",
+    "flip16", "arith16+", "arith16-", "interest16", "delete", "delete",
+    "flip32", "arith32+", "arith32-", "interest32",
+    //text mode only, see text_op_cnt
+    "text-number", "text-sep+", "text-sep-", "text-dup-line", "text-swap-words", "text-case",
+];
 
+//One random havoc operation. Returns its name, e.g. "flip8" or "delete".
+pub fn havoc_mutate_in_place<R: Rng>(seed_buf:&mut Vec<u8>, rang:& mut R)->Result<&'static str, MutationError> {
+    let op_cnt = havoc_op_cnt(seed_buf.len() as u64)?;
+    let random_value = rang.gen_range(0,op_cnt);
+    havoc_op_in_place(seed_buf, random_value, rang)
+}
+
+//How many of the havoc operations work on len bytes, they are the first ones of HAVOC_OPS
+pub fn havoc_op_cnt(len:u64)->Result<u32, MutationError> {
     let max_random_value = match len{
+                            0 => {
+                                return Err(MutationError::EmptySeed);
+                            }
                             1 => {
                                 9
                             }
-                            2...3 => {
+                            2..=3 => {
                                 15
                             }
                             _ => config::HAVOC_WAY as u32
                            };
+    Ok(max_random_value)
+}
 
-    let random_value = rang.gen_range(0,max_random_value);
+//How many havoc operations work on a text input when op_cnt of the others work on it:
+//the text operations come after all of them
+pub fn text_op_cnt(op_cnt:u32)->u32 {
+    if op_cnt == config::HAVOC_WAY as u32 { op_cnt + config::HAVOC_TEXT_WAY as u32 } else { op_cnt }
+}
+
+//Havoc operation number random_value (see HAVOC_OPS), with random positions and values
+pub fn havoc_op_in_place<R: Rng>(seed_buf:&mut Vec<u8>, random_value:u32, rang:& mut R)->Result<&'static str, MutationError> {
+    havoc_op_placed_in_place(seed_buf, random_value, None, rang)
+}
 
+//havoc_op_in_place, with the position and byte order (pos, another_endian) of the
+//16 and 32 bit arith and interest operations given by at instead of random
+pub fn havoc_op_placed_in_place<R: Rng>(seed_buf:&mut Vec<u8>, random_value:u32, at:Option<(u64, bool)>, rang:& mut R)
+    ->Result<&'static str, MutationError> {
+    let len = seed_buf.len() as u64;
+    //the operations from 9 on need two bytes, the ones from 15 on four
+    if random_value < config::HAVOC_WAY as u32 && random_value >= havoc_op_cnt(len)? {
+        let width = if random_value < 15 { 2 } else { 4 };
+        return Err(MutationError::OutOfBounds { pos: 0, width, len });
+    }
     match random_value {
-        //0--8 operations need one byte at least 
+        //0--8 operations need one byte at least
         0 => {
             //println!("we are using flipping bit");
-            flip_one_bit(input_seed, rang.gen_range(0,len.wrapping_shl(3)))
+            flip_bits_in_place(seed_buf, rang.gen_range(0,len.wrapping_shl(3)), 1).map(|_| "flip1")
         },
         1 => {
             // println!("we are using flipping two bits");
             let pos = rang.gen_range(0, len.wrapping_shl(3)-1);
-            flip_two_bits(input_seed, pos)  
+            flip_bits_in_place(seed_buf, pos, 2).map(|_| "flip2")
         },
         2 => {
             // println!("we are using flipping four bits");
             let pos = rang.gen_range(0, len.wrapping_shl(3)-3);
-            flip_four_bits(input_seed, pos)  
+            flip_bits_in_place(seed_buf, pos, 4).map(|_| "flip4")
         },
         3 => {
             // println!("we are using flipping one byte");
             let pos = rang.gen_range(0, len);
-            flip_one_byte(input_seed, pos)
+            flip_bytes_in_place(seed_buf, pos, 1).map(|_| "flip8")
         },
         4 => {
             // println!("we are using arith_add_one_byte");
             let pos = rang.gen_range(0, len);
             let arith_number = rang.gen_range(0, config::ARITH_MAX);
-            arithmetic_add_one_byte(input_seed, pos, arith_number)
+            arithmetic_add_in_place(seed_buf, pos, 1, arith_number as u32, false).map(|_| "arith8+")
         },
         5 => {
             // println!("we are using arith_sub_one_byte");
             let pos = rang.gen_range(0, len);
             let arith_number = rang.gen_range(0, config::ARITH_MAX);
-            arithmetic_sub_one_byte(input_seed, pos, arith_number)
+            arithmetic_sub_in_place(seed_buf, pos, 1, arith_number as u32, false).map(|_| "arith8-")
         },
         6 => {
             // println!("we are using interesting8");
             let pos = rang.gen_range(0,len);
             let index_number = rang.gen_range(0,config::INTERESTING_8_CNT);
-            interesting8_replace(input_seed, pos, index_number)
+            interesting_replace_in_place(seed_buf, pos, 1, index_number, false).map(|_| "interest8")
         },
         7 => {
             // println!("We are setting a random byte with a random value");
             let pos = rang.gen_range(0, len);
             let random_byte_value = 1 + rang.gen_range(0,255);
-            set_one_byte(input_seed, pos, random_byte_value)
+            set_one_byte_in_place(seed_buf, pos, random_byte_value).map(|_| "rand8")
         },
         8 => {
             //afl-fuzz 13 Extra-large blocks, selected very rarely (<5% of the time)
-            if input_seed.len() as u64 + config::HAVOC_BLK_XL < config::MAX_FILE {
-                // println!("We are inserting the clone bytes");    
-                insert_clone_bytes(input_seed, rang)
+            // println!("We are inserting the clone bytes");
+            match insert_clone_bytes_in_place(seed_buf, rang) {
+                Err(MutationError::ExceedMaxFile(_)) => {
+                    //the seed is too long to grow, set a random byte instead
+                    let pos = rang.gen_range(0, len);
+                    let random_byte_value = 1 + rang.gen_range(0,255);
+                    set_one_byte_in_place(seed_buf, pos, random_byte_value).map(|_| "rand8")
+                },
+                result => result.map(|_| "clone"),
             }
-            else {
-                //not a good solution when the length is too long, but we first deal with it in this way
-                //need a more elegant way in the second edition (like return a Result and deal with Err in lib.rs)
-                let pos = rang.gen_range(0, len);
-                let random_byte_value = 1 + rang.gen_range(0,255);
-                set_one_byte(input_seed, pos, random_byte_value)
-            }
-            
         },
         //9--14 operations need two bytes at least
         9 => {
             // println!("we are using flipping two bytes");
             let pos = rang.gen_range(0, len-1);
-            flip_two_bytes(input_seed, pos)
+            flip_bytes_in_place(seed_buf, pos, 2).map(|_| "flip16")
         },
         10 => {
             // println!("we are using arithmetic_add_two_bytes, randomly choose endian");
-            let pos = rang.gen_range(0, len-1);            
+            let pos = at.map_or_else(|| rang.gen_range(0, len-1), |at| at.0);
             let arith_number = rang.gen_range(0, config::ARITH_MAX);
-            let use_another = rang.gen_range(0,2);
-            if use_another == 1 {
-                arithmetic_add_two_bytes_another_endian(input_seed, pos, arith_number as u16)
-            }
-            else {
-                arithmetic_add_two_bytes(input_seed, pos, arith_number as u16)
-            }
+            let use_another = at.map_or_else(|| rang.gen_range(0,2) == 1, |at| at.1);
+            arithmetic_add_in_place(seed_buf, pos, 2, arith_number as u32, use_another).map(|_| "arith16+")
         },
         11 => {
             // println!("we are using arithmetic_sub_two_bytes, randomly choose endian");
-            let pos = rang.gen_range(0, len-1);            
+            let pos = at.map_or_else(|| rang.gen_range(0, len-1), |at| at.0);
             let arith_number = rang.gen_range(0, config::ARITH_MAX);
-            let use_another = rang.gen_range(0,2);
-            if use_another == 1 {
-                arithmetic_sub_two_bytes_another_endian(input_seed, pos, arith_number as u16)
-            }
-            else {
-                arithmetic_sub_two_bytes(input_seed, pos, arith_number as u16)
-            }
+            let use_another = at.map_or_else(|| rang.gen_range(0,2) == 1, |at| at.1);
+            arithmetic_sub_in_place(seed_buf, pos, 2, arith_number as u32, use_another).map(|_| "arith16-")
         },
         12 => {
             // println!("we are using interesting16, randomly choose endian");
-            let pos = rang.gen_range(0, len-1);            
+            let pos = at.map_or_else(|| rang.gen_range(0, len-1), |at| at.0);
             let index_number = rang.gen_range(0,config::INTERESTING_16_CNT);
-            let use_another = rang.gen_range(0,2);
-            if use_another == 1 {
-                interesting16_replace(input_seed, pos, index_number)
-            }
-            else {
-                interesting16_replace_another_endian(input_seed, pos, index_number)
-            }
-            
+            let use_another = at.map_or_else(|| rang.gen_range(0,2) == 1, |at| at.1);
+            interesting_replace_in_place(seed_buf, pos, 2, index_number, use_another).map(|_| "interest16")
         },
-        13 ...14 => {
+        13 ..=14 => {
             //need least two bytes
             // println!("we try to delete bytes");
-            let mut del_from:u64;
-            let mut del_len:u64;
-            
-            del_len = choose_block_len(len-1, rang);
-            del_from = rang.gen_range(0, len - del_len + 1);
-            delete_byte(input_seed, del_from, del_len)
+            let del_len = choose_block_len(len-1, rang);
+            let del_from = rang.gen_range(0, len - del_len + 1);
+            delete_bytes_in_place(seed_buf, del_from, del_len).map(|_| "delete")
         },
         //15--18 operations need four bytes at least
         15 => {
             // println!("we are using flipping four bytes");
             let pos = rang.gen_range(0, len-3);
-            flip_four_bytes(input_seed, pos)
+            flip_bytes_in_place(seed_buf, pos, 4).map(|_| "flip32")
         },
         16 => {
             // println!("we are using arithmetic_add_four_bytes, randomly choose endian");
-            let pos = rang.gen_range(0, len-3);
+            let pos = at.map_or_else(|| rang.gen_range(0, len-3), |at| at.0);
             let arith_number = rang.gen_range(0, config::ARITH_MAX);
-            let use_another = rang.gen_range(0,2);
-            if use_another == 1 {
-                arithmetic_add_four_bytes_another_endian(input_seed, pos, arith_number as u32)
-            }
-            else {
-                arithmetic_add_four_bytes(input_seed, pos, arith_number as u32)
-            }
+            let use_another = at.map_or_else(|| rang.gen_range(0,2) == 1, |at| at.1);
+            arithmetic_add_in_place(seed_buf, pos, 4, arith_number as u32, use_another).map(|_| "arith32+")
         },
         17 => {
             // println!("we are using arithmetic_sub_four_bytes, randomly choose endian");
-            let pos = rang.gen_range(0, len-3);
+            let pos = at.map_or_else(|| rang.gen_range(0, len-3), |at| at.0);
             let arith_number = rang.gen_range(0, config::ARITH_MAX);
-            let use_another = rang.gen_range(0,2);
-            if use_another == 1 {
-                arithmetic_sub_four_bytes_another_endian(input_seed, pos, arith_number as u32)
-            }
-            else {
-                arithmetic_sub_four_bytes(input_seed, pos, arith_number as u32)
-            }
+            let use_another = at.map_or_else(|| rang.gen_range(0,2) == 1, |at| at.1);
+            arithmetic_sub_in_place(seed_buf, pos, 4, arith_number as u32, use_another).map(|_| "arith32-")
         },
         18 => {
             // println!("we are using interesting32, randomly choose endian");
-            let pos = rang.gen_range(0, len-3);
+            let pos = at.map_or_else(|| rang.gen_range(0, len-3), |at| at.0);
             let index_number = rang.gen_range(0,config::INTERESTING_32_CNT);
-            let use_another = rang.gen_range(0,2);
-            if use_another == 1 {
-                interesting32_replace(input_seed, pos, index_number)
-            }
-            else {
-                interesting32_replace_another_endian(input_seed, pos, index_number)
-            }
+            let use_another = at.map_or_else(|| rang.gen_range(0,2) == 1, |at| at.1);
+            interesting_replace_in_place(seed_buf, pos, 4, index_number, use_another).map(|_| "interest32")
         },
-        
-        
+
+
+        //19--24 operations work on text
         19 => {
-            //afl-case 14
-            input_seed.clone()
+            text::replace_number(seed_buf, rang);
+            Ok("text-number")
+        },
+        20 => {
+            text::insert_separator(seed_buf, rang);
+            Ok("text-sep+")
+        },
+        21 => {
+            text::delete_separator(seed_buf, rang);
+            Ok("text-sep-")
+        },
+        22 => {
+            text::duplicate_line(seed_buf, rang);
+            Ok("text-dup-line")
         },
-        _ => input_seed.clone()
+        23 => {
+            text::swap_words(seed_buf, rang);
+            Ok("text-swap-words")
+        },
+        24 => {
+            text::change_case(seed_buf, rang);
+            Ok("text-case")
+        },
+        _ => Err(MutationError::UnknownHavocOp(random_value)),
+    }
+}
+
+//Write token at byte_pos. Ok(false) if it does not fit or the bytes are already the token.
+pub fn extras_overwrite_in_place(seed_buf:&mut [u8], byte_pos:u64, token:&[u8])->Result<bool, MutationError> {
+    check_bytes(seed_buf, byte_pos, 1)?;
+    let pos = byte_pos as usize;
+    if token.is_empty() || token.len() > seed_buf.len() - pos {
+        return Ok(false);
+    }
+    let target = &mut seed_buf[pos..pos + token.len()];
+    if target == token {
+        return Ok(false);
+    }
+    target.copy_from_slice(token);
+    Ok(true)
+}
+
+//Insert token in front of byte_pos (at the end if byte_pos is the length).
+//Ok(false) if the seed would grow beyond config::MAX_FILE.
+pub fn extras_insert_in_place(seed_buf:&mut Vec<u8>, byte_pos:u64, token:&[u8])->Result<bool, MutationError> {
+    let len = seed_buf.len() as u64;
+    if len == 0 {
+        return Err(MutationError::EmptySeed);
+    }
+    if byte_pos > len {
+        return Err(MutationError::OutOfBounds { pos: byte_pos, width: 0, len });
+    }
+    if token.is_empty() || len + token.len() as u64 > config::MAX_FILE {
+        return Ok(false);
+    }
+    let pos = byte_pos as usize;
+    seed_buf.splice(pos..pos, token.iter().cloned());
+    Ok(true)
+}
+
+//havoc_mutate_in_place, plus overwriting or inserting a random dictionary token
+pub fn havoc_mutate_with_extras_in_place<R: Rng>(seed_buf:&mut Vec<u8>, extras:&[Vec<u8>], rang:& mut R)->Result<&'static str, MutationError> {
+    havoc_mutate_with_extras_chosen_in_place(seed_buf, extras, rang, |op_cnt, rang| rang.gen_range(0, op_cnt), |_, _, _| None)
+}
+
+//havoc_mutate_with_extras_in_place, with choose_op picking the havoc operation
+//among the first op_cnt ones and place(op, len) where it goes, see havoc_op_placed_in_place
+pub fn havoc_mutate_with_extras_chosen_in_place<R, F, P>(seed_buf:&mut Vec<u8>, extras:&[Vec<u8>], rang:& mut R, choose_op:F, place:P)
+    ->Result<&'static str, MutationError>
+    where R: Rng, F: FnOnce(u32, &mut R)->u32, P: FnOnce(u32, u64, &mut R)->Option<(u64, bool)> {
+    let len = seed_buf.len() as u64;
+    if len == 0 {
+        return Err(MutationError::EmptySeed);
+    }
+    //afl-fuzz adds the two dictionary operations to the others
+    if extras.is_empty() || rang.gen_range(0, config::HAVOC_WAY as u32 + 2) < config::HAVOC_WAY as u32 {
+        let op = choose_op(havoc_op_cnt(len)?, rang);
+        let at = place(op, len, rang);
+        return havoc_op_placed_in_place(seed_buf, op, at, rang);
+    }
+    let token = &extras[rang.gen_range(0, extras.len())];
+    if rang.gen_range(0, 2) == 0 {
+        extras_overwrite_in_place(seed_buf, rang.gen_range(0, len), token).map(|_| "extra-over")
+    }
+    else {
+        extras_insert_in_place(seed_buf, rang.gen_range(0, len + 1), token).map(|_| "extra-insert")
     }
-}
\ No newline at end of file
+}
+
+pub fn havoc_mutate<R: Rng>(input_seed: &[u8], rang:& mut R)->Result<Vec<u8>, MutationError> {
+    cloned(input_seed, |seed_buf| havoc_mutate_in_place(seed_buf, rang).map(|_| ()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn empty_seed_is_an_error() {
+        let mut rang = rand::thread_rng();
+        assert_eq!(flip_one_bit(&[], 0), Err(MutationError::EmptySeed));
+        assert_eq!(delete_byte(&[], 0, 1), Err(MutationError::EmptySeed));
+        assert_eq!(insert_clone_bytes(&[], &mut rang), Err(MutationError::EmptySeed));
+        assert_eq!(havoc_mutate(&[], &mut rang), Err(MutationError::EmptySeed));
+    }
+
+    #[test]
+    fn havoc_ops_check_the_length() {
+        let mut rang = rand::thread_rng();
+        assert_eq!(havoc_op_cnt(1), Ok(9));
+        assert_eq!(havoc_op_cnt(4), Ok(config::HAVOC_WAY as u32));
+        assert_eq!((text_op_cnt(config::HAVOC_WAY as u32), text_op_cnt(9)), (HAVOC_OPS.len() as u32, 9));
+        let mut seed_buf = vec![1u8, 2];
+        assert_eq!(havoc_op_in_place(&mut seed_buf, 9, &mut rang), Ok("flip16"));
+        assert_eq!(havoc_op_in_place(&mut seed_buf, 15, &mut rang), Err(MutationError::OutOfBounds { pos: 0, width: 4, len: 2 }));
+        for (op, name) in HAVOC_OPS.iter().enumerate() {
+            let mut seed_buf = vec![0x55u8; 64];
+            assert_eq!(havoc_op_in_place(&mut seed_buf, op as u32, &mut rang), Ok(*name));
+        }
+        let op_cnt = HAVOC_OPS.len() as u32;
+        assert_eq!(havoc_op_in_place(&mut seed_buf, op_cnt, &mut rang), Err(MutationError::UnknownHavocOp(op_cnt)));
+    }
+
+    #[test]
+    fn extras_overwrite_and_insert_tokens() {
+        let mut seed_buf = vec![1u8, 2, 3, 4];
+        assert_eq!(extras_overwrite_in_place(&mut seed_buf, 1, b"ab"), Ok(true));
+        assert_eq!(seed_buf, vec![1, b'a', b'b', 4]);
+        //already there, or does not fit
+        assert_eq!(extras_overwrite_in_place(&mut seed_buf, 1, b"ab"), Ok(false));
+        assert_eq!(extras_overwrite_in_place(&mut seed_buf, 3, b"ab"), Ok(false));
+        assert_eq!(extras_insert_in_place(&mut seed_buf, 4, b"xy"), Ok(true));
+        assert_eq!(seed_buf, vec![1, b'a', b'b', 4, b'x', b'y']);
+        assert_eq!(extras_insert_in_place(&mut seed_buf, 0, b"z"), Ok(true));
+        assert_eq!(seed_buf[0], b'z');
+        assert!(extras_insert_in_place(&mut seed_buf, 8, b"z").is_err());
+    }
+
+    #[test]
+    fn out_of_bounds_is_an_error() {
+        assert_eq!(flip_one_bit(&[1], 8), Err(MutationError::OutOfBounds { pos: 1, width: 1, len: 1 }));
+        assert_eq!(flip_four_bits(&[1], 6), Err(MutationError::OutOfBounds { pos: 0, width: 2, len: 1 }));
+        assert_eq!(flip_two_bytes(&[1], 0), Err(MutationError::OutOfBounds { pos: 0, width: 2, len: 1 }));
+        assert_eq!(arithmetic_add_four_bytes(&[1, 2, 3, 4], 1, 1),
+                   Err(MutationError::OutOfBounds { pos: 1, width: 4, len: 4 }));
+        assert_eq!(delete_byte(&[1, 2], 1, 2), Err(MutationError::OutOfBounds { pos: 1, width: 2, len: 2 }));
+        assert_eq!(delete_byte(&[1, 2], 0, 0), Err(MutationError::EmptyBlock));
+        assert_eq!(interesting8_replace(&[1], 0, config::INTERESTING_8_CNT),
+                   Err(MutationError::InterestingIndex(config::INTERESTING_8_CNT)));
+        assert_eq!(flip_bytes_in_place(&mut [1, 2, 3], 0, 3), Err(MutationError::UnsupportedWidth(3)));
+    }
+
+    #[test]
+    fn arithmetic_wraps_instead_of_overflowing() {
+        assert_eq!(arithmetic_add_one_byte(&[0xff], 0, 1), Ok(vec![0]));
+        assert_eq!(arithmetic_sub_one_byte(&[0], 0, 1), Ok(vec![0xff]));
+        assert!(arithmetic_add_four_bytes_option(&[0xff; 4], 0, 35).is_ok());
+        assert!(arithmetic_sub_two_bytes_another_endian_option(&[0; 2], 0, 35).is_ok());
+    }
+
+    #[test]
+    fn multi_byte_values_keep_every_bit() {
+        assert_eq!(arithmetic_add_two_bytes(&[0x12, 0xf0], 0, 0x20), Ok(vec![0x13, 0x10]));
+        assert_eq!(arithmetic_add_two_bytes_another_endian(&[0xf0, 0x12], 0, 0x20), Ok(vec![0x10, 0x13]));
+        assert_eq!(arithmetic_sub_four_bytes(&[0, 0, 0, 0], 0, 1), Ok(vec![0xff; 4]));
+        assert_eq!(arithmetic_add_four_bytes_another_endian(&[0xff, 0xff, 0, 0x80], 0, 1),
+                   Ok(vec![0, 0, 1, 0x80]));
+        //INTERESTING_16[9] is -32768, INTERESTING_32[19] is -2147483648
+        assert_eq!(interesting16_replace(&[0, 0], 0, 9), Ok(vec![0x80, 0]));
+        assert_eq!(interesting16_replace_another_endian(&[0, 0], 0, 9), Ok(vec![0, 0x80]));
+        assert_eq!(interesting32_replace(&[0; 4], 0, 19), Ok(vec![0x80, 0, 0, 0]));
+        assert_eq!(interesting32_replace(&[0; 4], 0, 12), Ok(vec![0, 0, 0, 0xff]));
+    }
+
+    #[test]
+    fn skipped_arithmetic_leaves_the_buffer_alone() {
+        let mut seed_buf = vec![0u8, 0];
+        assert_eq!(arithmetic_add_option_in_place(&mut seed_buf, 0, 1, 1, false), Ok(false));
+        assert_eq!(arithmetic_add_option_in_place(&mut seed_buf, 0, 1, 5, false), Ok(true));
+        assert_eq!(seed_buf, vec![5, 0]);
+    }
+
+    #[test]
+    fn max_file_seed_cannot_grow() {
+        let mut rang = rand::thread_rng();
+        let input_seed = vec![0u8; config::MAX_FILE as usize];
+        for _ in 0..64 {
+            match insert_clone_bytes(&input_seed, &mut rang) {
+                Err(MutationError::ExceedMaxFile(len)) => assert!(len > config::MAX_FILE),
+                other => panic!("unexpected {:?}", other.map(|seed| seed.len())),
+            }
+            let output_seed = havoc_mutate(&input_seed, &mut rang).unwrap();
+            assert!(output_seed.len() as u64 <= config::MAX_FILE);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn havoc_never_panics(input_seed in prop::collection::vec(any::<u8>(), 0..64)) {
+            let mut rang = rand::thread_rng();
+            for _ in 0..64 {
+                match havoc_mutate(&input_seed, &mut rang) {
+                    Ok(output_seed) => prop_assert!(!output_seed.is_empty()),
+                    Err(e) => prop_assert!(input_seed.is_empty() && e == MutationError::EmptySeed),
+                }
+            }
+        }
+
+        #[test]
+        fn insert_clone_bytes_copies_a_block_of_the_seed(input_seed in prop::collection::vec(any::<u8>(), 1..64)) {
+            let mut rang = rand::thread_rng();
+            let output_seed = insert_clone_bytes(&input_seed, &mut rang).unwrap();
+            let clone_len = output_seed.len() - input_seed.len();
+            prop_assert!(clone_len > 0);
+            //the seed is still there once the inserted block is cut out again
+            let insert = (0..input_seed.len()).find(|&insert| {
+                output_seed[..insert] == input_seed[..insert] &&
+                    output_seed[insert + clone_len..] == input_seed[insert..]
+            });
+            prop_assert!(insert.is_some());
+        }
+
+        #[test]
+        fn mutators_never_panic(input_seed in prop::collection::vec(any::<u8>(), 0..8),
+                                pos in 0u64..80, arith_number in any::<u32>(), index_number in any::<u8>()) {
+            let _ = flip_one_bit(&input_seed, pos);
+            let _ = flip_two_bits(&input_seed, pos);
+            let _ = flip_four_bits(&input_seed, pos);
+            let _ = flip_one_byte(&input_seed, pos);
+            let _ = flip_two_bytes(&input_seed, pos);
+            let _ = flip_four_bytes(&input_seed, pos);
+            let _ = arithmetic_add_one_byte_option(&input_seed, pos, arith_number as u8);
+            let _ = arithmetic_sub_one_byte_option(&input_seed, pos, arith_number as u8);
+            let _ = arithmetic_add_two_bytes_option(&input_seed, pos, arith_number as u16);
+            let _ = arithmetic_sub_two_bytes_another_endian_option(&input_seed, pos, arith_number as u16);
+            let _ = arithmetic_add_four_bytes_another_endian_option(&input_seed, pos, arith_number);
+            let _ = arithmetic_sub_four_bytes_option(&input_seed, pos, arith_number);
+            let _ = interesting8_replace(&input_seed, pos, index_number);
+            let _ = interesting16_replace(&input_seed, pos, index_number);
+            let _ = interesting32_replace_another_endian(&input_seed, pos, index_number);
+            let _ = delete_byte(&input_seed, pos, u64::from(index_number));
+        }
+    }
+}
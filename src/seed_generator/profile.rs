@@ -0,0 +1,225 @@
+/*
+   DeepSAFL - seed field inference
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+// The arith and interesting stages try every offset with every width and byte order,
+// though most of these do not hold an integer. A quick look at the seed tells where
+// its fields likely are: runs of text, bytes that repeat (magic values), small
+// integers, whose high bytes are zero, and integers that hold the length of the rest
+// of the input. The 16 and 32 bit stages then skip the widths and byte orders that do
+// not fit the fields at an offset, and havoc puts its 16 and 32 bit operations on the
+// integers more often. Offsets outside of every field are still tried every way.
+
+use std::collections::HashMap;
+
+use rand::Rng;
+
+use super::config;
+use super::text::is_printable;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    //printable text
+    Ascii,
+    //four bytes without a zero that show up more than once
+    Magic,
+    //an integer holding the number of bytes after it
+    LengthPrefix { width: u8, big_endian: bool },
+    //a small integer: its high bytes are zero, its low ones are not
+    Int { width: u8, big_endian: bool },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InferredField {
+    pub offset: u64,
+    pub len: u64,
+    pub kind: FieldKind,
+}
+
+impl InferredField {
+    fn overlaps(&self, offset: u64, len: u64)->bool {
+        self.offset < offset + len && offset < self.offset + self.len
+    }
+
+    //width and byte order, if it is an integer
+    fn int(&self)->Option<(u64, bool)> {
+        match self.kind {
+            FieldKind::Int { width, big_endian } | FieldKind::LengthPrefix { width, big_endian } => Some((width as u64, big_endian)),
+            _ => None,
+        }
+    }
+}
+
+//The fields of a seed, by offset. The default one has no field, so it allows everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SeedProfile {
+    fields: Vec<InferredField>,
+}
+
+fn load(bytes: &[u8], big_endian: bool)->u64 {
+    let fold = |value: u64, byte: &u8| (value << 8) | *byte as u64;
+    if big_endian { bytes.iter().fold(0, fold) } else { bytes.iter().rev().fold(0, fold) }
+}
+
+impl SeedProfile {
+    pub fn fields(&self)->&[InferredField] {
+        &self.fields
+    }
+
+    //Whether the width bytes at offset are worth changing as an integer in that byte order:
+    //not in text or a magic value, and the low bytes of an integer field if there is one
+    pub fn allows(&self, offset: u64, width: u64, big_endian: bool)->bool {
+        let mut in_int = false;
+        for field in self.fields.iter().filter(|field| field.overlaps(offset, width)) {
+            let (field_width, field_big_endian) = match field.int() {
+                Some(int) => int,
+                None => return false,
+            };
+            in_int = true;
+            let low_bytes = if big_endian { offset + width == field.offset + field_width } else { offset == field.offset };
+            if big_endian == field_big_endian && width <= field_width && low_bytes {
+                return true;
+            }
+        }
+        !in_int
+    }
+
+    //Where the havoc operation op (see mutator::HAVOC_OPS) goes in an input of len bytes:
+    //(offset, another_endian) of one of the integers of its width, sometimes, or None
+    //for a random place
+    pub fn havoc_placement<R: Rng>(&self, op: u32, len: u64, rang: &mut R)->Option<(u64, bool)> {
+        let width = match op {
+            10..=12 => 2,
+            16..=18 => 4,
+            _ => return None,
+        };
+        let ints: Vec<(u64, bool)> = self.fields.iter().filter(|field| field.offset + width <= len)
+            .filter_map(|field| field.int().filter(|&(field_width, _)| field_width == width).map(|(_, big_endian)| (field.offset, big_endian)))
+            .collect();
+        if ints.is_empty() || rang.gen::<f64>() >= config::FIELD_HAVOC_BIAS {
+            return None;
+        }
+        let (offset, big_endian) = ints[rang.gen_range(0, ints.len())];
+        //the mutators write big endian unless told another_endian
+        Some((offset, !big_endian))
+    }
+}
+
+//Look for the fields of seed
+pub fn infer_fields(seed: &[u8])->SeedProfile {
+    let len = seed.len();
+    let mut fields = Vec::new();
+    let mut taken = vec![false; len];
+
+    let mut start = 0;
+    while start < len {
+        let end = start + seed[start..].iter().take_while(|&&byte| is_printable(byte)).count();
+        if end - start >= config::FIELD_MIN_TEXT {
+            fields.push(InferredField { offset: start as u64, len: (end - start) as u64, kind: FieldKind::Ascii });
+            taken[start..end].iter_mut().for_each(|taken| *taken = true);
+        }
+        start = end + 1;
+    }
+
+    let mut windows: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    for pos in (0..len.saturating_sub(3)).filter(|&pos| !taken[pos..pos + 4].contains(&true)) {
+        let window = &seed[pos..pos + 4];
+        if !window.contains(&0) && window.iter().any(|&byte| byte != window[0]) {
+            windows.entry(window).or_default().push(pos);
+        }
+    }
+    let mut magic: Vec<usize> = windows.values().filter(|positions| positions.len() > 1).flat_map(|positions| positions.iter().cloned()).collect();
+    magic.sort_unstable();
+    for pos in magic {
+        match fields.last_mut() {
+            //overlapping windows make one field
+            Some(field) if field.kind == FieldKind::Magic && field.offset + field.len >= pos as u64 => {
+                field.len = pos as u64 + 4 - field.offset;
+            },
+            _ => fields.push(InferredField { offset: pos as u64, len: 4, kind: FieldKind::Magic }),
+        }
+        taken[pos..pos + 4].iter_mut().for_each(|taken| *taken = true);
+    }
+
+    let mut pos = 0;
+    while pos < len {
+        let mut found = None;
+        'widths: for &width in [4usize, 2].iter() {
+            if pos + width > len || taken[pos..pos + width].contains(&true) {
+                continue;
+            }
+            for &big_endian in [false, true].iter() {
+                let value = load(&seed[pos..pos + width], big_endian);
+                if value != 0 && (value == len as u64 || value == (len - pos - width) as u64) {
+                    found = Some((width, FieldKind::LengthPrefix { width: width as u8, big_endian }));
+                    break 'widths;
+                }
+                let (high, low) = seed[pos..pos + width].split_at(width / 2);
+                let (high, low) = if big_endian { (high, low) } else { (low, high) };
+                if high.iter().all(|&byte| byte == 0) && low.iter().any(|&byte| byte != 0) {
+                    found = Some((width, FieldKind::Int { width: width as u8, big_endian }));
+                    break 'widths;
+                }
+            }
+        }
+        match found {
+            Some((width, kind)) => {
+                fields.push(InferredField { offset: pos as u64, len: width as u64, kind });
+                pos += width;
+            },
+            None => pos += 1,
+        }
+    }
+    fields.sort_by_key(|field| field.offset);
+    SeedProfile { fields }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{SeedableRng, StdRng};
+
+    fn field(offset: u64, len: u64, kind: FieldKind)->InferredField {
+        InferredField { offset, len, kind }
+    }
+
+    #[test]
+    fn infers_text_magic_lengths_and_integers() {
+        //a length of the rest, a chunk tag, a small big endian integer, the tag again, text
+        let seed = b"\x19\x00\xfeTAG\x00\x00\x01\x2c\xfeTAG\x05\x00hello world";
+        let profile = infer_fields(seed);
+        assert_eq!(profile.fields(), &[
+            field(0, 2, FieldKind::LengthPrefix { width: 2, big_endian: false }),
+            field(2, 4, FieldKind::Magic),
+            field(6, 4, FieldKind::Int { width: 4, big_endian: true }),
+            field(10, 4, FieldKind::Magic),
+            field(14, 2, FieldKind::Int { width: 2, big_endian: false }),
+            field(16, 11, FieldKind::Ascii),
+        ][..]);
+
+        //the low bytes of an integer in its byte order, nothing in text or magic values
+        assert!(profile.allows(0, 2, false) && !profile.allows(0, 2, true));
+        assert!(profile.allows(8, 2, true) && profile.allows(6, 4, true) && !profile.allows(6, 2, true));
+        assert!(!profile.allows(3, 2, false) && !profile.allows(20, 4, true));
+        assert!(profile.allows(14, 2, false) && !profile.allows(14, 2, true) && !profile.allows(15, 2, false));
+        assert!(SeedProfile::default().allows(3, 4, true));
+    }
+
+    #[test]
+    fn havoc_goes_to_the_integers_of_its_width() {
+        let profile = infer_fields(b"\xff\xfe\xfd\xfc\x01\x00\xfb\xfa");
+        assert_eq!(profile.fields(), &[field(4, 2, FieldKind::Int { width: 2, big_endian: false })][..]);
+        let mut rang = StdRng::from_seed(&[1usize, 2][..]);
+        let placements: Vec<_> = (0..100).map(|_| profile.havoc_placement(10, 8, &mut rang)).collect();
+        assert!(placements.iter().any(|at| at.is_none()));
+        assert!(placements.iter().all(|&at| at.is_none() || at == Some((4, true))));
+        //no 32 bit integer, no flip and nothing that is past the end of the input
+        assert!((0..100).all(|_| profile.havoc_placement(16, 8, &mut rang).is_none()));
+        assert!((0..100).all(|_| profile.havoc_placement(3, 8, &mut rang).is_none()));
+        assert!((0..100).all(|_| profile.havoc_placement(10, 5, &mut rang).is_none()));
+    }
+}
@@ -0,0 +1,100 @@
+/*
+   DeepSAFL - power schedules
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+// How much havoc a seed gets, following the AFLFast schedules. AFLFast divides by
+// the number of times the seed's path was hit; that count is not tracked yet, so
+// the schedules only depend on how often the seed has been fuzzed.
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::input_seed::InputSeed;
+
+//perf_score is never more than this many times the normal one
+pub const MAX_FACTOR: u64 = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Schedule {
+    //the same energy for every seed
+    #[default]
+    Explore,
+    //the most energy for every seed
+    Exploit,
+    //doubles each time the seed is fuzzed
+    Fast,
+    //grows linearly with the times the seed is fuzzed
+    Lin,
+    //grows quadratically with the times the seed is fuzzed
+    Quad,
+}
+
+impl Schedule {
+    //Energy of input_seed in percent of the normal amount of havoc
+    pub fn perf_score(&self, input_seed: &InputSeed)->u64 {
+        let fuzz_level = input_seed.fuzz_level as u64;
+        let factor = match *self {
+            Schedule::Explore => 1,
+            Schedule::Exploit => MAX_FACTOR,
+            Schedule::Fast => 1u64.checked_shl(fuzz_level as u32).unwrap_or(MAX_FACTOR),
+            Schedule::Lin => fuzz_level,
+            Schedule::Quad => fuzz_level.saturating_mul(fuzz_level),
+        };
+        100 * factor.clamp(1, MAX_FACTOR)
+    }
+
+    pub fn name(&self)->&'static str {
+        match *self {
+            Schedule::Explore => "explore",
+            Schedule::Exploit => "exploit",
+            Schedule::Fast => "fast",
+            Schedule::Lin => "lin",
+            Schedule::Quad => "quad",
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(name: &str)->Result<Schedule, String> {
+        match name {
+            "explore" => Ok(Schedule::Explore),
+            "exploit" => Ok(Schedule::Exploit),
+            "fast" => Ok(Schedule::Fast),
+            "lin" => Ok(Schedule::Lin),
+            "quad" => Ok(Schedule::Quad),
+            _ => Err(format!("unknown schedule '{}'", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn energy_grows_with_fuzz_level() {
+        let mut input_seed = InputSeed::new(vec![1, 2, 3]);
+        assert_eq!(Schedule::Explore.perf_score(&input_seed), 100);
+        assert_eq!(Schedule::Fast.perf_score(&input_seed), 100);
+        input_seed.fuzz_level = 3;
+        assert_eq!(Schedule::Explore.perf_score(&input_seed), 100);
+        assert_eq!(Schedule::Fast.perf_score(&input_seed), 800);
+        assert_eq!(Schedule::Lin.perf_score(&input_seed), 300);
+        assert_eq!(Schedule::Quad.perf_score(&input_seed), 900);
+        input_seed.fuzz_level = 100;
+        assert_eq!(Schedule::Fast.perf_score(&input_seed), 100 * MAX_FACTOR);
+        assert_eq!(Schedule::Quad.perf_score(&input_seed), 100 * MAX_FACTOR);
+    }
+}
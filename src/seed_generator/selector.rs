@@ -1,6 +1,5 @@
 use super::config;
 use super::input_seed;
-use rand;
 use rand::Rng;
 
 #[derive(Debug)]
@@ -10,8 +9,8 @@ pub struct Selector{
 
 impl Selector {
 
-    pub fn whether_select(&self, seed:& input_seed::InputSeed)->bool {
-        let probability = rand::thread_rng().gen_range(0, 100);
+    pub fn whether_select<R: Rng>(&self, seed:& input_seed::InputSeed, rang:&mut R)->bool {
+        let probability = rang.gen_range(0, 100);
         if (seed.was_fuzzed || !seed.is_favored) &&  (probability < config::SKIP_TO_NEW_PROB) {
             return false;
         } 
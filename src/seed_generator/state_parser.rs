@@ -6,16 +6,25 @@
    Copyright 2018. All rights reserved.
 */
 
+use std::cmp;
+use std::mem;
 use super::config;
-use rand;
+use super::profile::SeedProfile;
 use rand::Rng;
 
 #[derive(PartialEq)]
 #[derive(Debug)]
+#[derive(Default)]
 pub enum FuzzingState{
+          #[default]
           Ready,
           Select,
+          //the seed is trimmed before the deterministic stages
+          Trim,
           CalHavocTimes,
+          //the comparisons of the seed are logged before the input-to-state stage
+          CmpLog,
+          StateCmpLog(u64),
           StateFlip1(u64),
           StateFlip2(u64),
           StateFlip4(u64),
@@ -37,12 +46,185 @@ pub enum FuzzingState{
           StateInterest16AnotherEndian((u64,u8)),
           StateInterest32((u64,u8)),
           StateInterest32AnotherEndian((u64,u8)),
+          StateExtrasOverwrite((u64,u32)),
+          StateExtrasInsert((u64,u32)),
           StateHavoc((u64,u8)),
           End
 }
 
-impl Default for FuzzingState {
-    fn default() -> FuzzingState { FuzzingState::Ready }
+impl FuzzingState {
+    //The stage a mutating state belongs to, None for the bookkeeping states
+    pub fn stage(&self)->Option<Stage> {
+        match *self {
+            FuzzingState::StateCmpLog(_) => Some(Stage::CmpLog),
+            FuzzingState::StateFlip1(_) | FuzzingState::StateFlip2(_) | FuzzingState::StateFlip4(_) |
+            FuzzingState::StateFlip8(_) | FuzzingState::StateFlip16(_) | FuzzingState::StateFlip32(_) => Some(Stage::Flip),
+            FuzzingState::StateAddArith8(_) | FuzzingState::StateSubArith8(_) |
+            FuzzingState::StateAddArith16(_) | FuzzingState::StateSubArith16(_) |
+            FuzzingState::StateAddArith16AnotherEndian(_) | FuzzingState::StateSubArith16AnotherEndian(_) |
+            FuzzingState::StateAddArith32(_) | FuzzingState::StateSubArith32(_) |
+            FuzzingState::StateAddArith32AnotherEndian(_) | FuzzingState::StateSubArith32AnotherEndian(_) => Some(Stage::Arith),
+            FuzzingState::StateInterest8(_) | FuzzingState::StateInterest16(_) | FuzzingState::StateInterest16AnotherEndian(_) |
+            FuzzingState::StateInterest32(_) | FuzzingState::StateInterest32AnotherEndian(_) => Some(Stage::Interest),
+            FuzzingState::StateExtrasOverwrite(_) | FuzzingState::StateExtrasInsert(_) => Some(Stage::Extras),
+            FuzzingState::StateHavoc(_) => Some(Stage::Havoc),
+            _ => None,
+        }
+    }
+
+    //Name of the variant without its parameters, e.g. "StateInterest32AnotherEndian"
+    pub fn family(&self)->&'static str {
+        match *self {
+            FuzzingState::Ready => "Ready",
+            FuzzingState::Select => "Select",
+            FuzzingState::Trim => "Trim",
+            FuzzingState::CalHavocTimes => "CalHavocTimes",
+            FuzzingState::CmpLog => "CmpLog",
+            FuzzingState::StateCmpLog(_) => "StateCmpLog",
+            FuzzingState::StateFlip1(_) => "StateFlip1",
+            FuzzingState::StateFlip2(_) => "StateFlip2",
+            FuzzingState::StateFlip4(_) => "StateFlip4",
+            FuzzingState::StateFlip8(_) => "StateFlip8",
+            FuzzingState::StateFlip16(_) => "StateFlip16",
+            FuzzingState::StateFlip32(_) => "StateFlip32",
+            FuzzingState::StateAddArith8(_) => "StateAddArith8",
+            FuzzingState::StateSubArith8(_) => "StateSubArith8",
+            FuzzingState::StateAddArith16(_) => "StateAddArith16",
+            FuzzingState::StateSubArith16(_) => "StateSubArith16",
+            FuzzingState::StateAddArith16AnotherEndian(_) => "StateAddArith16AnotherEndian",
+            FuzzingState::StateSubArith16AnotherEndian(_) => "StateSubArith16AnotherEndian",
+            FuzzingState::StateAddArith32(_) => "StateAddArith32",
+            FuzzingState::StateSubArith32(_) => "StateSubArith32",
+            FuzzingState::StateAddArith32AnotherEndian(_) => "StateAddArith32AnotherEndian",
+            FuzzingState::StateSubArith32AnotherEndian(_) => "StateSubArith32AnotherEndian",
+            FuzzingState::StateInterest8(_) => "StateInterest8",
+            FuzzingState::StateInterest16(_) => "StateInterest16",
+            FuzzingState::StateInterest16AnotherEndian(_) => "StateInterest16AnotherEndian",
+            FuzzingState::StateInterest32(_) => "StateInterest32",
+            FuzzingState::StateInterest32AnotherEndian(_) => "StateInterest32AnotherEndian",
+            FuzzingState::StateExtrasOverwrite(_) => "StateExtrasOverwrite",
+            FuzzingState::StateExtrasInsert(_) => "StateExtrasInsert",
+            FuzzingState::StateHavoc(_) => "StateHavoc",
+            FuzzingState::End => "End",
+        }
+    }
+
+    //Short name of the state in the style of the afl-fuzz status screen
+    pub fn name(&self)->&'static str {
+        match *self {
+            FuzzingState::Ready => "ready",
+            FuzzingState::Select => "select",
+            FuzzingState::Trim => "trim",
+            FuzzingState::CalHavocTimes => "havoc setup",
+            FuzzingState::CmpLog => "cmplog",
+            FuzzingState::StateCmpLog(_) => "input-to-state",
+            FuzzingState::StateFlip1(_) => "bitflip 1/1",
+            FuzzingState::StateFlip2(_) => "bitflip 2/1",
+            FuzzingState::StateFlip4(_) => "bitflip 4/1",
+            FuzzingState::StateFlip8(_) => "bitflip 8/8",
+            FuzzingState::StateFlip16(_) => "bitflip 16/8",
+            FuzzingState::StateFlip32(_) => "bitflip 32/8",
+            FuzzingState::StateAddArith8(_) | FuzzingState::StateSubArith8(_) => "arith 8/8",
+            FuzzingState::StateAddArith16(_) | FuzzingState::StateSubArith16(_) |
+            FuzzingState::StateAddArith16AnotherEndian(_) | FuzzingState::StateSubArith16AnotherEndian(_) => "arith 16/8",
+            FuzzingState::StateAddArith32(_) | FuzzingState::StateSubArith32(_) |
+            FuzzingState::StateAddArith32AnotherEndian(_) | FuzzingState::StateSubArith32AnotherEndian(_) => "arith 32/8",
+            FuzzingState::StateInterest8(_) => "interest 8/8",
+            FuzzingState::StateInterest16(_) | FuzzingState::StateInterest16AnotherEndian(_) => "interest 16/8",
+            FuzzingState::StateInterest32(_) | FuzzingState::StateInterest32AnotherEndian(_) => "interest 32/8",
+            FuzzingState::StateExtrasOverwrite(_) => "user extras (over)",
+            FuzzingState::StateExtrasInsert(_) => "user extras (insert)",
+            FuzzingState::StateHavoc(_) => "havoc",
+            FuzzingState::End => "end",
+        }
+    }
+}
+
+//The groups of states, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    //the input-to-state replacements, skipped when there are no comparisons
+    CmpLog,
+    Flip,
+    Arith,
+    Interest,
+    //the dictionary tokens, skipped when there is no dictionary
+    Extras,
+    Havoc,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 6] = [Stage::CmpLog, Stage::Flip, Stage::Arith, Stage::Interest, Stage::Extras, Stage::Havoc];
+
+    pub fn name(&self)->&'static str {
+        match *self {
+            Stage::CmpLog => "cmplog",
+            Stage::Flip => "flip",
+            Stage::Arith => "arith",
+            Stage::Interest => "interest",
+            Stage::Extras => "extras",
+            Stage::Havoc => "havoc",
+        }
+    }
+}
+
+//Which stages the state parser goes through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stages {
+    pub cmplog: bool,
+    pub flip: bool,
+    pub arith: bool,
+    pub interest: bool,
+    pub extras: bool,
+    pub havoc: bool,
+}
+
+impl Default for Stages {
+    fn default()->Stages {
+        Stages::all()
+    }
+}
+
+impl Stages {
+    pub fn all()->Stages {
+        Stages { cmplog: true, flip: true, arith: true, interest: true, extras: true, havoc: true }
+    }
+
+    //Skip the deterministic stages, like afl-fuzz -d
+    pub fn havoc_only()->Stages {
+        Stages { cmplog: false, flip: false, arith: false, interest: false, extras: false, havoc: true }
+    }
+
+    pub fn contains(&self, stage: Stage)->bool {
+        match stage {
+            Stage::CmpLog => self.cmplog,
+            Stage::Flip => self.flip,
+            Stage::Arith => self.arith,
+            Stage::Interest => self.interest,
+            Stage::Extras => self.extras,
+            Stage::Havoc => self.havoc,
+        }
+    }
+
+    //A comma separated list of stage names, e.g. "flip,havoc"
+    pub fn parse(stage_list: &str)->Result<Stages, String> {
+        let mut stages = Stages { cmplog: false, flip: false, arith: false, interest: false, extras: false, havoc: false };
+        for name in stage_list.split(',').map(|name| name.trim()).filter(|name| !name.is_empty()) {
+            match Stage::ALL.iter().find(|stage| stage.name() == name) {
+                Some(&Stage::CmpLog) => stages.cmplog = true,
+                Some(&Stage::Flip) => stages.flip = true,
+                Some(&Stage::Arith) => stages.arith = true,
+                Some(&Stage::Interest) => stages.interest = true,
+                Some(&Stage::Extras) => stages.extras = true,
+                Some(&Stage::Havoc) => stages.havoc = true,
+                None => return Err(format!("unknown stage '{}'", name)),
+            }
+        }
+        if stages == (Stages { cmplog: false, flip: false, arith: false, interest: false, extras: false, havoc: false }) {
+            return Err("no stage selected".to_string());
+        }
+        Ok(stages)
+    }
 }
 
 
@@ -53,6 +235,15 @@ pub struct StateParser{
     mutate_state: FuzzingState,
     havoc_outer_times: u64,
     havoc_inner_times: u8,
+    stages: Stages,
+    //number of dictionary tokens
+    extras_cnt: u32,
+    //number of input-to-state replacements of the current seed
+    cmp_cnt: u64,
+    //energy of the current seed in percent, scales the number of havoc rounds
+    perf_score: u64,
+    //the inferred fields of the current seed
+    profile: SeedProfile,
 }
 
 impl StateParser {
@@ -62,9 +253,89 @@ impl StateParser {
             mutate_state:FuzzingState::Ready,
             havoc_outer_times: 0,
             havoc_inner_times: 0,
+            stages: Stages::all(),
+            extras_cnt: 0,
+            cmp_cnt: 0,
+            perf_score: 100,
+            profile: SeedProfile::default(),
+        }
+    }
+
+    pub fn set_stages(&mut self, stages: Stages) {
+        self.stages = stages;
+    }
+
+    pub fn set_extras_cnt(&mut self, extras_cnt: u32) {
+        self.extras_cnt = extras_cnt;
+    }
+
+    //Set for every seed in the CmpLog state
+    pub fn set_cmp_cnt(&mut self, cmp_cnt: u64) {
+        self.cmp_cnt = cmp_cnt;
+    }
+
+    //Set for every seed when it is selected, 100 is the normal amount of havoc
+    pub fn set_perf_score(&mut self, perf_score: u64) {
+        self.perf_score = perf_score;
+    }
+
+    //Set for every seed, the 16 and 32 bit arith and interest stages skip the
+    //places the profile does not allow
+    pub fn set_profile(&mut self, profile: SeedProfile) {
+        self.profile = profile;
+    }
+
+    pub fn profile(&self)->&SeedProfile {
+        &self.profile
+    }
+
+    //Whether the profile allows the integer that state changes, if it is one
+    fn profile_allows(&self, state: &FuzzingState)->bool {
+        let (i, width, big_endian) = match *state {
+            FuzzingState::StateAddArith16((i,_)) | FuzzingState::StateSubArith16((i,_)) => (i, 2, true),
+            FuzzingState::StateAddArith16AnotherEndian((i,_)) | FuzzingState::StateSubArith16AnotherEndian((i,_)) => (i, 2, false),
+            FuzzingState::StateAddArith32((i,_)) | FuzzingState::StateSubArith32((i,_)) => (i, 4, true),
+            FuzzingState::StateAddArith32AnotherEndian((i,_)) | FuzzingState::StateSubArith32AnotherEndian((i,_)) => (i, 4, false),
+            FuzzingState::StateInterest16((i,_)) => (i, 2, true),
+            FuzzingState::StateInterest16AnotherEndian((i,_)) => (i, 2, false),
+            FuzzingState::StateInterest32((i,_)) => (i, 4, true),
+            FuzzingState::StateInterest32AnotherEndian((i,_)) => (i, 4, false),
+            _ => return true,
+        };
+        self.profile.allows(i, width, big_endian)
+    }
+
+    //The state of the last input
+    pub fn current_state(&self)->&FuzzingState {
+        &self.mutate_state
+    }
+
+    //How far the current stage is: (done, total), in bytes of the seed for the
+    //deterministic stages and in havoc rounds for havoc
+    pub fn progress(&self)->(u64, u64) {
+        match self.mutate_state {
+            FuzzingState::StateCmpLog(i) => (i, self.cmp_cnt),
+            FuzzingState::StateFlip1(i) | FuzzingState::StateFlip2(i) | FuzzingState::StateFlip4(i) => (i >> 3, self.seed_len),
+            FuzzingState::StateFlip8(i) | FuzzingState::StateFlip16(i) | FuzzingState::StateFlip32(i) => (i, self.seed_len),
+            FuzzingState::StateAddArith8((i,_)) | FuzzingState::StateSubArith8((i,_)) |
+            FuzzingState::StateAddArith16((i,_)) | FuzzingState::StateSubArith16((i,_)) |
+            FuzzingState::StateAddArith16AnotherEndian((i,_)) | FuzzingState::StateSubArith16AnotherEndian((i,_)) |
+            FuzzingState::StateAddArith32((i,_)) | FuzzingState::StateSubArith32((i,_)) |
+            FuzzingState::StateAddArith32AnotherEndian((i,_)) | FuzzingState::StateSubArith32AnotherEndian((i,_)) |
+            FuzzingState::StateInterest8((i,_)) | FuzzingState::StateInterest16((i,_)) |
+            FuzzingState::StateInterest16AnotherEndian((i,_)) | FuzzingState::StateInterest32((i,_)) |
+            FuzzingState::StateInterest32AnotherEndian((i,_)) => (i, self.seed_len),
+            FuzzingState::StateExtrasOverwrite((i,_)) | FuzzingState::StateExtrasInsert((i,_)) => (i, self.seed_len + 1),
+            FuzzingState::StateHavoc((outer_count,_)) => (outer_count, self.havoc_outer_times),
+            _ => (0, 0),
         }
     }
 
+    //The seed got shorter in the Trim state, the stages after it go over the new length
+    pub fn set_trimmed_len(&mut self, trimmed_len:u64) {
+        self.seed_len = trimmed_len;
+    }
+
     fn set_seed_len(&mut self, input_seed_len:u64) {
         self.seed_len = input_seed_len;
     }
@@ -75,27 +346,39 @@ impl StateParser {
     //Attention: In the initial developing stage, we focus on how to mutate the seed,
     //so we just use a constant number to replace the calculating result,
     //in future we may use a structure to represent the seed with its information and other things
-    fn calculate_havoc_outer_times(&mut self){
-        self.havoc_outer_times = 1+rand::thread_rng().gen_range(0, config::HAVOC_CYCLES_INIT as u64);
+    fn calculate_havoc_outer_times<R: Rng>(&mut self, rang:&mut R){
+        let havoc_max = cmp::max(1, config::HAVOC_CYCLES_INIT as u64 * self.perf_score / 100);
+        self.havoc_outer_times = 1+rang.gen_range(0, havoc_max);
     }
 
-    fn calculate_havoc_inner_times(&mut self){
-        self.havoc_inner_times = 1 << (1+rand::thread_rng().gen_range(0, config::HAVOC_STACK_POW2));
+    fn calculate_havoc_inner_times<R: Rng>(&mut self, rang:&mut R){
+        self.havoc_inner_times = 1 << (1+rang.gen_range(0, config::HAVOC_STACK_POW2));
     }
 
     fn state_select_next(&self, len:u64)->FuzzingState {
         if len == 0 {
           return FuzzingState::End;
         }
-        FuzzingState::CalHavocTimes
+        FuzzingState::Trim
     }
 
     fn state_cal_havoc_next(&self)->FuzzingState {
+      if self.stages.cmplog {
+        return FuzzingState::CmpLog;
+      }
       FuzzingState::StateFlip1(0)
       //just for test, we should use the last line
       //FuzzingState::StateInterest8((0,0))
     }
 
+    //the input-to-state stage runs only when there are replacements
+    fn state_cmplog_next(&self, now_count:u64)->FuzzingState {
+        if now_count < self.cmp_cnt {
+          return FuzzingState::StateCmpLog(now_count);
+        }
+        FuzzingState::StateFlip1(0)
+    }
+
     fn state_flip1_next(&self, len:u64, now_count:u64)->FuzzingState {
         let state_count = (len << 3)-1;
         if now_count < state_count {
@@ -348,7 +631,7 @@ impl StateParser {
     fn state_interesting8_next(&self, len:u64, now_count:u64, index_count:u8)->FuzzingState {
         let state_count = len;
         if now_count < state_count {
-          if index_count < (config::INTERESTING_8_CNT-1) as u8 {
+          if index_count < (config::INTERESTING_8_CNT-1) {
             return FuzzingState::StateInterest8((now_count, index_count+1));
           } 
           else {
@@ -356,7 +639,7 @@ impl StateParser {
               // we have doing all things, let's go to next out State
               //the length of the input is not enough for two bytes
               if len < 2 {
-                return FuzzingState::StateHavoc((0,0));
+                return self.state_extras_first();
               }
               return FuzzingState::StateInterest16((0,0));
             }
@@ -370,7 +653,7 @@ impl StateParser {
     fn state_interesting16_next(&self, len:u64, now_count:u64, index_count:u8)->FuzzingState {
         let state_count = len-1;
         if now_count < state_count {
-          if index_count < (config::INTERESTING_16_CNT-1) as u8 {
+          if index_count < (config::INTERESTING_16_CNT-1) {
             return FuzzingState::StateInterest16((now_count, index_count+1));
           } 
           else {
@@ -388,14 +671,14 @@ impl StateParser {
     fn state_interesting16_another_endian_next(&self, len:u64, now_count:u64, index_count:u8)->FuzzingState {
       let state_count = len-1;
         if now_count < state_count {
-          if index_count < (config::INTERESTING_16_CNT-1) as u8 {
+          if index_count < (config::INTERESTING_16_CNT-1) {
             return FuzzingState::StateInterest16AnotherEndian((now_count, index_count+1));
           } 
           else {
             if now_count == state_count-1 {
               // we have doing all things, let's go to next out State
               if len < 4 {
-                return FuzzingState::StateHavoc((0,0));
+                return self.state_extras_first();
               }
 
               return FuzzingState::StateInterest32((0,0));
@@ -410,7 +693,7 @@ impl StateParser {
     fn state_interesting32_next(&self, len:u64, now_count:u64, index_count:u8)->FuzzingState {
         let state_count = len-3;
         if now_count < state_count {
-          if index_count < (config::INTERESTING_32_CNT-1) as u8 {
+          if index_count < (config::INTERESTING_32_CNT-1) {
             return FuzzingState::StateInterest32((now_count, index_count+1));
           } 
           else {
@@ -428,13 +711,13 @@ impl StateParser {
     fn state_interesting32_another_endian_next(&self, len:u64, now_count:u64, index_count:u8)->FuzzingState {
         let state_count = len-3;
         if now_count < state_count {
-          if index_count < (config::INTERESTING_32_CNT-1) as u8 {
+          if index_count < (config::INTERESTING_32_CNT-1) {
             return FuzzingState::StateInterest32AnotherEndian((now_count, index_count+1));
           } 
           else {
             if now_count == state_count-1 {
               // we have doing all things, let's go to next out State
-              return FuzzingState::StateHavoc((0,0));
+              return self.state_extras_first();
 
               //just for a simple test to skip havoc
               //return FuzzingState::End;
@@ -446,6 +729,60 @@ impl StateParser {
         FuzzingState::End    
     }
 
+    //the dictionary stages run only when there are tokens
+    fn state_extras_first(&self)->FuzzingState {
+        if self.extras_cnt > 0 {
+          return FuzzingState::StateExtrasOverwrite((0,0));
+        }
+        FuzzingState::StateHavoc((0,0))
+    }
+
+    fn state_extras_overwrite_next(&self, len:u64, now_count:u64, extra_index:u32)->FuzzingState {
+        if extra_index + 1 < self.extras_cnt {
+          return FuzzingState::StateExtrasOverwrite((now_count, extra_index+1));
+        }
+        if now_count + 1 < len {
+          return FuzzingState::StateExtrasOverwrite((now_count+1, 0));
+        }
+        FuzzingState::StateExtrasInsert((0,0))
+    }
+
+    //tokens are inserted in front of every byte and at the end
+    fn state_extras_insert_next(&self, len:u64, now_count:u64, extra_index:u32)->FuzzingState {
+        if extra_index + 1 < self.extras_cnt {
+          return FuzzingState::StateExtrasInsert((now_count, extra_index+1));
+        }
+        if now_count < len {
+          return FuzzingState::StateExtrasInsert((now_count+1, 0));
+        }
+        FuzzingState::StateHavoc((0,0))
+    }
+
+    //Jump over the stages that are not enabled
+    fn skip_disabled_stages(&self, mut next_state: FuzzingState)->FuzzingState {
+        loop {
+            let stage = match next_state.stage() {
+                Some(stage) => stage,
+                None => return next_state,
+            };
+            if stage == Stage::Extras && self.extras_cnt == 0 {
+                next_state = FuzzingState::StateHavoc((0,0));
+                continue;
+            }
+            if self.stages.contains(stage) {
+                return next_state;
+            }
+            next_state = match stage {
+                Stage::CmpLog => FuzzingState::StateFlip1(0),
+                Stage::Flip => FuzzingState::StateAddArith8((0,0)),
+                Stage::Arith => FuzzingState::StateInterest8((0,0)),
+                Stage::Interest => self.state_extras_first(),
+                Stage::Extras => FuzzingState::StateHavoc((0,0)),
+                Stage::Havoc => FuzzingState::End,
+            };
+        }
+    }
+
     fn state_havoc_next(&self, outer_count:u64, inner_count:u8)->FuzzingState {
         if outer_count < self.havoc_outer_times {
           if inner_count < self.havoc_inner_times{
@@ -463,116 +800,149 @@ impl StateParser {
         FuzzingState::End
     }
 
-    pub fn get_next_mutate_state(&mut self, input_seed_len:u64)->FuzzingState {
-        match self.mutate_state {
+    pub fn get_next_mutate_state<R: Rng>(&mut self, input_seed_len:u64, rang:&mut R)->FuzzingState {
+        let mut next_state = self.state_after(input_seed_len, rang);
+        if self.profile_allows(&next_state) {
+            return next_state;
+        }
+        //go past the integers the profile rules out, the last input keeps its state
+        let current_state = mem::replace(&mut self.mutate_state, next_state);
+        loop {
+            next_state = self.state_after(input_seed_len, rang);
+            if self.profile_allows(&next_state) {
+                break;
+            }
+            self.mutate_state = next_state;
+        }
+        self.mutate_state = current_state;
+        next_state
+    }
+
+    fn state_after<R: Rng>(&mut self, input_seed_len:u64, rang:&mut R)->FuzzingState {
+        let next_state = match self.mutate_state {
             //最初始状态，更新存储状态，进入选择状态
             FuzzingState::Ready => {
                 self.set_seed_len(input_seed_len);
-                return FuzzingState::Select
+                FuzzingState::Select
             },
             FuzzingState::Select => {
-                return self.state_select_next(self.seed_len)
+                self.state_select_next(self.seed_len)
+            },
+            FuzzingState::Trim => {
+                FuzzingState::CalHavocTimes
             },
             FuzzingState::CalHavocTimes => {
-                self.calculate_havoc_outer_times();
-                self.calculate_havoc_inner_times();
-                return self.state_cal_havoc_next()
+                self.calculate_havoc_outer_times(rang);
+                self.calculate_havoc_inner_times(rang);
+                self.state_cal_havoc_next()
+            },
+            FuzzingState::CmpLog => {
+                self.state_cmplog_next(0)
+            },
+            FuzzingState::StateCmpLog(i) => {
+                self.state_cmplog_next(i+1)
             },
             FuzzingState::StateFlip1(i) => {
                 // println!("we are now in the state {:?}",self.mutate_state);
-                return self.state_flip1_next(self.seed_len, i)
+                self.state_flip1_next(self.seed_len, i)
             },
             FuzzingState::StateFlip2(i) => {
                 // println!("we are now in the state {:?}",self.mutate_state);
-                return self.state_flip2_next(self.seed_len, i)
+                self.state_flip2_next(self.seed_len, i)
             },
             FuzzingState::StateFlip4(i) => {
                 // println!("we are now in the state {:?}",self.mutate_state);
-                return self.state_flip4_next(self.seed_len, i)
+                self.state_flip4_next(self.seed_len, i)
             },
             FuzzingState::StateFlip8(i) => {
                 // println!("we are now in the state {:?}",self.mutate_state);
-                return self.state_flip8_next(self.seed_len, i)
+                self.state_flip8_next(self.seed_len, i)
             },
             FuzzingState::StateFlip16(i) => {
                 // println!("we are now in the state {:?}",self.mutate_state);
-                return self.state_flip16_next(self.seed_len, i)
+                self.state_flip16_next(self.seed_len, i)
             },
             FuzzingState::StateFlip32(i) => {
                 // println!("we are now in the state {:?}",self.mutate_state);
-                return self.state_flip32_next(self.seed_len, i)
+                self.state_flip32_next(self.seed_len, i)
             },
             FuzzingState::StateAddArith8((i,arith_j)) => {
                 // println!("we are now in the state {:?}",self.mutate_state);
-                return self.state_arith8_add_next(self.seed_len, i, arith_j)
+                self.state_arith8_add_next(self.seed_len, i, arith_j)
             },
             FuzzingState::StateSubArith8((i,arith_j)) => {
                 // println!("we are now in the state {:?}",self.mutate_state);
-                return self.state_arith8_sub_next(self.seed_len, i, arith_j)
+                self.state_arith8_sub_next(self.seed_len, i, arith_j)
             },
             FuzzingState::StateAddArith16((i,arith_j)) => {
                 // println!("we are now in the state {:?}",self.mutate_state);
-                return self.state_arith16_add_next(self.seed_len, i, arith_j)
+                self.state_arith16_add_next(self.seed_len, i, arith_j)
             },
             FuzzingState::StateSubArith16((i,arith_j)) => {
                 // println!("we are now in the state {:?}",self.mutate_state);
-                return self.state_arith16_sub_next(self.seed_len, i, arith_j)
+                self.state_arith16_sub_next(self.seed_len, i, arith_j)
             },
             FuzzingState::StateAddArith16AnotherEndian((i,arith_j)) => {
                 // println!("we are now in the state {:?}",self.mutate_state);
-                return self.state_arith16_add_another_endian_next(self.seed_len, i, arith_j)
+                self.state_arith16_add_another_endian_next(self.seed_len, i, arith_j)
             },
             FuzzingState::StateSubArith16AnotherEndian((i,arith_j)) => {
                 // println!("we are now in the state {:?}",self.mutate_state);
-                return self.state_arith16_sub_another_endian_next(self.seed_len, i, arith_j)
+                self.state_arith16_sub_another_endian_next(self.seed_len, i, arith_j)
             },
             FuzzingState::StateAddArith32((i,arith_j)) => {
                 // println!("we are now in the state {:?}",self.mutate_state);
-                return self.state_arith32_add_next(self.seed_len, i, arith_j)
+                self.state_arith32_add_next(self.seed_len, i, arith_j)
             },
             FuzzingState::StateSubArith32((i,arith_j)) => {
                 // println!("we are now in the state {:?}",self.mutate_state);
-                return self.state_arith32_sub_next(self.seed_len, i, arith_j)
+                self.state_arith32_sub_next(self.seed_len, i, arith_j)
             },
             FuzzingState::StateAddArith32AnotherEndian((i,arith_j)) => {
                 // println!("we are now in the state {:?}",self.mutate_state);
-                return self.state_arith32_add_another_endian_next(self.seed_len, i, arith_j)
+                self.state_arith32_add_another_endian_next(self.seed_len, i, arith_j)
             },
             FuzzingState::StateSubArith32AnotherEndian((i,arith_j)) => {
                 // println!("we are now in the state {:?}",self.mutate_state);
-                return self.state_arith32_sub_another_endian_next(self.seed_len, i, arith_j)
+                self.state_arith32_sub_another_endian_next(self.seed_len, i, arith_j)
             },
             FuzzingState::StateInterest8((i,index_count)) => {
                 // println!("we are now in the state {:?}",self.mutate_state);
-                return self.state_interesting8_next(self.seed_len, i, index_count)
+                self.state_interesting8_next(self.seed_len, i, index_count)
             },
             FuzzingState::StateInterest16((i,index_count)) => {
                 // println!("we are now in the state {:?}",self.mutate_state);
-                return self.state_interesting16_next(self.seed_len, i, index_count)
+                self.state_interesting16_next(self.seed_len, i, index_count)
             },
             FuzzingState::StateInterest16AnotherEndian((i, index_count)) => {
-                return self.state_interesting16_another_endian_next(self.seed_len, i, index_count)
+                self.state_interesting16_another_endian_next(self.seed_len, i, index_count)
             },
             FuzzingState::StateInterest32((i,index_count)) => {
                 // println!("we are now in the state {:?}",self.mutate_state);
-                return self.state_interesting32_next(self.seed_len, i, index_count)
+                self.state_interesting32_next(self.seed_len, i, index_count)
             },
             FuzzingState::StateInterest32AnotherEndian((i,index_count)) => {
                 // println!("we are now in the state {:?}",self.mutate_state);
-                return self.state_interesting32_another_endian_next(self.seed_len, i, index_count)
+                self.state_interesting32_another_endian_next(self.seed_len, i, index_count)
+            },
+            FuzzingState::StateExtrasOverwrite((i,extra_index)) => {
+                self.state_extras_overwrite_next(self.seed_len, i, extra_index)
+            },
+            FuzzingState::StateExtrasInsert((i,extra_index)) => {
+                self.state_extras_insert_next(self.seed_len, i, extra_index)
             },
             FuzzingState::StateHavoc((outer_cnt,inner_cnt)) => {
                 // println!("we are now in the state {:?}",self.mutate_state);
-                return self.state_havoc_next(outer_cnt,inner_cnt)
+                self.state_havoc_next(outer_cnt,inner_cnt)
             },
-            _=> return FuzzingState::Ready,
-        }
-      
+            _=> FuzzingState::Ready,
+        };
+        self.skip_disabled_stages(next_state)
     }
 
     pub fn change_to_next_state(&mut self, next_state: FuzzingState) {
         self.mutate_state = next_state;
-        println!("We just finish the state {:?}", self.mutate_state);
+        trace!("finished state {:?}", self.mutate_state);
         //To do: update next internal state;
     }
 
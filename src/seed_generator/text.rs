@@ -0,0 +1,194 @@
+/*
+   DeepSAFL - text mutations
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+// Bit flips, binary arithmetic and INTERESTING_* values seldom turn text into other
+// valid text: a flipped digit is no longer a number, a 32 bit value in the middle of
+// a word makes no token. In text mode the havoc of a seed that is mostly printable
+// also picks operations that work on the text: numbers are replaced by boundary
+// numbers, separators are inserted and deleted, lines are duplicated, words swapped
+// and their case changed. These never fail on an input of one byte or more, an
+// operation that finds nothing to work on leaves the input alone.
+
+use std::ops::Range;
+
+use rand::Rng;
+
+use super::config;
+
+pub(crate) fn is_printable(byte: u8)->bool {
+    (0x20..0x7f).contains(&byte) || byte == b'\t' || byte == b'\n' || byte == b'\r'
+}
+
+//Whether seed is mostly printable, see config::TEXT_MIN_PRINTABLE
+pub fn is_text(seed: &[u8])->bool {
+    !seed.is_empty() && seed.iter().filter(|&&byte| is_printable(byte)).count() as f64 >= config::TEXT_MIN_PRINTABLE * seed.len() as f64
+}
+
+fn is_word(byte: u8)->bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+//The maximal runs of bytes for which is_part holds
+fn runs<F: Fn(&[u8], usize)->bool>(buf: &[u8], is_part: F)->Vec<Range<usize>> {
+    let mut runs = Vec::new();
+    let mut pos = 0;
+    while pos < buf.len() {
+        if !is_part(buf, pos) {
+            pos += 1;
+            continue;
+        }
+        let start = pos;
+        while pos < buf.len() && is_part(buf, pos) {
+            pos += 1;
+        }
+        runs.push(start..pos);
+    }
+    runs
+}
+
+//Numbers with their sign
+fn numbers(buf: &[u8])->Vec<Range<usize>> {
+    runs(buf, |buf, pos| buf[pos].is_ascii_digit() || (buf[pos] == b'-' && buf.get(pos + 1).is_some_and(|byte| byte.is_ascii_digit())))
+}
+
+fn words(buf: &[u8])->Vec<Range<usize>> {
+    runs(buf, |buf, pos| is_word(buf[pos]))
+}
+
+//Replace range by bytes, unless the input would grow beyond config::MAX_FILE
+fn replace(buf: &mut Vec<u8>, range: Range<usize>, bytes: &[u8]) {
+    if buf.len() - range.len() + bytes.len() <= config::MAX_FILE as usize {
+        buf.splice(range, bytes.iter().cloned());
+    }
+}
+
+//Write a boundary number over a number of the text, or insert it if there is none
+pub fn replace_number<R: Rng>(buf: &mut Vec<u8>, rang: &mut R) {
+    let number = config::TEXT_NUMBERS[rang.gen_range(0, config::TEXT_NUMBERS.len())].as_bytes();
+    let numbers = numbers(buf);
+    let range = if numbers.is_empty() {
+        let pos = rang.gen_range(0, buf.len() + 1);
+        pos..pos
+    }
+    else {
+        numbers[rang.gen_range(0, numbers.len())].clone()
+    };
+    replace(buf, range, number);
+}
+
+pub fn insert_separator<R: Rng>(buf: &mut Vec<u8>, rang: &mut R) {
+    let separator = config::TEXT_SEPARATORS[rang.gen_range(0, config::TEXT_SEPARATORS.len())];
+    let pos = rang.gen_range(0, buf.len() + 1);
+    replace(buf, pos..pos, &[separator]);
+}
+
+pub fn delete_separator<R: Rng>(buf: &mut Vec<u8>, rang: &mut R) {
+    let separators: Vec<usize> = (0..buf.len()).filter(|&pos| config::TEXT_SEPARATORS.contains(&buf[pos])).collect();
+    //keep one byte at least
+    if !separators.is_empty() && buf.len() > 1 {
+        buf.remove(separators[rang.gen_range(0, separators.len())]);
+    }
+}
+
+//Insert a copy of a line after it
+pub fn duplicate_line<R: Rng>(buf: &mut Vec<u8>, rang: &mut R) {
+    let lines: Vec<Range<usize>> = runs(buf, |buf, pos| buf[pos] != b'\n').into_iter()
+        .map(|line| line.start..(line.end + 1).min(buf.len()))
+        .collect();
+    if lines.is_empty() {
+        return;
+    }
+    let line = lines[rang.gen_range(0, lines.len())].clone();
+    let mut copy = buf[line.clone()].to_vec();
+    if copy.last() != Some(&b'\n') {
+        //the last line has no end of line
+        copy.insert(0, b'\n');
+    }
+    replace(buf, line.end..line.end, &copy);
+}
+
+//Swap two different words
+pub fn swap_words<R: Rng>(buf: &mut Vec<u8>, rang: &mut R) {
+    let words = words(buf);
+    if words.len() < 2 {
+        return;
+    }
+    let first = rang.gen_range(0, words.len());
+    let second = (first + rang.gen_range(1, words.len())) % words.len();
+    let (first, second) = (words[first.min(second)].clone(), words[first.max(second)].clone());
+    let mut swapped = buf[second.clone()].to_vec();
+    swapped.extend_from_slice(&buf[first.end..second.start]);
+    swapped.extend_from_slice(&buf[first.clone()]);
+    buf.splice(first.start..second.end, swapped);
+}
+
+//Upper case, lower case or capitalize a word
+pub fn change_case<R: Rng>(buf: &mut [u8], rang: &mut R) {
+    let words = words(buf);
+    if words.is_empty() {
+        return;
+    }
+    let word = &mut buf[words[rang.gen_range(0, words.len())].clone()];
+    match rang.gen_range(0, 3) {
+        0 => word.make_ascii_uppercase(),
+        1 => word.make_ascii_lowercase(),
+        _ => {
+            word.make_ascii_lowercase();
+            word[0] = word[0].to_ascii_uppercase();
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{SeedableRng, StdRng};
+
+    #[test]
+    fn finds_the_text_and_its_parts() {
+        assert!(is_text(b"GET /index.html HTTP/1.1\r\n"));
+        assert!(!is_text(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR") && !is_text(b""));
+        assert_eq!(numbers(b"a=-12, b=3-x"), vec![2..5, 9..10]);
+        assert_eq!(words(b"foo bar_1 (baz)"), vec![0..3, 4..9, 11..14]);
+    }
+
+    #[test]
+    fn text_mutations_keep_the_text() {
+        let mut rang = StdRng::from_seed(&[1usize, 2][..]);
+        for _ in 0..100 {
+            let mut buf = b"x = 42;\ny = foo(x)".to_vec();
+            replace_number(&mut buf, &mut rang);
+            let text = String::from_utf8(buf).unwrap();
+            assert!(text.starts_with("x = ") && text.ends_with(";\ny = foo(x)"));
+            assert!(config::TEXT_NUMBERS.contains(&&text[4..text.len() - 12]));
+
+            let mut buf = b"a\nb\nc".to_vec();
+            duplicate_line(&mut buf, &mut rang);
+            assert!([&b"a\na\nb\nc"[..], b"a\nb\nb\nc", b"a\nb\nc\nc"].contains(&&buf[..]));
+
+            let mut buf = b"one two".to_vec();
+            swap_words(&mut buf, &mut rang);
+            assert_eq!(buf, b"two one");
+
+            let mut buf = b"hello".to_vec();
+            change_case(&mut buf, &mut rang);
+            assert!([&b"HELLO"[..], b"hello", b"Hello"].contains(&&buf[..]));
+
+            let mut buf = b"a,b".to_vec();
+            delete_separator(&mut buf, &mut rang);
+            assert_eq!(buf, b"ab");
+            insert_separator(&mut buf, &mut rang);
+            assert_eq!(buf.len(), 3);
+        }
+        //nothing to work on
+        let mut buf = b"x".to_vec();
+        delete_separator(&mut buf, &mut rang);
+        swap_words(&mut buf, &mut rang);
+        assert_eq!(buf, b"x");
+    }
+}
@@ -1,21 +1,33 @@
-use super::input_seed;
 use input_seed::InputSeed;
+use calibrate::Calibration;
 
 #[derive(Debug)]
 pub struct SeedPool {
     seed_pool: Vec<InputSeed>,
     seed_index: usize,
+    //times seed_index went back to the first seed
+    cycles_done: u64,
+    //id of the next seed pushed
+    next_id: usize,
 }
 
 impl SeedPool {
-    pub fn new(path: &str)->SeedPool {
-        let seed1 = InputSeed::new(vec![40u8,32u8]);
-        let seed2 = InputSeed::new(vec![40u8,32u8]);
-        SeedPool {
-            seed_pool:vec![seed1, seed2],
-            seed_index:0,
+    pub fn new(_path: &str)->SeedPool {
+        SeedPool::from_seeds(vec![vec![40u8,32u8], vec![40u8,32u8]])
+    }
+    pub fn from_seeds(seeds: Vec<Vec<u8>>)->SeedPool {
+        let mut seed_pool = SeedPool {
+            seed_pool: Vec::with_capacity(seeds.len()),
+            seed_index: 0,
+            cycles_done: 0,
+            next_id: 0,
+        };
+        for seed_vec in seeds {
+            seed_pool.push(InputSeed::new(seed_vec));
         }
+        seed_pool
     }
+
     pub fn get_a_ini_seed(&mut self)->Option<&InputSeed> {
         let seed = self.seed_pool.get(self.seed_index);
         self.seed_index += 1;
@@ -25,21 +37,134 @@ impl SeedPool {
         seed
     }
 
+    pub fn len(&self)->usize {
+        self.seed_pool.len()
+    }
+
+    pub fn get(&self, index:usize)->Option<&InputSeed> {
+        self.seed_pool.get(index)
+    }
+
+    //The seed with this id, if it is still in the pool
+    pub fn get_by_id(&self, id:usize)->Option<&InputSeed> {
+        //ids grow with the index
+        self.index_of(id).map(|index| &self.seed_pool[index])
+    }
+
+    //Index of the seed with this id, if it is still in the pool
+    pub fn index_of(&self, id:usize)->Option<usize> {
+        self.seed_pool.binary_search_by_key(&id, |seed| seed.id).ok()
+    }
+
+    //id, the id of its parent, of the parent's parent... as far as they are in the pool
+    pub fn parent_chain(&self, id:usize)->Vec<usize> {
+        let mut chain = Vec::new();
+        let mut next = Some(id);
+        while let Some(seed) = next.and_then(|id| self.get_by_id(id)) {
+            chain.push(seed.id);
+            next = seed.parent;
+        }
+        chain
+    }
+
+    pub fn set_origin(&mut self, index:usize, parent:Option<usize>, mutation:Option<String>) {
+        let seed = &mut self.seed_pool[index];
+        seed.parent = parent;
+        seed.mutation = mutation;
+    }
+
     pub fn seed_index_move(&mut self) {
         if self.seed_index + 1 == self.seed_pool.len() {
             self.seed_index = 0;
+            self.cycles_done += 1;
         }
         else {
            self.seed_index += 1;
         }
     }
 
-    pub fn get_a_seed_to_mutate(&mut self)->InputSeed {
-        self.seed_pool[self.seed_index].clone()
+    pub fn seed_index(&self)->usize {
+        self.seed_index
+    }
+
+    pub fn cycles_done(&self)->u64 {
+        self.cycles_done
+    }
+
+    //Seeds that were never picked for fuzzing
+    pub fn pending_cnt(&self)->usize {
+        self.seed_pool.iter().filter(|seed| !seed.was_fuzzed).count()
+    }
+
+    pub fn favored_cnt(&self)->usize {
+        self.seed_pool.iter().filter(|seed| seed.is_favored).count()
+    }
+
+    //Favored seeds that were never picked for fuzzing
+    pub fn pending_favored_cnt(&self)->usize {
+        self.seed_pool.iter().filter(|seed| seed.is_favored && !seed.was_fuzzed).count()
+    }
+
+    pub fn get_a_seed_to_mutate(&self)->&InputSeed {
+        &self.seed_pool[self.seed_index]
+    }
+
+    //The seed at seed_index has been picked for fuzzing again
+    pub fn mark_fuzzed(&mut self) {
+        let index = self.seed_index;
+        self.mark_fuzzed_at(index);
+    }
+
+    pub fn mark_fuzzed_at(&mut self, index:usize) {
+        let seed = &mut self.seed_pool[index];
+        seed.was_fuzzed = true;
+        seed.fuzz_level += 1;
+    }
+
+    //Keep only the seeds at these indexes (in increasing order), and start over at the first one
+    pub fn retain_indexes(&mut self, keep: &[usize]) {
+        let mut keep = keep.iter().peekable();
+        let mut index = 0;
+        self.seed_pool.retain(|_| {
+            let is_kept = keep.peek() == Some(&&index);
+            if is_kept {
+                keep.next();
+            }
+            index += 1;
+            is_kept
+        });
+        self.seed_index = 0;
+    }
+
+    //The seed at seed_index after trimming
+    pub fn replace_trimmed(&mut self, seed_vec: Vec<u8>) {
+        let seed = &mut self.seed_pool[self.seed_index];
+        seed.seed_vec = seed_vec;
+        seed.trim_done = true;
+    }
+
+    pub fn set_calibration(&mut self, index: usize, calibration: Calibration) {
+        let seed = &mut self.seed_pool[index];
+        seed.exec_us = calibration.exec_us;
+        seed.bitmap_size = calibration.bitmap_size;
+        seed.var_edges = calibration.var_edges;
+        seed.cal_done = true;
+    }
+
+    pub fn push_a_trimmed_seed(&mut self, seed_vec: Vec<u8>) {
+        let mut seed_to_push = InputSeed::new(seed_vec);
+        seed_to_push.trim_done = true;
+        self.push(seed_to_push);
     }
 
     pub fn push_a_seed(&mut self, seed_vec: Vec<u8>) {
         let seed_to_push = InputSeed::new(seed_vec);
-        self.seed_pool.push(seed_to_push);
+        self.push(seed_to_push);
+    }
+
+    fn push(&mut self, mut seed: InputSeed) {
+        seed.id = self.next_id;
+        self.next_id += 1;
+        self.seed_pool.push(seed);
     }
 }
\ No newline at end of file
@@ -0,0 +1,309 @@
+/*
+   DeepSAFL - seed management shared by several fuzzing threads
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+
+use config;
+use feedback::{ExecInfo, ExecStatus};
+use input_seed::InputSeed;
+use seed_generator::{Mutation, SeedGenerator};
+use seed_pool::SeedPool;
+use NoSeedsError;
+
+//The part of SeedManagement that all the threads see: the seed pool and the covered map.
+//Each thread fuzzes through its own SeedWorker.
+#[derive(Debug)]
+pub struct SharedSeedManagement {
+    seed_pool: RwLock<SeedPool>,
+    covered_bit_map: Vec<AtomicBool>,
+    ini_seed_cnt: usize,
+    //the next initial seed to hand out unmutated
+    next_ini_seed: AtomicUsize,
+    //workers take seeds to mutate in turn, so they work on different ones
+    next_seed_index: AtomicUsize,
+}
+
+impl SharedSeedManagement {
+    pub fn new(path:& str)->SharedSeedManagement {
+        SharedSeedManagement::with_seed_pool(SeedPool::new(path))
+    }
+
+    //Share these seeds instead of the default ones
+    pub fn with_seeds(seeds: Vec<Vec<u8>>)->Result<SharedSeedManagement, NoSeedsError> {
+        if seeds.is_empty() {
+            return Err(NoSeedsError);
+        }
+        Ok(SharedSeedManagement::with_seed_pool(SeedPool::from_seeds(seeds)))
+    }
+
+    fn with_seed_pool(seed_pool: SeedPool)->SharedSeedManagement {
+        SharedSeedManagement {
+            ini_seed_cnt: seed_pool.len(),
+            seed_pool: RwLock::new(seed_pool),
+            covered_bit_map: (0..config::MAP_SIZE).map(|_| AtomicBool::new(false)).collect(),
+            next_ini_seed: AtomicUsize::new(0),
+            next_seed_index: AtomicUsize::new(0),
+        }
+    }
+
+    //Number of seeds in the pool
+    pub fn len(&self)->usize {
+        self.seed_pool.read().unwrap_or_else(PoisonError::into_inner).len()
+    }
+
+    pub fn is_empty(&self)->bool {
+        self.len() == 0
+    }
+
+    //Number of edges covered by all the workers so far
+    pub fn covered_edges(&self)->usize {
+        self.covered_bit_map.iter().filter(|covered| covered.load(Ordering::Relaxed)).count()
+    }
+
+    fn get_a_ini_seed(&self)->Option<InputSeed> {
+        if self.next_ini_seed.load(Ordering::Relaxed) >= self.ini_seed_cnt {
+            return None;
+        }
+        let index = self.next_ini_seed.fetch_add(1, Ordering::Relaxed);
+        if index >= self.ini_seed_cnt {
+            return None;
+        }
+        self.seed_pool.read().unwrap_or_else(PoisonError::into_inner).get(index).cloned()
+    }
+
+    //The seed to mutate next with its index
+    fn get_a_seed_to_mutate(&self)->(usize, InputSeed) {
+        let seed_pool = self.seed_pool.read().unwrap_or_else(PoisonError::into_inner);
+        let index = self.next_seed_index.fetch_add(1, Ordering::Relaxed) % seed_pool.len();
+        (index, seed_pool.get(index).unwrap().clone())
+    }
+
+    //The seed at index has been picked for fuzzing by a worker
+    fn mark_fuzzed(&self, index:usize) {
+        self.seed_pool.write().unwrap_or_else(PoisonError::into_inner).mark_fuzzed_at(index);
+    }
+
+    //Mark the edges of cov_info as covered. Only one worker gets true for an edge.
+    fn has_new_bits(&self, cov_info:&[u32; config::MAP_SIZE])->bool {
+        let mut is_changed: bool = false;
+        for (covered, cov) in self.covered_bit_map.iter().zip(cov_info.iter()) {
+            if *cov != 0 && !covered.load(Ordering::Relaxed) && !covered.swap(true, Ordering::Relaxed) {
+                is_changed = true;
+            }
+        }
+        is_changed
+    }
+
+    fn push_a_seed(&self, seed_vec: Vec<u8>) {
+        self.seed_pool.write().unwrap_or_else(PoisonError::into_inner).push_a_seed(seed_vec);
+    }
+}
+
+//The per-thread part: its own generator (and so its own RNG) and the seed it is mutating
+#[derive(Debug)]
+pub struct SeedWorker {
+    shared: Arc<SharedSeedManagement>,
+    seed_generator: SeedGenerator,
+    //a copy of the pool entry and its index, kept until the generator is done with it
+    seed_to_mutate: Option<(usize, InputSeed)>,
+    seed_current: Vec<u8>,
+    seed_current_in_generator: bool,
+    //the last input is an initial seed, which is in the pool already
+    seed_current_is_ini: bool,
+}
+
+impl SeedWorker {
+    pub fn new(shared: Arc<SharedSeedManagement>)->SeedWorker {
+        SeedWorker::with_generator(shared, SeedGenerator::new())
+    }
+
+    pub fn with_rng_seed(shared: Arc<SharedSeedManagement>, rng_seed:u64)->SeedWorker {
+        SeedWorker::with_generator(shared, SeedGenerator::with_rng_seed(rng_seed))
+    }
+
+    fn with_generator(shared: Arc<SharedSeedManagement>, seed_generator: SeedGenerator)->SeedWorker {
+        SeedWorker {
+            shared,
+            seed_generator,
+            seed_to_mutate: None,
+            seed_current: Vec::new(),
+            seed_current_in_generator: false,
+            seed_current_is_ini: false,
+        }
+    }
+
+    pub fn shared(&self)->&Arc<SharedSeedManagement> {
+        &self.shared
+    }
+
+    pub fn get_a_seed(&mut self)->Vec<u8> {
+        self.get_a_seed_ref().to_vec()
+    }
+
+    //The input, repaired by the post processors of the generator, is valid until the next call
+    pub fn get_a_seed_ref(&mut self)->&[u8] {
+        if let Some(seed) = self.shared.get_a_ini_seed() {
+            self.seed_current = seed.seed_vec;
+            self.seed_current_in_generator = false;
+            self.seed_current_is_ini = true;
+            return self.seed_generator.post_process(&self.seed_current);
+        }
+        self.seed_current_is_ini = false;
+        let mut failed_seeds = 0;
+        loop {
+            if self.seed_to_mutate.is_none() {
+                self.seed_to_mutate = Some(self.shared.get_a_seed_to_mutate());
+            }
+            let (index, seed_to_mutate) = self.seed_to_mutate.as_ref().unwrap();
+            let mutation = self.seed_generator.mutate_in_place(seed_to_mutate);
+            if self.seed_generator.started_new_seed() {
+                self.shared.mark_fuzzed(*index);
+            }
+            match mutation {
+                Ok(Mutation::Mutated) => {
+                    self.seed_current_in_generator = true;
+                    return self.seed_generator.post_process_current();
+                },
+                Ok(Mutation::NextSeed) => {},
+                //the pool is shared, a worker does not replace its seeds
+                Ok(Mutation::Trim) => {
+                    self.seed_generator.seed_trimmed(seed_to_mutate.get_seed_slice());
+                    continue;
+                },
+                //nor does it log comparisons
+                Ok(Mutation::CmpLog) => {
+                    self.seed_generator.seed_cmp_log(&[]);
+                    continue;
+                },
+                Err(_) => {
+                    failed_seeds += 1;
+                    //no seed in the pool can be mutated, hand out the original one
+                    if failed_seeds >= self.shared.len() {
+                        self.seed_current.clear();
+                        self.seed_current.extend_from_slice(seed_to_mutate.get_seed_slice());
+                        self.seed_current_in_generator = false;
+                        self.seed_to_mutate = None;
+                        return self.seed_generator.post_process(&self.seed_current);
+                    }
+                },
+            }
+            self.seed_to_mutate = None;
+        }
+    }
+
+    fn current_seed(&self)->&[u8] {
+        if self.seed_current_in_generator {
+            self.seed_generator.get_current_seed()
+        }
+        else {
+            &self.seed_current
+        }
+    }
+
+    //Coverage and outcome of the last input. Returns true if it went fine, found new
+    //edges and went into the shared pool.
+    pub fn give_coverage_info(&mut self, cov_info:&[u32; config::MAP_SIZE], exec_info:&ExecInfo)->bool {
+        if exec_info.status != ExecStatus::Normal {
+            return false;
+        }
+        //the initial seeds count their edges, they are in the pool already
+        if self.shared.has_new_bits(cov_info) && !self.seed_current_is_ini {
+            let seed_vec = self.current_seed().to_vec();
+            self.shared.push_a_seed(seed_vec);
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn workers_share_pool_and_coverage() {
+        let shared = Arc::new(SharedSeedManagement::new("124"));
+        let pool_len = shared.len();
+        //the initial seeds run before the threads start, they cover edge 0
+        let mut cov_info = [0u32; config::MAP_SIZE];
+        cov_info[0] = 1;
+        let mut first = SeedWorker::new(shared.clone());
+        for _ in 0..pool_len {
+            first.get_a_seed();
+            assert!(!first.give_coverage_info(&cov_info, &ExecInfo::default()));
+        }
+        let handles: Vec<_> = (0..4).map(|worker_id| {
+            let mut worker = SeedWorker::with_rng_seed(shared.clone(), worker_id as u64);
+            thread::spawn(move || {
+                let mut cov_info = [0u32; config::MAP_SIZE];
+                for i in 0..200 {
+                    let seed = worker.get_a_seed();
+                    assert!(!seed.is_empty());
+                    //edge 0 is found by everybody, edge 1+worker_id only by this worker
+                    cov_info[0] = 1;
+                    cov_info[1 + worker_id] = if i == 100 { 1 } else { 0 };
+                    worker.give_coverage_info(&cov_info, &ExecInfo::default());
+                }
+            })
+        }).collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        //edge 0 is known already, one seed for each worker's own edge
+        assert_eq!(shared.len(), pool_len + 4);
+        assert_eq!(shared.covered_edges(), 5);
+        //the workers took turns at mutating the initial seeds
+        let seed_pool = shared.seed_pool.read().unwrap();
+        assert!((0..pool_len).all(|index| seed_pool.get(index).unwrap().was_fuzzed));
+    }
+
+    #[test]
+    fn crashes_and_hangs_stay_out_of_the_pool() {
+        let shared = Arc::new(SharedSeedManagement::new("124"));
+        let pool_len = shared.len();
+        let mut worker = SeedWorker::with_rng_seed(shared.clone(), 1);
+        for _ in 0..pool_len {
+            worker.get_a_seed();
+        }
+        let mut cov_info = [0u32; config::MAP_SIZE];
+        worker.get_a_seed();
+        cov_info[1] = 1;
+        assert!(!worker.give_coverage_info(&cov_info, &ExecInfo::new(ExecStatus::Crash, 0)));
+        cov_info[2] = 1;
+        assert!(!worker.give_coverage_info(&cov_info, &ExecInfo::new(ExecStatus::Timeout, 0)));
+        assert!(worker.give_coverage_info(&cov_info, &ExecInfo::default()));
+        assert_eq!(shared.len(), pool_len + 1);
+    }
+
+    #[test]
+    fn workers_share_the_given_seeds() {
+        assert_eq!(SharedSeedManagement::with_seeds(Vec::new()).err(), Some(NoSeedsError));
+        let shared = Arc::new(SharedSeedManagement::with_seeds(vec![b"abc".to_vec()]).unwrap());
+        let mut worker = SeedWorker::with_rng_seed(shared.clone(), 1);
+        assert_eq!(worker.get_a_seed(), b"abc");
+        assert_eq!(worker.get_a_seed().len(), 3);
+    }
+
+    #[test]
+    fn initial_seeds_are_handed_out_once() {
+        let shared = Arc::new(SharedSeedManagement::new("124"));
+        let ini_seeds: Vec<Vec<u8>> = (0..shared.len())
+            .map(|index| shared.seed_pool.read().unwrap().get(index).unwrap().seed_vec.clone())
+            .collect();
+        let mut first = SeedWorker::new(shared.clone());
+        for ini_seed in ini_seeds.iter() {
+            assert_eq!(&first.get_a_seed(), ini_seed);
+        }
+        //the other worker starts mutating right away
+        let mut second = SeedWorker::new(shared.clone());
+        assert!(shared.get_a_ini_seed().is_none());
+        assert!(!second.get_a_seed().is_empty());
+    }
+}
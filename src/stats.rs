@@ -0,0 +1,295 @@
+/*
+   DeepSAFL - fuzzer statistics
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+// The fuzzer_stats and plot_data files of afl-fuzz, so afl-whatsup, afl-plot and
+// the scripts built on them work on our output directory as well.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use config;
+use feedback::ExecStatus;
+use output::OutputDir;
+
+//What the inputs of one stage cost and what they found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Yield {
+    pub execs: u64,
+    //inputs that went into the pool
+    pub seeds: u64,
+    //runs that crashed or timed out, not only the unique ones
+    pub crashes: u64,
+    pub hangs: u64,
+}
+
+impl Yield {
+    fn record(&mut self, status: ExecStatus, is_kept: bool) {
+        self.execs += 1;
+        if is_kept {
+            self.seeds += 1;
+        }
+        match status {
+            ExecStatus::Crash => self.crashes += 1,
+            ExecStatus::Timeout => self.hangs += 1,
+            ExecStatus::Normal => {},
+        }
+    }
+}
+
+//The fuzzing strategy yields of afl-fuzz, for each state of the generator
+//(e.g. "StateInterest32AnotherEndian") and each havoc operation (e.g. "flip8")
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StageYields {
+    pub states: BTreeMap<&'static str, Yield>,
+    pub havoc_ops: BTreeMap<&'static str, Yield>,
+}
+
+impl StageYields {
+    //Count a run of an input made by state with the havoc operations havoc_ops
+    pub fn record(&mut self, state: &'static str, havoc_ops: &[&'static str], status: ExecStatus, is_kept: bool) {
+        self.states.entry(state).or_default().record(status, is_kept);
+        for op in havoc_ops.iter() {
+            self.havoc_ops.entry(op).or_default().record(status, is_kept);
+        }
+    }
+}
+
+//A snapshot of SeedManagement, see SeedManagement::stats
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FuzzStats {
+    pub execs_done: u64,
+    pub paths_total: usize,
+    pub paths_favored: usize,
+    //found by this instance, not the initial seeds or imports
+    pub paths_found: usize,
+    pub paths_imported: usize,
+    //never picked for fuzzing
+    pub pending_total: usize,
+    //favored and never picked for fuzzing. There is no favored culling yet, every
+    //seed is favored, so this is pending_total and paths_favored is paths_total.
+    pub pending_favs: usize,
+    pub cycles_done: u64,
+    //index of the seed being fuzzed
+    pub cur_path: usize,
+    pub stage: &'static str,
+    pub stage_cur: u64,
+    pub stage_max: u64,
+    pub edges_found: usize,
+    //covered edges that vary between runs of the same input, see calibrate
+    pub var_edges: usize,
+    pub last_path: Option<SystemTime>,
+    pub yields: StageYields,
+}
+
+impl FuzzStats {
+    //Percent of the coverage map that is set
+    pub fn bitmap_cvg(&self)->f64 {
+        self.edges_found as f64 * 100.0 / config::MAP_SIZE as f64
+    }
+
+    //Percent of the covered edges that behave the same in every run
+    pub fn stability(&self)->f64 {
+        if self.edges_found == 0 {
+            return 100.0;
+        }
+        (self.edges_found - self.var_edges) as f64 * 100.0 / self.edges_found as f64
+    }
+}
+
+//afl-fuzz writes 0 for things that never happened
+fn unix_time(time: Option<SystemTime>)->u64 {
+    time.and_then(|time| time.duration_since(UNIX_EPOCH).ok()).map_or(0, |since_epoch| since_epoch.as_secs())
+}
+
+//"1 days, 2 hrs, 3 min, 4 sec", or "none yet"
+fn format_since(since: Option<Duration>)->String {
+    match since {
+        Some(since) => {
+            let secs = since.as_secs();
+            format!("{} days, {} hrs, {} min, {} sec", secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60)
+        },
+        None => "none yet".to_string(),
+    }
+}
+
+#[derive(Debug)]
+pub struct StatsFile {
+    out_dir: PathBuf,
+    start: Instant,
+    start_time: SystemTime,
+    command_line: String,
+    exec_timeout: u64,
+    //execs and time of the last update, the speed is measured between updates
+    last_execs: u64,
+    last_update: Instant,
+    execs_per_sec: f64,
+}
+
+impl StatsFile {
+    //Start the stats of out, plot_data gets its header
+    pub fn create(out: &OutputDir, command_line: &str, exec_timeout: u64)->io::Result<StatsFile> {
+        let mut plot_data = File::create(out.out_dir().join("plot_data"))?;
+        writeln!(plot_data, "# unix_time, cycles_done, cur_path, paths_total, pending_total, pending_favs, \
+                             map_size, unique_crashes, unique_hangs, max_depth, execs_per_sec")?;
+        let now = Instant::now();
+        Ok(StatsFile {
+            out_dir: out.out_dir().to_path_buf(),
+            start: now,
+            start_time: SystemTime::now(),
+            command_line: command_line.to_string(),
+            exec_timeout,
+            last_execs: 0,
+            last_update: now,
+            execs_per_sec: 0.0,
+        })
+    }
+
+    pub fn run_time(&self)->Duration {
+        self.start.elapsed()
+    }
+
+    pub fn execs_per_sec(&self)->f64 {
+        self.execs_per_sec
+    }
+
+    //Measure the speed since the last call
+    pub fn update_speed(&mut self, stats: &FuzzStats) {
+        let secs = self.last_update.elapsed().as_secs_f64();
+        if secs > 0.0 {
+            self.execs_per_sec = (stats.execs_done - self.last_execs) as f64 / secs;
+            self.last_execs = stats.execs_done;
+            self.last_update = Instant::now();
+        }
+    }
+
+    //Rewrite fuzzer_stats; a reader never sees a half written file
+    pub fn write_fuzzer_stats(&self, stats: &FuzzStats, out: &OutputDir)->io::Result<()> {
+        let lines: Vec<(&str, String)> = vec![
+            ("start_time", unix_time(Some(self.start_time)).to_string()),
+            ("last_update", unix_time(Some(SystemTime::now())).to_string()),
+            ("fuzzer_pid", process::id().to_string()),
+            ("cycles_done", stats.cycles_done.to_string()),
+            ("execs_done", stats.execs_done.to_string()),
+            ("execs_per_sec", format!("{:.2}", self.execs_per_sec)),
+            ("paths_total", stats.paths_total.to_string()),
+            ("paths_favored", stats.paths_favored.to_string()),
+            ("paths_found", stats.paths_found.to_string()),
+            ("paths_imported", stats.paths_imported.to_string()),
+            ("cur_path", stats.cur_path.to_string()),
+            ("pending_favs", stats.pending_favs.to_string()),
+            ("pending_total", stats.pending_total.to_string()),
+            ("stability", format!("{:.2}%", stats.stability())),
+            ("bitmap_cvg", format!("{:.2}%", stats.bitmap_cvg())),
+            ("unique_crashes", out.unique_crashes().to_string()),
+            ("unique_hangs", out.unique_hangs().to_string()),
+            ("last_path", unix_time(stats.last_path).to_string()),
+            ("last_crash", unix_time(out.last_crash()).to_string()),
+            ("last_hang", unix_time(out.last_hang()).to_string()),
+            ("exec_timeout", self.exec_timeout.to_string()),
+            ("command_line", self.command_line.clone()),
+        ];
+        let mut content = String::new();
+        for (key, value) in lines {
+            content.push_str(&format!("{:<18}: {}\n", key, value));
+        }
+        let tmp_path = self.out_dir.join(".fuzzer_stats_tmp");
+        fs::write(&tmp_path, content)?;
+        fs::rename(tmp_path, self.out_dir.join("fuzzer_stats"))
+    }
+
+    //Add a line to plot_data
+    pub fn append_plot_data(&self, stats: &FuzzStats, out: &OutputDir)->io::Result<()> {
+        let mut plot_data = OpenOptions::new().append(true).open(self.out_dir.join("plot_data"))?;
+        //there is no depth yet, max_depth stays 0
+        writeln!(plot_data, "{}, {}, {}, {}, {}, {}, {:.2}%, {}, {}, 0, {:.2}",
+                 unix_time(Some(SystemTime::now())), stats.cycles_done, stats.cur_path, stats.paths_total,
+                 stats.pending_total, stats.pending_favs, stats.bitmap_cvg(), out.unique_crashes(), out.unique_hangs(),
+                 self.execs_per_sec)
+    }
+
+    //The status screen, one "name : value" per line
+    pub fn status_screen(&self, stats: &FuzzStats, out: &OutputDir)->String {
+        let since = |time: Option<SystemTime>| time.map(|time| time.elapsed().unwrap_or_default());
+        let stage_percent = if stats.stage_max == 0 { 0.0 } else { stats.stage_cur as f64 * 100.0 / stats.stage_max as f64 };
+        let lines: Vec<(&str, String)> = vec![
+            ("run time", format_since(Some(self.run_time()))),
+            ("last new path", format_since(since(stats.last_path))),
+            ("last uniq crash", format_since(since(out.last_crash()))),
+            ("last uniq hang", format_since(since(out.last_hang()))),
+            ("cycles done", stats.cycles_done.to_string()),
+            ("total paths", stats.paths_total.to_string()),
+            ("pending", format!("{} ({} favored)", stats.pending_total, stats.pending_favs)),
+            ("favored paths", stats.paths_favored.to_string()),
+            ("now processing", stats.cur_path.to_string()),
+            ("now trying", stats.stage.to_string()),
+            ("stage progress", format!("{}/{} ({:.2}%)", stats.stage_cur, stats.stage_max, stage_percent)),
+            ("total execs", stats.execs_done.to_string()),
+            ("exec speed", format!("{:.1}/sec", self.execs_per_sec)),
+            ("map density", format!("{} edges ({:.2}%)", stats.edges_found, stats.bitmap_cvg())),
+            ("stability", format!("{:.2}%", stats.stability())),
+            ("uniq crashes", out.unique_crashes().to_string()),
+            ("uniq hangs", out.unique_hangs().to_string()),
+        ];
+        let mut screen = String::new();
+        for (name, value) in lines {
+            screen.push_str(&format!("{:>16} : {}\n", name, value));
+        }
+        screen
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile;
+
+    #[test]
+    fn counts_yields_by_state_and_havoc_operation() {
+        let mut yields = StageYields::default();
+        yields.record("StateFlip1", &[], ExecStatus::Normal, true);
+        yields.record("StateFlip1", &[], ExecStatus::Crash, false);
+        yields.record("StateHavoc", &["delete"], ExecStatus::Timeout, false);
+        assert_eq!(yields.states["StateFlip1"], Yield { execs: 2, seeds: 1, crashes: 1, hangs: 0 });
+        assert_eq!(yields.states["StateHavoc"], Yield { execs: 1, seeds: 0, crashes: 0, hangs: 1 });
+        assert_eq!(yields.havoc_ops["delete"], yields.states["StateHavoc"]);
+        assert_eq!(yields.havoc_ops.len(), 1);
+    }
+
+    #[test]
+    fn writes_afl_stats_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = OutputDir::create(dir.path()).unwrap();
+        let mut stats_file = StatsFile::create(&out, "./target @@", 1000).unwrap();
+        let stats = FuzzStats { execs_done: 10, paths_total: 3, paths_favored: 3, pending_total: 2, pending_favs: 2, edges_found: 655,
+                                var_edges: 131, stage: "havoc", ..FuzzStats::default() };
+        stats_file.update_speed(&stats);
+        stats_file.write_fuzzer_stats(&stats, &out).unwrap();
+        stats_file.append_plot_data(&stats, &out).unwrap();
+
+        let fuzzer_stats = fs::read_to_string(dir.path().join("fuzzer_stats")).unwrap();
+        assert!(fuzzer_stats.contains("execs_done        : 10\n"));
+        assert!(fuzzer_stats.contains("paths_total       : 3\n"));
+        assert!(fuzzer_stats.contains("paths_favored     : 3\n"));
+        assert!(fuzzer_stats.contains("pending_favs      : 2\n"));
+        assert!(fuzzer_stats.contains("bitmap_cvg        : 1.00%\n"));
+        assert!(fuzzer_stats.contains("stability         : 80.00%\n"));
+        assert!(fuzzer_stats.contains("command_line      : ./target @@\n"));
+        let plot_data = fs::read_to_string(dir.path().join("plot_data")).unwrap();
+        let lines: Vec<&str> = plot_data.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("# unix_time"));
+        assert_eq!(lines[1].split(", ").count(), 11);
+        assert_eq!(lines[1].split(", ").nth(5), Some("2"));
+
+        assert!(stats_file.status_screen(&stats, &out).contains("      now trying : havoc\n"));
+    }
+}
@@ -0,0 +1,261 @@
+/*
+   DeepSAFL - corpus synchronisation between fuzzer instances
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+// The layout follows AFL -M/-S: every instance owns <sync_dir>/<sync_id>/ and keeps
+// its interesting inputs in queue/ as files named id:NNNNNN[,...]. An instance reads
+// the queues of all its peers and remembers, in <sync_dir>/<sync_id>/.synced/<peer>,
+// the first id it has not looked at yet.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use config;
+
+//Scan the peers at most this often when using SyncDir::is_due
+pub const SYNC_INTERVAL_SECS: u64 = 30;
+//A queue file changed more recently than this may still be being written,
+//it is left for the next sync
+pub const SYNC_SETTLE_MS: u64 = 1000;
+
+#[derive(Debug)]
+pub struct SyncDir {
+    sync_dir: PathBuf,
+    sync_id: String,
+    //peer name -> first queue id not imported yet
+    peer_progress: HashMap<String, u64>,
+    last_sync: Option<Instant>,
+    interval: Duration,
+    settle_time: Duration,
+}
+
+impl SyncDir {
+    pub fn new<P: AsRef<Path>>(sync_dir: P, sync_id: &str)->SyncDir {
+        SyncDir {
+            sync_dir: sync_dir.as_ref().to_path_buf(),
+            sync_id: sync_id.to_string(),
+            peer_progress: HashMap::new(),
+            last_sync: None,
+            interval: Duration::from_secs(SYNC_INTERVAL_SECS),
+            settle_time: Duration::from_millis(SYNC_SETTLE_MS),
+        }
+    }
+
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    //How long a queue file has to stay unchanged before it is read, see SYNC_SETTLE_MS
+    pub fn set_settle_time(&mut self, settle_time: Duration) {
+        self.settle_time = settle_time;
+    }
+
+    //Whether the file at path was last changed more than settle_time ago
+    fn is_settled(&self, path: &Path)->bool {
+        let modified = match fs::metadata(path).and_then(|metadata| metadata.modified()) {
+            Ok(modified) => modified,
+            //no time to go by, the read tells
+            Err(_) => return true,
+        };
+        //a time in the future counts as settled, a skewed clock must not stop the sync
+        SystemTime::now().duration_since(modified).map_or(true, |age| age >= self.settle_time)
+    }
+
+    //Whether the last scan is older than the interval
+    pub fn is_due(&self)->bool {
+        match self.last_sync {
+            Some(last_sync) => last_sync.elapsed() >= self.interval,
+            None => true,
+        }
+    }
+
+    //The directory of this instance, <sync_dir>/<sync_id>
+    pub fn out_dir(&self)->PathBuf {
+        self.sync_dir.join(&self.sync_id)
+    }
+
+    //First queue id of peer that has not been imported
+    pub fn peer_progress(&self, peer: &str)->u64 {
+        self.peer_progress.get(peer).cloned().unwrap_or(0)
+    }
+
+    fn synced_file(&self, peer: &str)->PathBuf {
+        self.out_dir().join(".synced").join(peer)
+    }
+
+    fn load_progress(&mut self, peer: &str)->u64 {
+        if let Some(next_id) = self.peer_progress.get(peer) {
+            return *next_id;
+        }
+        let next_id = fs::read_to_string(self.synced_file(peer)).ok()
+            .and_then(|content| content.trim().parse().ok())
+            .unwrap_or(0);
+        self.peer_progress.insert(peer.to_string(), next_id);
+        next_id
+    }
+
+    fn save_progress(&mut self, peer: &str, next_id: u64)->io::Result<()> {
+        self.peer_progress.insert(peer.to_string(), next_id);
+        let synced_file = self.synced_file(peer);
+        if let Some(synced_dir) = synced_file.parent() {
+            fs::create_dir_all(synced_dir)?;
+        }
+        fs::write(synced_file, next_id.to_string())
+    }
+
+    //Names of the other instances that have a queue/
+    fn peers(&self)->io::Result<Vec<String>> {
+        let mut peers = Vec::new();
+        for entry in fs::read_dir(&self.sync_dir)? {
+            let entry = entry?;
+            let peer = match entry.file_name().into_string() {
+                Ok(peer) => peer,
+                Err(_) => continue,
+            };
+            if peer == self.sync_id || peer.starts_with('.') {
+                continue;
+            }
+            if entry.path().join("queue").is_dir() {
+                peers.push(peer);
+            }
+        }
+        peers.sort();
+        Ok(peers)
+    }
+
+    //Walk the queues of the peers and call import with the peer and every new input of
+    //it, in id order. Returns the number of inputs import accepted.
+    //The first error of import stops the sync, the input it failed on and the ones after
+    //it are handed out again by the next sync.
+    pub fn sync<F, E>(&mut self, mut import: F)->Result<usize, E>
+        where F: FnMut(&str, &[u8])->Result<bool, E>, E: From<io::Error> {
+        self.last_sync = Some(Instant::now());
+        let mut imported = 0;
+        for peer in self.peers()? {
+            let next_id = self.load_progress(&peer);
+            let mut new_seeds = Vec::new();
+            for entry in fs::read_dir(self.sync_dir.join(&peer).join("queue"))? {
+                let entry = entry?;
+                if let Some(id) = queue_id(&entry.file_name().to_string_lossy()) {
+                    if id >= next_id {
+                        new_seeds.push((id, entry.path()));
+                    }
+                }
+            }
+            new_seeds.sort();
+
+            //the progress only goes past the files that were read, a file that is still
+            //being written or cannot be read yet is tried again by the next sync
+            let mut synced_id = None;
+            for (id, path) in new_seeds {
+                if !self.is_settled(&path) {
+                    break;
+                }
+                let seed_vec = match fs::read(&path) {
+                    Ok(seed_vec) => seed_vec,
+                    Err(_) => break,
+                };
+                if seed_vec.is_empty() || seed_vec.len() as u64 > config::MAX_FILE {
+                    synced_id = Some(id);
+                    continue;
+                }
+                match import(&peer, &seed_vec) {
+                    Ok(is_imported) => {
+                        synced_id = Some(id);
+                        if is_imported {
+                            imported += 1;
+                        }
+                    },
+                    Err(e) => {
+                        if let Some(synced_id) = synced_id {
+                            self.save_progress(&peer, synced_id + 1)?;
+                        }
+                        return Err(e);
+                    },
+                }
+            }
+            if let Some(synced_id) = synced_id {
+                self.save_progress(&peer, synced_id + 1)?;
+            }
+        }
+        Ok(imported)
+    }
+}
+
+//The id of a queue file named id:NNNNNN[,...]
+fn queue_id(file_name: &str)->Option<u64> {
+    if !file_name.starts_with("id:") {
+        return None;
+    }
+    let id = file_name[3..].split(',').next().unwrap_or("");
+    id.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile;
+
+    #[test]
+    fn queue_ids_follow_afl_names() {
+        assert_eq!(queue_id("id:000012,src:000001,op:havoc"), Some(12));
+        assert_eq!(queue_id("id:000003"), Some(3));
+        assert_eq!(queue_id("README.txt"), None);
+        assert_eq!(queue_id("id:abc"), None);
+    }
+
+    #[test]
+    fn progress_stays_before_the_files_that_were_not_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = dir.path().join("fuzzer01").join("queue");
+        fs::create_dir_all(&queue).unwrap();
+        fs::write(queue.join("id:000000"), b"a").unwrap();
+        //a file that cannot be read, and one after it
+        fs::create_dir(queue.join("id:000001")).unwrap();
+        fs::write(queue.join("id:000002"), b"c").unwrap();
+
+        let mut sync = SyncDir::new(dir.path(), "fuzzer02");
+        //the files were just written, they may not be complete
+        assert_eq!(sync.sync(|_, _| Ok::<_, io::Error>(true)).unwrap(), 0);
+        assert_eq!(sync.peer_progress("fuzzer01"), 0);
+
+        sync.set_settle_time(Duration::from_secs(0));
+        let mut seeds = Vec::new();
+        assert_eq!(sync.sync(|_, seed| { seeds.push(seed.to_vec()); Ok::<_, io::Error>(true) }).unwrap(), 1);
+        assert_eq!(sync.peer_progress("fuzzer01"), 1);
+
+        fs::remove_dir(queue.join("id:000001")).unwrap();
+        fs::write(queue.join("id:000001"), b"b").unwrap();
+        assert_eq!(sync.sync(|_, seed| { seeds.push(seed.to_vec()); Ok::<_, io::Error>(true) }).unwrap(), 2);
+        assert_eq!(sync.peer_progress("fuzzer01"), 3);
+        assert_eq!(seeds, vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+    }
+
+    #[test]
+    fn an_import_error_stops_before_the_failed_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = dir.path().join("fuzzer01").join("queue");
+        fs::create_dir_all(&queue).unwrap();
+        for (id, seed) in [b"a", b"b", b"c"].iter().enumerate() {
+            fs::write(queue.join(format!("id:{:06}", id)), seed).unwrap();
+        }
+
+        let mut sync = SyncDir::new(dir.path(), "fuzzer02");
+        sync.set_settle_time(Duration::from_secs(0));
+        let result = sync.sync(|_, seed| if seed == b"b" { Err(io::Error::other("no")) } else { Ok(true) });
+        assert!(result.is_err());
+        assert_eq!(sync.peer_progress("fuzzer01"), 1);
+
+        let mut seeds = Vec::new();
+        assert_eq!(sync.sync(|_, seed| { seeds.push(seed.to_vec()); Ok::<_, io::Error>(true) }).unwrap(), 2);
+        assert_eq!(seeds, vec![b"b".to_vec(), b"c".to_vec()]);
+        assert_eq!(sync.peer_progress("fuzzer01"), 3);
+    }
+}
@@ -0,0 +1,214 @@
+/*
+   DeepSAFL - test case minimisation
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+// Two ways of making an input smaller without changing what it does:
+//
+//   trim:     the trim stage of afl-fuzz, removes blocks as long as the coverage
+//             checksum stays the same. Cheap enough for every new seed.
+//   minimize: afl-tmin, removes blocks down to single bytes and sets bytes to '0'
+//             until nothing changes any more. For crashes and standalone use.
+//
+// A crash only has to keep crashing, a hang only has to keep hanging.
+
+use std::cmp;
+
+use config;
+use feedback::{ExecInfo, ExecStatus};
+
+//What a trimmed input has to keep
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signature {
+    //checksum of the coverage, see coverage_checksum
+    Coverage(u64),
+    Crash,
+    Timeout,
+}
+
+impl Signature {
+    pub fn new(exec_info: &ExecInfo, cov_info: &[u32; config::MAP_SIZE])->Signature {
+        match exec_info.status {
+            ExecStatus::Normal => Signature::Coverage(coverage_checksum(cov_info)),
+            ExecStatus::Crash => Signature::Crash,
+            ExecStatus::Timeout => Signature::Timeout,
+        }
+    }
+}
+
+//Hit counts in the buckets of afl-fuzz: 1, 2, 3, 4-7, 8-15, 16-31, 32-127, 128+
+pub(crate) fn count_class(count: u32)->u8 {
+    match count {
+        0 => 0,
+        1 => 1,
+        2 => 2,
+        3 => 4,
+        4..=7 => 8,
+        8..=15 => 16,
+        16..=31 => 32,
+        32..=127 => 64,
+        _ => 128,
+    }
+}
+
+//FNV-1a of the bucketed hit counts of the covered edges
+pub fn coverage_checksum(cov_info: &[u32; config::MAP_SIZE])->u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for (edge, count) in cov_info.iter().enumerate().filter(|&(_, count)| *count != 0) {
+        for byte in (edge as u32).to_le_bytes().iter().chain(Some(count_class(*count)).iter()) {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+//Runs candidates and compares them to the signature of the original input
+struct Runner<F> {
+    run_target: F,
+    cov_info: Box<[u32; config::MAP_SIZE]>,
+}
+
+impl<F, E> Runner<F> where F: FnMut(&[u8], &mut [u32; config::MAP_SIZE])->Result<ExecInfo, E> {
+    fn new(run_target: F)->Runner<F> {
+        Runner {
+            run_target,
+            cov_info: Box::new([0; config::MAP_SIZE]),
+        }
+    }
+
+    fn signature(&mut self, input: &[u8])->Result<Signature, E> {
+        for cov in self.cov_info.iter_mut() {
+            *cov = 0;
+        }
+        let exec_info = (self.run_target)(input, &mut self.cov_info)?;
+        Ok(Signature::new(&exec_info, &self.cov_info))
+    }
+
+    //Try removing remove_len bytes at every multiple of remove_len, keep what works
+    fn remove_blocks(&mut self, input: &mut Vec<u8>, remove_len: usize, signature: Signature)->Result<bool, E> {
+        let mut is_changed = false;
+        let mut candidate = Vec::with_capacity(input.len());
+        let mut pos = 0;
+        //an input is never trimmed down to nothing
+        while pos < input.len() && input.len() > 1 {
+            let trim_len = cmp::min(remove_len, input.len() - pos);
+            if trim_len == input.len() {
+                break;
+            }
+            candidate.clear();
+            candidate.extend_from_slice(&input[..pos]);
+            candidate.extend_from_slice(&input[pos + trim_len..]);
+            if self.signature(&candidate)? == signature {
+                input.drain(pos..pos + trim_len);
+                is_changed = true;
+            }
+            else {
+                pos += remove_len;
+            }
+        }
+        Ok(is_changed)
+    }
+}
+
+//Remove blocks of input while the coverage checksum (or crash) stays the same
+pub fn trim<F, E>(input: &[u8], run_target: F)->Result<Vec<u8>, E>
+    where F: FnMut(&[u8], &mut [u32; config::MAP_SIZE])->Result<ExecInfo, E> {
+    let mut runner = Runner::new(run_target);
+    let mut trimmed = input.to_vec();
+    if input.len() <= config::TRIM_MIN_BYTES {
+        return Ok(trimmed);
+    }
+    let signature = runner.signature(input)?;
+    let len_p2 = input.len().next_power_of_two();
+    let min_remove_len = cmp::max(len_p2 / config::TRIM_END_STEPS, config::TRIM_MIN_BYTES);
+    let mut remove_len = cmp::max(len_p2 / config::TRIM_START_STEPS, config::TRIM_MIN_BYTES);
+    while remove_len >= min_remove_len {
+        runner.remove_blocks(&mut trimmed, remove_len, signature)?;
+        remove_len >>= 1;
+    }
+    Ok(trimmed)
+}
+
+//Make input as small and as plain as possible while it keeps its signature
+pub fn minimize<F, E>(input: &[u8], run_target: F)->Result<Vec<u8>, E>
+    where F: FnMut(&[u8], &mut [u32; config::MAP_SIZE])->Result<ExecInfo, E> {
+    let mut runner = Runner::new(run_target);
+    let signature = runner.signature(input)?;
+    let mut minimized = input.to_vec();
+    loop {
+        let mut is_changed = false;
+
+        let mut remove_len = cmp::max(minimized.len().next_power_of_two() / config::TRIM_START_STEPS, 1);
+        while remove_len >= 1 {
+            is_changed |= runner.remove_blocks(&mut minimized, remove_len, signature)?;
+            remove_len >>= 1;
+        }
+
+        for i in 0..minimized.len() {
+            if minimized[i] == b'0' {
+                continue;
+            }
+            let orig = minimized[i];
+            minimized[i] = b'0';
+            if runner.signature(&minimized)? == signature {
+                is_changed = true;
+            }
+            else {
+                minimized[i] = orig;
+            }
+        }
+
+        if !is_changed {
+            return Ok(minimized);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //one edge per distinct byte of the input
+    fn distinct_bytes(input: &[u8], cov_info: &mut [u32; config::MAP_SIZE])->Result<ExecInfo, ()> {
+        for &byte in input.iter() {
+            cov_info[byte as usize] = 1;
+        }
+        Ok(ExecInfo::default())
+    }
+
+    #[test]
+    fn trim_keeps_the_coverage() {
+        let input: Vec<u8> = b"abcd".iter().cycle().take(64).cloned().collect();
+        let trimmed = trim(&input, distinct_bytes).unwrap();
+        assert_eq!(trimmed, b"abcd");
+
+        //every byte is needed
+        let input: Vec<u8> = (0..64).collect();
+        assert_eq!(trim(&input, distinct_bytes).unwrap(), input);
+    }
+
+    #[test]
+    fn checksum_uses_hit_count_buckets() {
+        let mut cov_info = [0u32; config::MAP_SIZE];
+        cov_info[7] = 4;
+        let checksum = coverage_checksum(&cov_info);
+        cov_info[7] = 6;
+        assert_eq!(coverage_checksum(&cov_info), checksum);
+        cov_info[7] = 8;
+        assert!(coverage_checksum(&cov_info) != checksum);
+    }
+
+    #[test]
+    fn minimize_keeps_the_crash() {
+        //crashes when the third byte is '!'
+        let crash_at_2 = |input: &[u8], _cov_info: &mut [u32; config::MAP_SIZE]| {
+            let status = if input.get(2) == Some(&b'!') { ExecStatus::Crash } else { ExecStatus::Normal };
+            Ok::<ExecInfo, ()>(ExecInfo::new(status, 0))
+        };
+        assert_eq!(minimize(b"ab!cdefgh", crash_at_2).unwrap(), b"00!");
+    }
+}
@@ -0,0 +1,203 @@
+/*
+   DeepSAFL - crash triage
+   ------------------------------------------------------
+
+   Written and maintained by Liang Jie <liangjie.mailbox.cn@google.com>
+   Copyright 2018. All rights reserved.
+*/
+
+// Crashes are put into buckets in two ways, so one bug gets filed once:
+//
+//   coverage:   the unique crash rule of afl-fuzz. A crash that covers an edge no
+//               earlier crash did opens a bucket; any other crash is counted in the
+//               newest bucket that first covered one of its edges.
+//   stack hash: a string the user computes for a crash (e.g. a hash of the top
+//               frames of the ASan report), one bucket per distinct string.
+//
+// A crash is counted in one bucket of each kind.
+
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+use config;
+use tmin::coverage_checksum;
+
+//Computes the stack hash of a crashing input, None if there is none
+pub type StackHasher = Box<dyn FnMut(&[u8])->Option<String> + Send>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BucketKey {
+    //checksum of the coverage of the first input, see tmin::coverage_checksum
+    Coverage(u64),
+    StackHash(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashBucket {
+    pub key: BucketKey,
+    pub first_input: Vec<u8>,
+    //crashes that fell into this bucket, the first one included
+    pub count: u64,
+    pub first_seen: SystemTime,
+    pub last_seen: SystemTime,
+    //ids of the seed the first input was mutated from, of its parent and so on,
+    //empty if the first input was an initial seed
+    pub parent_chain: Vec<usize>,
+}
+
+//The buckets add_crash opened for a crash, as indexes into CrashTriage::buckets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NewBuckets {
+    //the crash covers an edge no earlier crash did: a unique crash for afl-fuzz
+    pub coverage: Option<usize>,
+    pub stack_hash: Option<usize>,
+}
+
+impl NewBuckets {
+    pub fn is_new(&self)->bool {
+        self.coverage.is_some() || self.stack_hash.is_some()
+    }
+}
+
+#[derive(Debug)]
+pub struct CrashTriage {
+    buckets: Vec<CrashBucket>,
+    by_key: HashMap<BucketKey, usize>,
+    crash_bit_map: Box<[u32; config::MAP_SIZE]>,
+    //the coverage bucket that first covered each edge
+    edge_bucket: Vec<Option<usize>>,
+}
+
+impl Default for CrashTriage {
+    fn default()->CrashTriage {
+        CrashTriage::new()
+    }
+}
+
+impl CrashTriage {
+    pub fn new()->CrashTriage {
+        CrashTriage {
+            buckets: Vec::new(),
+            by_key: HashMap::new(),
+            crash_bit_map: Box::new([0; config::MAP_SIZE]),
+            edge_bucket: vec![None; config::MAP_SIZE],
+        }
+    }
+
+    //Count a crash of input. Returns the buckets it opened.
+    pub fn add_crash(&mut self, input: &[u8], cov_info: &[u32; config::MAP_SIZE], stack_hash: Option<&str>,
+                     parent_chain: &[usize])->NewBuckets {
+        let now = SystemTime::now();
+        let mut new_buckets = NewBuckets::default();
+
+        let new_edges: Vec<usize> = (0..config::MAP_SIZE)
+            .filter(|&edge| cov_info[edge] != 0 && self.crash_bit_map[edge] == 0)
+            .collect();
+        if !new_edges.is_empty() {
+            let index = self.open_bucket(BucketKey::Coverage(coverage_checksum(cov_info)), input, parent_chain, now);
+            for edge in new_edges {
+                self.crash_bit_map[edge] = 1;
+                self.edge_bucket[edge] = Some(index);
+            }
+            new_buckets.coverage = Some(index);
+        }
+        else {
+            let newest = (0..config::MAP_SIZE)
+                .filter(|&edge| cov_info[edge] != 0)
+                .filter_map(|edge| self.edge_bucket[edge])
+                .max();
+            //a crash without any coverage has no coverage bucket
+            if let Some(index) = newest {
+                self.count_crash(index, now);
+            }
+        }
+
+        if let Some(stack_hash) = stack_hash {
+            let key = BucketKey::StackHash(stack_hash.to_string());
+            match self.by_key.get(&key) {
+                Some(&index) => self.count_crash(index, now),
+                None => new_buckets.stack_hash = Some(self.open_bucket(key, input, parent_chain, now)),
+            }
+        }
+        new_buckets
+    }
+
+    fn open_bucket(&mut self, key: BucketKey, input: &[u8], parent_chain: &[usize], now: SystemTime)->usize {
+        let index = self.buckets.len();
+        debug!("new crash bucket {:?}", key);
+        self.by_key.insert(key.clone(), index);
+        self.buckets.push(CrashBucket {
+            key,
+            first_input: input.to_vec(),
+            count: 1,
+            first_seen: now,
+            last_seen: now,
+            parent_chain: parent_chain.to_vec(),
+        });
+        index
+    }
+
+    fn count_crash(&mut self, index: usize, now: SystemTime) {
+        let bucket = &mut self.buckets[index];
+        bucket.count += 1;
+        bucket.last_seen = now;
+    }
+
+    //All the buckets in the order they were opened
+    pub fn buckets(&self)->&[CrashBucket] {
+        &self.buckets
+    }
+
+    pub fn coverage_buckets(&self)->Vec<&CrashBucket> {
+        self.buckets.iter().filter(|bucket| matches!(bucket.key, BucketKey::Coverage(_))).collect()
+    }
+
+    pub fn stack_hash_buckets(&self)->Vec<&CrashBucket> {
+        self.buckets.iter().filter(|bucket| matches!(bucket.key, BucketKey::StackHash(_))).collect()
+    }
+
+    pub fn bucket(&self, key: &BucketKey)->Option<&CrashBucket> {
+        self.by_key.get(key).map(|&index| &self.buckets[index])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coverage(edges: &[usize])->Box<[u32; config::MAP_SIZE]> {
+        let mut cov_info = Box::new([0u32; config::MAP_SIZE]);
+        for &edge in edges.iter() {
+            cov_info[edge] = 1;
+        }
+        cov_info
+    }
+
+    #[test]
+    fn groups_crashes_by_coverage_and_stack_hash() {
+        let mut triage = CrashTriage::new();
+        assert_eq!(triage.add_crash(b"a", &coverage(&[1, 2]), Some("f1"), &[3, 0]),
+                   NewBuckets { coverage: Some(0), stack_hash: Some(1) });
+        assert_eq!(triage.add_crash(b"b", &coverage(&[2, 5]), Some("f1"), &[4]),
+                   NewBuckets { coverage: Some(2), stack_hash: None });
+        //no new edge and a known stack: counted, nothing new
+        assert!(!triage.add_crash(b"c", &coverage(&[1, 5]), Some("f1"), &[]).is_new());
+        assert_eq!(triage.add_crash(b"d", &coverage(&[1]), Some("f2"), &[]),
+                   NewBuckets { coverage: None, stack_hash: Some(3) });
+        assert!(!triage.add_crash(b"e", &coverage(&[2]), None, &[]).is_new());
+
+        let coverage_buckets = triage.coverage_buckets();
+        assert_eq!(coverage_buckets.len(), 2);
+        assert_eq!(coverage_buckets[0].first_input, b"a");
+        assert_eq!(coverage_buckets[0].parent_chain, vec![3, 0]);
+        //"d" and "e" only have edges of the first bucket, "c" has one of the second
+        assert_eq!(coverage_buckets[0].count, 3);
+        assert_eq!(coverage_buckets[1].count, 2);
+
+        let f1 = triage.bucket(&BucketKey::StackHash("f1".to_string())).unwrap();
+        assert_eq!((f1.count, &f1.first_input[..]), (3, &b"a"[..]));
+        assert!(f1.first_seen <= f1.last_seen);
+        assert_eq!(triage.stack_hash_buckets().len(), 2);
+        assert_eq!(triage.buckets().len(), 4);
+    }
+}